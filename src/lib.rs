@@ -7,10 +7,12 @@ extern crate lazy_static;
 #[macro_use]
 extern crate bitflags;
 
-use policydb::{Policy, PolicyReadError, PolicyReader};
+use policydb::{
+    Policy, PolicyPackage, PolicyReadError, PolicyReader, PolicyWriteError, PolicyWriter,
+};
 
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
 
 pub mod policydb;
@@ -22,3 +24,21 @@ pub fn load_policy_from_file<P: AsRef<Path>>(path: P) -> Result<Policy, PolicyRe
 
     policy_reader.read_policy()
 }
+
+pub fn save_policy_to_file<P: AsRef<Path>>(
+    policy: &Policy,
+    path: P,
+) -> Result<(), PolicyWriteError> {
+    let file = File::create(path)?;
+    let policy_writer = PolicyWriter::new(BufWriter::new(file));
+
+    policy_writer.write_policy(policy)?.flush()?;
+
+    Ok(())
+}
+
+pub fn load_package_from_file<P: AsRef<Path>>(path: P) -> Result<PolicyPackage, PolicyReadError> {
+    let file = File::open(path)?;
+
+    PolicyPackage::read(BufReader::new(file))
+}
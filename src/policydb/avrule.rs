@@ -0,0 +1,593 @@
+use croaring::Bitmap;
+use policydb::Feature;
+use policydb::MlsRange;
+use policydb::PolicyObject;
+use policydb::PolicyReadError;
+use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Remap;
+use policydb::RoleSet;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use policydb::TypeSet;
+use std::io::{Read, Write};
+
+pub(crate) mod constants {
+    pub const AVRULE_ALLOWED: u32 = 0x0001;
+    pub const AVRULE_AUDITALLOW: u32 = 0x0002;
+    pub const AVRULE_AUDITDENY: u32 = 0x0004;
+    pub const AVRULE_DONTAUDIT: u32 = 0x0008;
+    pub const AVRULE_NEVERALLOW: u32 = 0x0080;
+    pub const AVRULE_TRANSITION: u32 = 0x0010;
+    pub const AVRULE_MEMBER: u32 = 0x0020;
+    pub const AVRULE_CHANGE: u32 = 0x0040;
+    pub const AVRULE_XPERMS_ALLOWED: u32 = 0x0100;
+    pub const AVRULE_XPERMS_AUDITALLOW: u32 = 0x0200;
+    pub const AVRULE_XPERMS_DONTAUDIT: u32 = 0x0400;
+    pub const AVRULE_XPERMS_NEVERALLOW: u32 = 0x0800;
+
+    pub const RULE_SELF: u32 = 0x0001;
+}
+
+/// The kind of an unexpanded access vector or type rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AvRuleKind {
+    Allow,
+    AuditAllow,
+    AuditDeny,
+    DontAudit,
+    NeverAllow,
+    TypeTransition,
+    TypeMember,
+    TypeChange,
+    AllowXperm,
+    AuditAllowXperm,
+    DontAuditXperm,
+    NeverAllowXperm,
+}
+
+impl AvRuleKind {
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            constants::AVRULE_ALLOWED => Some(AvRuleKind::Allow),
+            constants::AVRULE_AUDITALLOW => Some(AvRuleKind::AuditAllow),
+            constants::AVRULE_AUDITDENY => Some(AvRuleKind::AuditDeny),
+            constants::AVRULE_DONTAUDIT => Some(AvRuleKind::DontAudit),
+            constants::AVRULE_NEVERALLOW => Some(AvRuleKind::NeverAllow),
+            constants::AVRULE_TRANSITION => Some(AvRuleKind::TypeTransition),
+            constants::AVRULE_MEMBER => Some(AvRuleKind::TypeMember),
+            constants::AVRULE_CHANGE => Some(AvRuleKind::TypeChange),
+            constants::AVRULE_XPERMS_ALLOWED => Some(AvRuleKind::AllowXperm),
+            constants::AVRULE_XPERMS_AUDITALLOW => Some(AvRuleKind::AuditAllowXperm),
+            constants::AVRULE_XPERMS_DONTAUDIT => Some(AvRuleKind::DontAuditXperm),
+            constants::AVRULE_XPERMS_NEVERALLOW => Some(AvRuleKind::NeverAllowXperm),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            AvRuleKind::Allow => constants::AVRULE_ALLOWED,
+            AvRuleKind::AuditAllow => constants::AVRULE_AUDITALLOW,
+            AvRuleKind::AuditDeny => constants::AVRULE_AUDITDENY,
+            AvRuleKind::DontAudit => constants::AVRULE_DONTAUDIT,
+            AvRuleKind::NeverAllow => constants::AVRULE_NEVERALLOW,
+            AvRuleKind::TypeTransition => constants::AVRULE_TRANSITION,
+            AvRuleKind::TypeMember => constants::AVRULE_MEMBER,
+            AvRuleKind::TypeChange => constants::AVRULE_CHANGE,
+            AvRuleKind::AllowXperm => constants::AVRULE_XPERMS_ALLOWED,
+            AvRuleKind::AuditAllowXperm => constants::AVRULE_XPERMS_AUDITALLOW,
+            AvRuleKind::DontAuditXperm => constants::AVRULE_XPERMS_DONTAUDIT,
+            AvRuleKind::NeverAllowXperm => constants::AVRULE_XPERMS_NEVERALLOW,
+        }
+    }
+
+    /// Checks if rules of this kind carry a new type instead of a set of permissions.
+    pub fn is_type_rule(&self) -> bool {
+        match *self {
+            AvRuleKind::TypeTransition | AvRuleKind::TypeMember | AvRuleKind::TypeChange => true,
+            _ => false,
+        }
+    }
+
+    /// Checks if rules of this kind carry extended permissions.
+    pub fn is_xperm_rule(&self) -> bool {
+        match *self {
+            AvRuleKind::AllowXperm
+            | AvRuleKind::AuditAllowXperm
+            | AvRuleKind::DontAuditXperm
+            | AvRuleKind::NeverAllowXperm => true,
+            _ => false,
+        }
+    }
+}
+
+/// The permissions granted to a single class by an `AvRule`.  For type rules, `data` holds the
+/// id of the new type instead of an access vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassPermission {
+    class: u32,
+    data: u32,
+}
+
+impl ClassPermission {
+    pub fn class_id(&self) -> u32 {
+        self.class
+    }
+
+    pub fn data(&self) -> u32 {
+        self.data
+    }
+}
+
+/// The extended permissions (e.g. ioctl commands) attached to an xperm rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtendedPermissions {
+    specified: u8,
+    driver: u8,
+    permissions: [u32; 8],
+}
+
+impl ExtendedPermissions {
+    pub fn specified(&self) -> u8 {
+        self.specified
+    }
+
+    pub fn driver(&self) -> u8 {
+        self.driver
+    }
+
+    pub fn permissions(&self) -> &[u32; 8] {
+        &self.permissions
+    }
+}
+
+/// An access vector or type rule as written in the source policy, before its type sets are
+/// expanded into a kernel `AccessVectorTable`.
+#[derive(Debug)]
+pub struct AvRule {
+    kind: AvRuleKind,
+    flags: u32,
+    source_types: TypeSet,
+    target_types: TypeSet,
+    permissions: Vec<ClassPermission>,
+    xperms: Option<ExtendedPermissions>,
+}
+
+impl AvRule {
+    pub fn kind(&self) -> AvRuleKind {
+        self.kind
+    }
+
+    /// Checks if the rule targets `self`, i.e. each source type is also the target type.
+    pub fn is_self(&self) -> bool {
+        self.flags & constants::RULE_SELF != 0
+    }
+
+    pub fn source_types(&self) -> &TypeSet {
+        &self.source_types
+    }
+
+    pub fn target_types(&self) -> &TypeSet {
+        &self.target_types
+    }
+
+    pub fn permissions(&self) -> &[ClassPermission] {
+        &self.permissions
+    }
+
+    pub fn xperms(&self) -> Option<&ExtendedPermissions> {
+        self.xperms.as_ref()
+    }
+}
+
+impl Remap for AvRule {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.source_types.remap(map);
+        self.target_types.remap(map);
+
+        for perm in &mut self.permissions {
+            perm.data = if self.kind.is_type_rule() {
+                map.id(SymbolKind::Type, perm.data)
+            } else {
+                map.access_vector(perm.class, perm.data)
+            };
+            perm.class = map.id(SymbolKind::Class, perm.class);
+        }
+
+        self.permissions.retain(|perm| perm.class != 0);
+    }
+}
+
+impl PolicyObject for AvRule {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let specified = reader.read_u32()?;
+        let kind =
+            AvRuleKind::from_id(specified).ok_or(PolicyReadError::InvalidAvRule(specified))?;
+        let flags = reader.read_u32()?;
+        let source_types = reader.read_object()?;
+        let target_types = reader.read_object()?;
+
+        let num_permissions = reader.read_u32()? as usize;
+        let mut permissions = Vec::with_capacity(num_permissions);
+
+        for _ in 0..num_permissions {
+            let class = reader.read_u32()?;
+            let data = reader.read_u32()?;
+
+            permissions.push(ClassPermission { class, data });
+        }
+
+        let xperms = if kind.is_xperm_rule() {
+            if !reader.profile().supports(Feature::XpermsIoctl) {
+                return Err(PolicyReadError::UnsupportedFeatureUsed(
+                    Feature::XpermsIoctl,
+                ));
+            }
+
+            let specified = reader.read_u8()?;
+            let driver = reader.read_u8()?;
+            let mut permissions = [0; 8];
+
+            for idx in 0..8 {
+                permissions[idx] = reader.read_u32()?;
+            }
+
+            Some(ExtendedPermissions {
+                specified,
+                driver,
+                permissions,
+            })
+        } else {
+            None
+        };
+
+        Ok(AvRule {
+            kind,
+            flags,
+            source_types,
+            target_types,
+            permissions,
+            xperms,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.kind.id())?;
+        writer.write_u32(self.flags)?;
+        writer.write_object(&self.source_types)?;
+        writer.write_object(&self.target_types)?;
+        writer.write_len(self.permissions.len())?;
+
+        for perm in &self.permissions {
+            writer.write_u32(perm.class)?;
+            writer.write_u32(perm.data)?;
+        }
+
+        if let Some(ref xperms) = self.xperms {
+            if !writer.profile().supports(Feature::XpermsIoctl) {
+                return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                    Feature::XpermsIoctl,
+                ));
+            }
+
+            writer.write_u8(xperms.specified)?;
+            writer.write_u8(xperms.driver)?;
+
+            for perms in xperms.permissions.iter() {
+                writer.write_u32(*perms)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A list of `AvRule`s, the module counterpart of an `AccessVectorTable`.
+#[derive(Debug, Default)]
+pub struct AvRuleList {
+    rules: Vec<AvRule>,
+}
+
+impl AvRuleList {
+    pub fn rules(&self) -> &[AvRule] {
+        &self.rules
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+impl Remap for AvRuleList {
+    fn remap(&mut self, map: &SymbolMap) {
+        for rule in &mut self.rules {
+            rule.remap(map);
+        }
+    }
+}
+
+impl PolicyObject for AvRuleList {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let num_rules = reader.read_u32()? as usize;
+        let rules = reader.read_objects(num_rules)?;
+
+        Ok(AvRuleList { rules })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.rules.len())?;
+        writer.write_objects(&self.rules)
+    }
+}
+
+/// An unexpanded `role_transition` rule.
+#[derive(Debug)]
+pub struct RoleTransitionRule {
+    roles: RoleSet,
+    types: TypeSet,
+    classes: Option<Bitmap>,
+    new_role: u32,
+}
+
+impl RoleTransitionRule {
+    pub fn roles(&self) -> &RoleSet {
+        &self.roles
+    }
+
+    pub fn types(&self) -> &TypeSet {
+        &self.types
+    }
+
+    /// The classes this rule applies to.  Modules older than `MOD_POLICYDB_VERSION_ROLETRANS`
+    /// don't record any and only apply to `process`.
+    pub fn classes(&self) -> Option<&Bitmap> {
+        self.classes.as_ref()
+    }
+
+    pub fn new_role_id(&self) -> u32 {
+        self.new_role
+    }
+}
+
+impl Remap for RoleTransitionRule {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.roles.remap(map);
+        self.types.remap(map);
+        self.new_role = map.id(SymbolKind::Role, self.new_role);
+
+        if let Some(ref mut classes) = self.classes {
+            *classes = map.bitmap(SymbolKind::Class, classes);
+        }
+    }
+}
+
+impl PolicyObject for RoleTransitionRule {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let roles = reader.read_object()?;
+        let types = reader.read_object()?;
+        let classes = if reader.profile().supports(Feature::RoleTransitionClass) {
+            Some(reader.read_object()?)
+        } else {
+            None
+        };
+
+        let new_role = reader.read_u32()?;
+
+        Ok(RoleTransitionRule {
+            roles,
+            types,
+            classes,
+            new_role,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_object(&self.roles)?;
+        writer.write_object(&self.types)?;
+
+        if writer.profile().supports(Feature::RoleTransitionClass) {
+            match self.classes {
+                Some(ref classes) => writer.write_object(classes)?,
+                None => {
+                    return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                        Feature::RoleTransitionClass,
+                    ))
+                }
+            }
+        }
+
+        writer.write_u32(self.new_role)?;
+
+        Ok(())
+    }
+}
+
+/// An unexpanded role `allow` rule.
+#[derive(Debug)]
+pub struct RoleAllowRule {
+    roles: RoleSet,
+    new_roles: RoleSet,
+}
+
+impl RoleAllowRule {
+    pub fn roles(&self) -> &RoleSet {
+        &self.roles
+    }
+
+    pub fn new_roles(&self) -> &RoleSet {
+        &self.new_roles
+    }
+}
+
+impl Remap for RoleAllowRule {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.roles.remap(map);
+        self.new_roles.remap(map);
+    }
+}
+
+impl PolicyObject for RoleAllowRule {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let roles = reader.read_object()?;
+        let new_roles = reader.read_object()?;
+
+        Ok(RoleAllowRule { roles, new_roles })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_object(&self.roles)?;
+        writer.write_object(&self.new_roles)
+    }
+}
+
+/// An unexpanded name-based `type_transition` rule.
+#[derive(Debug)]
+pub struct FilenameTransitionRule {
+    name: String,
+    source_types: TypeSet,
+    target_types: TypeSet,
+    class: u32,
+    new_type: u32,
+    flags: u32,
+}
+
+impl FilenameTransitionRule {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn source_types(&self) -> &TypeSet {
+        &self.source_types
+    }
+
+    pub fn target_types(&self) -> &TypeSet {
+        &self.target_types
+    }
+
+    pub fn class_id(&self) -> u32 {
+        self.class
+    }
+
+    pub fn new_type_id(&self) -> u32 {
+        self.new_type
+    }
+
+    /// Checks if the rule targets `self`, i.e. each source type is also the target type.
+    pub fn is_self(&self) -> bool {
+        self.flags & constants::RULE_SELF != 0
+    }
+}
+
+impl Remap for FilenameTransitionRule {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.source_types.remap(map);
+        self.target_types.remap(map);
+        self.class = map.id(SymbolKind::Class, self.class);
+        self.new_type = map.id(SymbolKind::Type, self.new_type);
+    }
+}
+
+impl PolicyObject for FilenameTransitionRule {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read_string(name_len)?;
+        let source_types = reader.read_object()?;
+        let target_types = reader.read_object()?;
+        let class = reader.read_u32()?;
+        let new_type = reader.read_u32()?;
+        let flags = if reader.profile().supports(Feature::SelfTypeTransition) {
+            reader.read_u32()?
+        } else {
+            0
+        };
+
+        Ok(FilenameTransitionRule {
+            name,
+            source_types,
+            target_types,
+            class,
+            new_type,
+            flags,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_string(&self.name)?;
+        writer.write_object(&self.source_types)?;
+        writer.write_object(&self.target_types)?;
+        writer.write_u32(self.class)?;
+        writer.write_u32(self.new_type)?;
+
+        if writer.profile().supports(Feature::SelfTypeTransition) {
+            writer.write_u32(self.flags)?;
+        } else if self.is_self() {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::SelfTypeTransition,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// An unexpanded `range_transition` rule.
+#[derive(Debug)]
+pub struct RangeTransitionRule {
+    source_types: TypeSet,
+    target_types: TypeSet,
+    classes: Bitmap,
+    range: MlsRange,
+}
+
+impl RangeTransitionRule {
+    pub fn source_types(&self) -> &TypeSet {
+        &self.source_types
+    }
+
+    pub fn target_types(&self) -> &TypeSet {
+        &self.target_types
+    }
+
+    pub fn classes(&self) -> &Bitmap {
+        &self.classes
+    }
+
+    pub fn range(&self) -> &MlsRange {
+        &self.range
+    }
+}
+
+impl Remap for RangeTransitionRule {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.source_types.remap(map);
+        self.target_types.remap(map);
+        self.classes = map.bitmap(SymbolKind::Class, &self.classes);
+        self.range.remap(map);
+    }
+}
+
+impl PolicyObject for RangeTransitionRule {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let source_types = reader.read_object()?;
+        let target_types = reader.read_object()?;
+        let classes = reader.read_object()?;
+        let range = MlsRange::decode_expanded(reader)?;
+
+        Ok(RangeTransitionRule {
+            source_types,
+            target_types,
+            classes,
+            range,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_object(&self.source_types)?;
+        writer.write_object(&self.target_types)?;
+        writer.write_object(&self.classes)?;
+        self.range.encode_expanded(writer)
+    }
+}
@@ -1,8 +1,14 @@
+use policydb::writer::group_consecutive;
+use policydb::AvRuleKind;
+use policydb::ExtendedPermissions;
 use policydb::Feature;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
-use std::io::Read;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use std::collections::HashMap;
+use std::io::{Read, Write};
 
 bitflags! {
     struct AccessVectorSpecifier : u16 {
@@ -46,6 +52,33 @@ lazy_static! {
 
 const AVTAB_ENABLED_OLD: u32 = 0x80000000;
 
+/// The extended permissions of an entry are the ioctl commands of a single driver.
+pub(crate) const AVTAB_XPERMS_IOCTLFUNCTION: u8 = 0x01;
+
+/// The extended permissions of an entry are ioctl drivers, with every command of them included.
+pub(crate) const AVTAB_XPERMS_IOCTLDRIVER: u8 = 0x02;
+
+impl AccessVectorSpecifier {
+    /// The specifier of the avtab entries holding rules of the given kind, or `None` for rules
+    /// that aren't stored in the kernel's avtab.
+    fn for_rule(kind: AvRuleKind) -> Option<Self> {
+        match kind {
+            AvRuleKind::Allow => Some(AccessVectorSpecifier::AVTAB_ALLOWED),
+            AvRuleKind::AuditAllow => Some(AccessVectorSpecifier::AVTAB_AUDITALLOW),
+            AvRuleKind::AuditDeny | AvRuleKind::DontAudit => {
+                Some(AccessVectorSpecifier::AVTAB_AUDITDENY)
+            }
+            AvRuleKind::TypeTransition => Some(AccessVectorSpecifier::AVTAB_TRANSITION),
+            AvRuleKind::TypeMember => Some(AccessVectorSpecifier::AVTAB_MEMBER),
+            AvRuleKind::TypeChange => Some(AccessVectorSpecifier::AVTAB_CHANGE),
+            AvRuleKind::AllowXperm => Some(AccessVectorSpecifier::AVTAB_XPERMS_ALLOWED),
+            AvRuleKind::AuditAllowXperm => Some(AccessVectorSpecifier::AVTAB_XPERMS_AUDITALLOW),
+            AvRuleKind::DontAuditXperm => Some(AccessVectorSpecifier::AVTAB_XPERMS_DONTAUDIT),
+            AvRuleKind::NeverAllow | AvRuleKind::NeverAllowXperm => None,
+        }
+    }
+}
+
 #[derive(Debug, Hash)]
 pub struct AccessVectorTableKey {
     source_type: u16,
@@ -60,9 +93,308 @@ pub struct AccessVectorTableEntry {
     av: AccessVector,
 }
 
-#[derive(Debug)]
+/// The source type, target type, class and specifier of an entry, along with the kind and driver
+/// of its extended permissions, which together identify the entry that rules are merged into.
+type IndexKey = (u16, u16, u16, u16, u8, u8);
+
+#[derive(Debug, Default)]
 pub struct AccessVectorTable {
     entries: Vec<AccessVectorTableEntry>,
+    index: HashMap<IndexKey, usize>,
+}
+
+impl AccessVectorTableKey {
+    pub fn source_type(&self) -> u16 {
+        self.source_type
+    }
+
+    pub fn target_type(&self) -> u16 {
+        self.target_type
+    }
+
+    pub fn target_class(&self) -> u16 {
+        self.target_class
+    }
+
+    /// The kind of rule stored in this entry.  Dontaudit rules are stored as `AuditDeny`, with the
+    /// permissions that are still audited.
+    pub fn kind(&self) -> AvRuleKind {
+        let specifier = self.specifier - AccessVectorSpecifier::AVTAB_ENABLED;
+
+        match specifier {
+            AccessVectorSpecifier::AVTAB_ALLOWED => AvRuleKind::Allow,
+            AccessVectorSpecifier::AVTAB_AUDITALLOW => AvRuleKind::AuditAllow,
+            AccessVectorSpecifier::AVTAB_AUDITDENY => AvRuleKind::AuditDeny,
+            AccessVectorSpecifier::AVTAB_TRANSITION => AvRuleKind::TypeTransition,
+            AccessVectorSpecifier::AVTAB_MEMBER => AvRuleKind::TypeMember,
+            AccessVectorSpecifier::AVTAB_CHANGE => AvRuleKind::TypeChange,
+            AccessVectorSpecifier::AVTAB_XPERMS_ALLOWED => AvRuleKind::AllowXperm,
+            AccessVectorSpecifier::AVTAB_XPERMS_AUDITALLOW => AvRuleKind::AuditAllowXperm,
+            AccessVectorSpecifier::AVTAB_XPERMS_DONTAUDIT => AvRuleKind::DontAuditXperm,
+            AccessVectorSpecifier::AVTAB_NEVERALLOW => AvRuleKind::NeverAllow,
+            // Decoding only accepts a single specifier, so this is the only one left.
+            _ => AvRuleKind::NeverAllowXperm,
+        }
+    }
+
+    /// Checks if the entry of a conditional rule list applies, because its expression had the
+    /// matching value when the policy was written.
+    pub fn is_enabled(&self) -> bool {
+        self.specifier
+            .contains(AccessVectorSpecifier::AVTAB_ENABLED)
+    }
+}
+
+impl AccessVectorTableKey {
+    /// The position of the rule kind in the legacy avtab format, which stores the datums of all
+    /// rule kinds sharing a source, target and class in this order.
+    fn legacy_order(&self) -> Option<usize> {
+        LEGACY_AV_SPECIFIERS
+            .iter()
+            .position(|specifier| self.specifier.contains(*specifier))
+    }
+
+    /// Checks if an entry can follow this one under a single key in the legacy avtab format,
+    /// which holds at most one datum of each rule kind, in a fixed order.
+    fn shares_legacy_entry_with(&self, next: &AccessVectorTableKey) -> bool {
+        self.source_type == next.source_type
+            && self.target_type == next.target_type
+            && self.target_class == next.target_class
+            && self.is_enabled() == next.is_enabled()
+            && self.legacy_order() < next.legacy_order()
+    }
+}
+
+impl AccessVectorTableEntry {
+    pub fn key(&self) -> &AccessVectorTableKey {
+        &self.key
+    }
+
+    pub fn av(&self) -> &AccessVector {
+        &self.av
+    }
+}
+
+impl AccessVectorTableEntry {
+    /// Creates an entry that doesn't grant, audit or transition anything yet.
+    fn empty(key: AccessVectorTableKey, xperms: Option<&ExtendedPermissions>) -> Self {
+        // A set bit in an auditdeny vector means the denial is audited, so these start out with
+        // every permission audited.
+        let av = match xperms {
+            Some(xperms) => AccessVector::Extended {
+                specified: xperms.specified(),
+                driver: xperms.driver(),
+                permissions: [0; 8],
+            },
+            None if key
+                .specifier
+                .contains(AccessVectorSpecifier::AVTAB_AUDITDENY) =>
+            {
+                AccessVector::Simple(!0)
+            }
+            None => AccessVector::Simple(0),
+        };
+
+        AccessVectorTableEntry { key, av }
+    }
+
+    /// The key of this entry in the index of its table.  Entries of conditional rule lists have
+    /// the same key whether or not they are enabled.
+    fn index_key(&self) -> IndexKey {
+        let (specified, driver) = match self.av {
+            AccessVector::Extended {
+                specified, driver, ..
+            } => (specified, driver),
+            AccessVector::Simple(_) => (0, 0),
+        };
+
+        (
+            self.key.source_type,
+            self.key.target_type,
+            self.key.target_class,
+            (self.key.specifier - AccessVectorSpecifier::AVTAB_ENABLED).bits,
+            specified,
+            driver,
+        )
+    }
+
+    /// Merges a rule into this entry.  A type rule that conflicts with the new type already in
+    /// the entry fails with that type.
+    fn merge(
+        &mut self,
+        kind: AvRuleKind,
+        data: u32,
+        xperms: Option<&ExtendedPermissions>,
+    ) -> Result<(), u32> {
+        match self.av {
+            AccessVector::Extended {
+                ref mut permissions,
+                ..
+            } => {
+                let new_permissions = xperms.map_or(&[0; 8], |xperms| xperms.permissions());
+
+                for (perms, new_perms) in permissions.iter_mut().zip(new_permissions.iter()) {
+                    *perms |= *new_perms;
+                }
+            }
+            AccessVector::Simple(ref mut av) => match kind {
+                AvRuleKind::AuditDeny => *av &= data,
+                AvRuleKind::DontAudit => *av &= !data,
+                _ if kind.is_type_rule() => {
+                    if *av != 0 && *av != data {
+                        return Err(*av);
+                    }
+
+                    *av = data;
+                }
+                _ => *av |= data,
+            },
+        }
+
+        Ok(())
+    }
+}
+
+impl AccessVectorTable {
+    fn from_entries(entries: Vec<AccessVectorTableEntry>) -> Self {
+        let mut avtab = AccessVectorTable {
+            entries,
+            index: HashMap::new(),
+        };
+
+        avtab.reindex();
+        avtab
+    }
+
+    /// Rebuilds the index after entries were removed.
+    fn reindex(&mut self) {
+        self.index.clear();
+
+        for (idx, entry) in self.entries.iter().enumerate() {
+            self.index.entry(entry.index_key()).or_insert(idx);
+        }
+    }
+
+    pub fn entries(&self) -> &[AccessVectorTableEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes the extended permission entries from this table, returning how many were removed.
+    pub(crate) fn discard_extended(&mut self) -> usize {
+        let len = self.entries.len();
+
+        self.entries.retain(|entry| match entry.av {
+            AccessVector::Simple(_) => true,
+            AccessVector::Extended { .. } => false,
+        });
+
+        self.reindex();
+
+        len - self.entries.len()
+    }
+
+    /// Adds a rule for a single source type, target type and class, merging it into the entry
+    /// that already holds rules of the same kind for them.  The types may be attributes.  A type
+    /// rule that conflicts with an existing one fails with the new type of the existing rule.
+    pub fn add(
+        &mut self,
+        source_type: u16,
+        target_type: u16,
+        target_class: u16,
+        kind: AvRuleKind,
+        data: u32,
+        xperms: Option<&ExtendedPermissions>,
+    ) -> Result<(), u32> {
+        let specifier = match AccessVectorSpecifier::for_rule(kind) {
+            Some(specifier) => specifier,
+            // Neverallow rules are only checked when building a policy, the kernel never sees them.
+            None => return Ok(()),
+        };
+
+        let (specified, driver) =
+            xperms.map_or((0, 0), |xperms| (xperms.specified(), xperms.driver()));
+        let key = (
+            source_type,
+            target_type,
+            target_class,
+            specifier.bits,
+            specified,
+            driver,
+        );
+
+        let entries = &mut self.entries;
+        let idx = *self.index.entry(key).or_insert_with(|| {
+            entries.push(AccessVectorTableEntry::empty(
+                AccessVectorTableKey {
+                    source_type,
+                    target_type,
+                    target_class,
+                    specifier,
+                },
+                xperms,
+            ));
+
+            entries.len() - 1
+        });
+
+        self.entries[idx].merge(kind, data, xperms)
+    }
+
+    /// Removes permissions from the `allow` entry for a source type, target type and class,
+    /// dropping the entry once it no longer allows anything.  Returns the permissions that were
+    /// actually removed.
+    pub fn remove_allowed(
+        &mut self,
+        source_type: u16,
+        target_type: u16,
+        target_class: u16,
+        permissions: u32,
+    ) -> u32 {
+        let key = (
+            source_type,
+            target_type,
+            target_class,
+            AccessVectorSpecifier::AVTAB_ALLOWED.bits,
+            0,
+            0,
+        );
+
+        let idx = match self.index.get(&key) {
+            Some(idx) => *idx,
+            None => return 0,
+        };
+
+        let removed = match self.entries[idx].av {
+            AccessVector::Simple(ref mut av) => {
+                let removed = *av & permissions;
+                *av &= !permissions;
+                removed
+            }
+            AccessVector::Extended { .. } => 0,
+        };
+
+        if let AccessVector::Simple(0) = self.entries[idx].av {
+            self.entries.remove(idx);
+            self.reindex();
+        }
+
+        removed
+    }
+
+    /// Marks every entry of a conditional rule list as enabled or disabled, after the value of its
+    /// expression changes.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        for entry in &mut self.entries {
+            entry
+                .key
+                .specifier
+                .set(AccessVectorSpecifier::AVTAB_ENABLED, enabled);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -126,7 +458,7 @@ impl PolicyObject for AccessVectorTable {
                 }
 
                 let ioctls_supported = reader.profile().supports(Feature::XpermsIoctl);
-                let extended_av = specifier.contains(AccessVectorSpecifier::AVTAB_XPERMS);
+                let extended_av = specifier.intersects(AccessVectorSpecifier::AVTAB_XPERMS);
 
                 let av = if !ioctls_supported && extended_av {
                     return Err(PolicyReadError::UnsupportedFeatureUsed(
@@ -162,6 +494,113 @@ impl PolicyObject for AccessVectorTable {
             }
         }
 
-        Ok(AccessVectorTable { entries })
+        Ok(AccessVectorTable::from_entries(entries))
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        if !writer.profile().supports(Feature::AvTab) {
+            return self.encode_legacy(writer, false);
+        }
+
+        writer.write_len(self.entries.len())?;
+
+        for entry in &self.entries {
+            writer.write_u16(entry.key.source_type)?;
+            writer.write_u16(entry.key.target_type)?;
+            writer.write_u16(entry.key.target_class)?;
+            writer.write_u16(entry.key.specifier.bits)?;
+
+            match entry.av {
+                AccessVector::Simple(datum) => writer.write_u32(datum)?,
+                AccessVector::Extended { .. }
+                    if !writer.profile().supports(Feature::XpermsIoctl) =>
+                {
+                    return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                        Feature::XpermsIoctl,
+                    ))
+                }
+                AccessVector::Extended {
+                    specified,
+                    driver,
+                    ref permissions,
+                } => {
+                    writer.write_u8(specified)?;
+                    writer.write_u8(driver)?;
+
+                    for perms in permissions.iter() {
+                        writer.write_u32(*perms)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AccessVectorTable {
+    /// Writes the policy's unconditional table.  Unlike the rule lists of conditionals, which
+    /// keep one entry per rule kind, the format used before `POLICYDB_VERSION_AVTAB` merges the
+    /// rule kinds sharing a source, target and class into a single entry.
+    pub(crate) fn encode_merged<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+    ) -> Result<(), PolicyWriteError> {
+        if writer.profile().supports(Feature::AvTab) {
+            writer.write_object(self)
+        } else {
+            self.encode_legacy(writer, true)
+        }
+    }
+
+    /// Writes the table in the format used before `POLICYDB_VERSION_AVTAB`, where the datums of
+    /// the rule kinds in the specifier follow a single key.  Without `merge` each entry holds a
+    /// single rule kind.
+    fn encode_legacy<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+        merge: bool,
+    ) -> Result<(), PolicyWriteError> {
+        let groups = if merge {
+            group_consecutive(&self.entries, |prev, next| {
+                prev.key.shares_legacy_entry_with(&next.key)
+            })
+        } else {
+            self.entries.chunks(1).collect()
+        };
+
+        writer.write_len(groups.len())?;
+
+        for group in groups {
+            let key = &group[0].key;
+            let mut specifier_val = group
+                .iter()
+                .fold(0, |val, entry| val | u32::from(entry.key.specifier.bits));
+
+            specifier_val &= !u32::from(AccessVectorSpecifier::AVTAB_ENABLED.bits);
+
+            if key.is_enabled() {
+                specifier_val |= AVTAB_ENABLED_OLD;
+            }
+
+            writer.write_len(group.len() + 4)?;
+            writer.write_u32(u32::from(key.source_type))?;
+            writer.write_u32(u32::from(key.target_type))?;
+            writer.write_u32(u32::from(key.target_class))?;
+            writer.write_u32(specifier_val)?;
+
+            for entry in group {
+                match entry.av {
+                    AccessVector::Simple(datum) => writer.write_u32(datum)?,
+                    AccessVector::Extended { .. } => {
+                        return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                            Feature::XpermsIoctl,
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(())
     }
 }
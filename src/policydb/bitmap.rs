@@ -1,28 +1,172 @@
 use croaring::Bitmap;
-use policydb::CompatibilityProfile;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
-use std::io::Read;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use std::io::{Read, Write};
 
-impl PolicyObject for Bitmap {
+const MAPSIZE: u32 = 64;
+
+/// A bitmap in the `ebitmap` format used by the kernel, made up of 64-bit nodes that each cover
+/// an aligned run of bits, in ascending order.  Policies use this to store sets of ids, and
+/// everything else converts it to and from a `Bitmap`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EBitmap {
+    high_bit: u32,
+    nodes: Vec<EBitmapNode>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EBitmapNode {
+    start_bit: u32,
+    map: u64,
+}
+
+impl EBitmap {
+    /// The bit after the last node, which is a multiple of the node size.
+    pub fn high_bit(&self) -> u32 {
+        self.high_bit
+    }
+
+    pub fn nodes(&self) -> &[EBitmapNode] {
+        &self.nodes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The set bits in ascending order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = u32> + 'a {
+        self.nodes.iter().flat_map(|node| node.iter())
+    }
+
+    /// Checks this bitmap in the same way as the kernel when it loads a policy: every node has to
+    /// be non-empty, aligned to the node size and after the previous node, and the high bit has
+    /// to be aligned and cover every node.
+    pub fn is_valid(&self) -> bool {
+        if self.high_bit % MAPSIZE != 0 || (self.high_bit != 0 && self.nodes.is_empty()) {
+            return false;
+        }
+
+        let mut next_start_bit = 0;
+
+        for node in &self.nodes {
+            if node.start_bit % MAPSIZE != 0 || node.start_bit < next_start_bit || node.map == 0 {
+                return false;
+            }
+
+            next_start_bit = node.start_bit.saturating_add(MAPSIZE);
+        }
+
+        next_start_bit <= self.high_bit
+    }
+}
+
+impl EBitmapNode {
+    pub fn start_bit(&self) -> u32 {
+        self.start_bit
+    }
+
+    /// The bits of this node, where the lowest bit is `start_bit`.
+    pub fn map(&self) -> u64 {
+        self.map
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> {
+        let EBitmapNode { start_bit, map } = *self;
+
+        (0..MAPSIZE)
+            .filter(move |bit| map & (1 << bit) != 0)
+            .map(move |bit| start_bit + bit)
+    }
+}
+
+impl<'a> From<&'a Bitmap> for EBitmap {
+    fn from(bitmap: &'a Bitmap) -> Self {
+        let mut nodes: Vec<EBitmapNode> = vec![];
+
+        for bit in bitmap.iter() {
+            let start_bit = bit - bit % MAPSIZE;
+
+            match nodes.last_mut() {
+                Some(node) if node.start_bit == start_bit => node.map |= 1 << (bit - start_bit),
+                _ => nodes.push(EBitmapNode {
+                    start_bit,
+                    map: 1 << (bit - start_bit),
+                }),
+            }
+        }
+
+        let high_bit = nodes.last().map_or(0, |node| node.start_bit + MAPSIZE);
+
+        EBitmap { high_bit, nodes }
+    }
+}
+
+impl<'a> From<&'a EBitmap> for Bitmap {
+    fn from(ebitmap: &'a EBitmap) -> Self {
+        let mut bitmap = Bitmap::create_with_capacity(ebitmap.nodes.len() as u32);
+
+        for bit in ebitmap.iter() {
+            bitmap.add(bit);
+        }
+
+        bitmap
+    }
+}
+
+impl PolicyObject for EBitmap {
     fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
         let map_size = reader.read_u32()?;
         let high_bit = reader.read_u32()?;
         let map_count = reader.read_u32()?;
-        let mut bitmap = Bitmap::create_with_capacity(map_count);
 
-        for map_idx in 0..map_count {
+        if map_size != MAPSIZE {
+            return Err(PolicyReadError::InvalidBitmap);
+        }
+
+        let mut nodes = Vec::with_capacity(map_count as usize);
+
+        for _ in 0..map_count {
             let start_bit = reader.read_u32()?;
             let map = reader.read_u64()?;
 
-            for bit in 0..64 {
-                if (1 << bit) & map != 0 {
-                    bitmap.add(start_bit + bit as u32);
-                }
-            }
+            nodes.push(EBitmapNode { start_bit, map });
         }
 
-        Ok(bitmap)
+        let ebitmap = EBitmap { high_bit, nodes };
+
+        if !ebitmap.is_valid() {
+            return Err(PolicyReadError::InvalidBitmap);
+        }
+
+        Ok(ebitmap)
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(MAPSIZE)?;
+        writer.write_u32(self.high_bit)?;
+        writer.write_len(self.nodes.len())?;
+
+        for node in &self.nodes {
+            writer.write_u32(node.start_bit)?;
+            writer.write_u64(node.map)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PolicyObject for Bitmap {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let ebitmap: EBitmap = reader.read_object()?;
+
+        Ok(Bitmap::from(&ebitmap))
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_object(&EBitmap::from(self))
     }
 }
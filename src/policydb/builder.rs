@@ -0,0 +1,890 @@
+use croaring::Bitmap;
+use policydb::link::access_vector;
+use policydb::role::constants::OBJECT_R_VAL;
+use policydb::symtable::{lookup, UnknownName};
+use policydb::AccessVectorTable;
+use policydb::AvRuleKind;
+use policydb::Boolean;
+use policydb::Class;
+use policydb::Common;
+use policydb::CompatibilityProfile;
+use policydb::ConditionalExpression;
+use policydb::ConditionalNode;
+use policydb::Feature;
+use policydb::HandleUnknown;
+use policydb::InitialSid;
+use policydb::MlsLevel;
+use policydb::MlsRange;
+use policydb::ObjectContexts;
+use policydb::Permission;
+use policydb::Policy;
+use policydb::PolicyCapability;
+use policydb::PolicyCapabilitySet;
+use policydb::PolicyConfig;
+use policydb::PolicyTargetPlatform;
+use policydb::PolicyType;
+use policydb::Role;
+use policydb::RoleAllow;
+use policydb::RoleTransition;
+use policydb::SecurityContext;
+use policydb::Symbol;
+use policydb::SymbolKind;
+use policydb::SymbolTable;
+use policydb::Type;
+use policydb::TypeAttributeMap;
+use policydb::TypeFlavor;
+use policydb::User;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// The pseudo-type that refers to the source type when used as the target of a rule.
+const SELF_TYPE: &str = "self";
+
+/// The most permissions a class can have, as they are stored in a 32-bit access vector.
+const MAX_PERMISSIONS: usize = 32;
+
+/// The initial SID of the kernel, which every policy has to give a context for to be loaded.
+const SECINITSID_KERNEL: u32 = 1;
+
+#[derive(Debug)]
+pub enum BuildError {
+    InvalidVersion(u32),
+    DuplicateSymbol {
+        kind: SymbolKind,
+        name: String,
+    },
+    UnknownSymbol {
+        kind: SymbolKind,
+        name: String,
+    },
+    UnknownPermission {
+        class: String,
+        permission: String,
+    },
+    DuplicatePermission {
+        class: String,
+        permission: String,
+    },
+    /// A class and its common have more permissions than fit in an access vector.
+    TooManyPermissions(String),
+    /// A type was added to something that isn't an attribute.
+    NotAnAttribute(String),
+    /// Two type rules for the same source type, target type and class give different new types.
+    ConflictingTypeRule {
+        source_type: String,
+        target_type: String,
+        class: String,
+    },
+    /// No context was given for an initial SID that the kernel needs to load the policy.
+    MissingInitialSid(u32),
+}
+
+impl From<UnknownName> for BuildError {
+    fn from(name: UnknownName) -> Self {
+        match name {
+            UnknownName::Symbol { kind, name } => BuildError::UnknownSymbol { kind, name },
+            UnknownName::Permission { class, permission } => {
+                BuildError::UnknownPermission { class, permission }
+            }
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn description(&self) -> &str {
+        "Unable to build policy"
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::InvalidVersion(version) => {
+                write!(f, "unsupported kernel policy version {}", version)
+            }
+            BuildError::DuplicateSymbol { kind, ref name } => {
+                write!(f, "{:?} {} is declared more than once", kind, name)
+            }
+            BuildError::UnknownSymbol { kind, ref name } => {
+                write!(f, "{:?} {} is not declared", kind, name)
+            }
+            BuildError::UnknownPermission {
+                ref class,
+                ref permission,
+            } => write!(f, "class {} has no permission {}", class, permission),
+            BuildError::DuplicatePermission {
+                ref class,
+                ref permission,
+            } => write!(
+                f,
+                "permission {} is declared more than once in {}",
+                permission, class
+            ),
+            BuildError::TooManyPermissions(ref class) => {
+                write!(
+                    f,
+                    "class {} has more than {} permissions",
+                    class, MAX_PERMISSIONS
+                )
+            }
+            BuildError::NotAnAttribute(ref name) => write!(f, "{} is not an attribute", name),
+            BuildError::ConflictingTypeRule {
+                ref source_type,
+                ref target_type,
+                ref class,
+            } => write!(
+                f,
+                "conflicting type rules for types {} and {} on class {}",
+                source_type, target_type, class
+            ),
+            BuildError::MissingInitialSid(sid) => {
+                write!(f, "no context is given for initial SID {}", sid)
+            }
+        }
+    }
+}
+
+struct ClassDecl {
+    name: String,
+    common: Option<String>,
+    permissions: Vec<String>,
+}
+
+struct TypeDecl {
+    name: String,
+    flavor: TypeFlavor,
+    primary: Option<String>,
+}
+
+struct RuleDecl {
+    kind: AvRuleKind,
+    source: String,
+    target: String,
+    class: String,
+    permissions: Vec<String>,
+    new_type: Option<String>,
+    boolean: Option<String>,
+}
+
+struct RoleTransitionDecl {
+    role: String,
+    ty: String,
+    class: String,
+    new_role: String,
+}
+
+struct InitialSidDecl {
+    sid: u32,
+    user: String,
+    role: String,
+    ty: String,
+}
+
+/// Builds a kernel policy from symbols and rules declared by name, for test fixtures and
+/// generated policy.
+///
+/// Symbols are given ids in the order they are declared, and the `object_r` role is declared
+/// first, as in policies built by `checkpolicy`.  Attributes used in rules and in the types of a
+/// role are replaced by their member types.  The policy is built without MLS, so contexts use an
+/// empty range when the version supports MLS.
+///
+/// Symbols may be declared after the rules that use them, and references are only resolved by
+/// `build`.
+pub struct PolicyBuilder {
+    version: u32,
+    handle_unknown: HandleUnknown,
+    polcaps: Vec<u32>,
+    commons: Vec<(String, Vec<String>)>,
+    classes: Vec<ClassDecl>,
+    types: Vec<TypeDecl>,
+    type_attributes: Vec<(String, String)>,
+    permissive_types: Vec<String>,
+    roles: Vec<(String, Vec<String>)>,
+    users: Vec<(String, Vec<String>)>,
+    booleans: Vec<(String, bool)>,
+    rules: Vec<RuleDecl>,
+    role_allows: Vec<(String, String)>,
+    role_transitions: Vec<RoleTransitionDecl>,
+    initial_sids: Vec<InitialSidDecl>,
+}
+
+impl PolicyBuilder {
+    /// Creates a builder for an SELinux kernel policy of the given version.
+    pub fn new(version: u32) -> Self {
+        PolicyBuilder {
+            version,
+            handle_unknown: HandleUnknown::Deny,
+            polcaps: vec![],
+            commons: vec![],
+            classes: vec![],
+            types: vec![],
+            type_attributes: vec![],
+            permissive_types: vec![],
+            roles: vec![],
+            users: vec![],
+            booleans: vec![],
+            rules: vec![],
+            role_allows: vec![],
+            role_transitions: vec![],
+            initial_sids: vec![],
+        }
+    }
+
+    pub fn handle_unknown(&mut self, handle_unknown: HandleUnknown) -> &mut Self {
+        self.handle_unknown = handle_unknown;
+        self
+    }
+
+    pub fn polcap(&mut self, polcap: PolicyCapability) -> &mut Self {
+        self.polcaps.push(polcap.id());
+        self
+    }
+
+    pub fn common(&mut self, name: &str, permissions: &[&str]) -> &mut Self {
+        self.commons
+            .push((name.to_string(), to_strings(permissions)));
+        self
+    }
+
+    /// Declares a class with its own permissions, inheriting the permissions of a common if one
+    /// is given.
+    pub fn class(&mut self, name: &str, common: Option<&str>, permissions: &[&str]) -> &mut Self {
+        self.classes.push(ClassDecl {
+            name: name.to_string(),
+            common: common.map(str::to_string),
+            permissions: to_strings(permissions),
+        });
+        self
+    }
+
+    pub fn ty(&mut self, name: &str) -> &mut Self {
+        self.declare_type(name, TypeFlavor::Type, None)
+    }
+
+    pub fn attribute(&mut self, name: &str) -> &mut Self {
+        self.declare_type(name, TypeFlavor::Attribute, None)
+    }
+
+    pub fn type_alias(&mut self, name: &str, primary: &str) -> &mut Self {
+        self.declare_type(name, TypeFlavor::Alias, Some(primary))
+    }
+
+    /// Adds a type to an attribute, as with a `typeattribute` statement.
+    pub fn type_attribute(&mut self, ty: &str, attribute: &str) -> &mut Self {
+        self.type_attributes
+            .push((ty.to_string(), attribute.to_string()));
+        self
+    }
+
+    /// Marks a type as a permissive domain.
+    pub fn permissive(&mut self, ty: &str) -> &mut Self {
+        self.permissive_types.push(ty.to_string());
+        self
+    }
+
+    /// Declares a role authorized for the given types and attributes.
+    pub fn role(&mut self, name: &str, types: &[&str]) -> &mut Self {
+        self.roles.push((name.to_string(), to_strings(types)));
+        self
+    }
+
+    /// Declares a user authorized for the given roles.
+    pub fn user(&mut self, name: &str, roles: &[&str]) -> &mut Self {
+        self.users.push((name.to_string(), to_strings(roles)));
+        self
+    }
+
+    pub fn boolean(&mut self, name: &str, default: bool) -> &mut Self {
+        self.booleans.push((name.to_string(), default));
+        self
+    }
+
+    pub fn allow(&mut self, source: &str, target: &str, class: &str, perms: &[&str]) -> &mut Self {
+        self.av_rule(AvRuleKind::Allow, source, target, class, perms, None)
+    }
+
+    pub fn auditallow(
+        &mut self,
+        source: &str,
+        target: &str,
+        class: &str,
+        perms: &[&str],
+    ) -> &mut Self {
+        self.av_rule(AvRuleKind::AuditAllow, source, target, class, perms, None)
+    }
+
+    pub fn dontaudit(
+        &mut self,
+        source: &str,
+        target: &str,
+        class: &str,
+        perms: &[&str],
+    ) -> &mut Self {
+        self.av_rule(AvRuleKind::DontAudit, source, target, class, perms, None)
+    }
+
+    /// Adds an `allow` rule that only applies while the given boolean is enabled.
+    pub fn allow_if(
+        &mut self,
+        boolean: &str,
+        source: &str,
+        target: &str,
+        class: &str,
+        perms: &[&str],
+    ) -> &mut Self {
+        self.av_rule(
+            AvRuleKind::Allow,
+            source,
+            target,
+            class,
+            perms,
+            Some(boolean),
+        )
+    }
+
+    pub fn type_transition(
+        &mut self,
+        source: &str,
+        target: &str,
+        class: &str,
+        new_type: &str,
+    ) -> &mut Self {
+        self.type_rule(AvRuleKind::TypeTransition, source, target, class, new_type)
+    }
+
+    pub fn type_change(
+        &mut self,
+        source: &str,
+        target: &str,
+        class: &str,
+        new_type: &str,
+    ) -> &mut Self {
+        self.type_rule(AvRuleKind::TypeChange, source, target, class, new_type)
+    }
+
+    pub fn type_member(
+        &mut self,
+        source: &str,
+        target: &str,
+        class: &str,
+        new_type: &str,
+    ) -> &mut Self {
+        self.type_rule(AvRuleKind::TypeMember, source, target, class, new_type)
+    }
+
+    pub fn role_allow(&mut self, role: &str, new_role: &str) -> &mut Self {
+        self.role_allows
+            .push((role.to_string(), new_role.to_string()));
+        self
+    }
+
+    pub fn role_transition(
+        &mut self,
+        role: &str,
+        ty: &str,
+        class: &str,
+        new_role: &str,
+    ) -> &mut Self {
+        self.role_transitions.push(RoleTransitionDecl {
+            role: role.to_string(),
+            ty: ty.to_string(),
+            class: class.to_string(),
+            new_role: new_role.to_string(),
+        });
+        self
+    }
+
+    /// Sets the context of an initial SID, identified by its number in the kernel.  The kernel
+    /// SID, number 1, is required.
+    pub fn initial_sid(&mut self, sid: u32, user: &str, role: &str, ty: &str) -> &mut Self {
+        self.initial_sids.push(InitialSidDecl {
+            sid,
+            user: user.to_string(),
+            role: role.to_string(),
+            ty: ty.to_string(),
+        });
+        self
+    }
+
+    fn declare_type(&mut self, name: &str, flavor: TypeFlavor, primary: Option<&str>) -> &mut Self {
+        self.types.push(TypeDecl {
+            name: name.to_string(),
+            flavor,
+            primary: primary.map(str::to_string),
+        });
+        self
+    }
+
+    fn av_rule(
+        &mut self,
+        kind: AvRuleKind,
+        source: &str,
+        target: &str,
+        class: &str,
+        perms: &[&str],
+        boolean: Option<&str>,
+    ) -> &mut Self {
+        self.rules.push(RuleDecl {
+            kind,
+            source: source.to_string(),
+            target: target.to_string(),
+            class: class.to_string(),
+            permissions: to_strings(perms),
+            new_type: None,
+            boolean: boolean.map(str::to_string),
+        });
+        self
+    }
+
+    fn type_rule(
+        &mut self,
+        kind: AvRuleKind,
+        source: &str,
+        target: &str,
+        class: &str,
+        new_type: &str,
+    ) -> &mut Self {
+        self.rules.push(RuleDecl {
+            kind,
+            source: source.to_string(),
+            target: target.to_string(),
+            class: class.to_string(),
+            permissions: vec![],
+            new_type: Some(new_type.to_string()),
+            boolean: None,
+        });
+        self
+    }
+
+    /// Resolves every declaration and builds the policy.
+    pub fn build(&self) -> Result<Policy, BuildError> {
+        let profile = CompatibilityProfile::new(
+            PolicyType::Kernel(PolicyTargetPlatform::SELinux),
+            self.version,
+        );
+
+        if !profile.is_supported_version() {
+            return Err(BuildError::InvalidVersion(self.version));
+        }
+
+        if !self
+            .initial_sids
+            .iter()
+            .any(|decl| decl.sid == SECINITSID_KERNEL)
+        {
+            return Err(BuildError::MissingInitialSid(SECINITSID_KERNEL));
+        }
+
+        let is_mls_supported = profile.supports(Feature::Mls);
+        let empty_level = || MlsLevel::new(0, Bitmap::create());
+        let empty_range = || {
+            if is_mls_supported {
+                Some(MlsRange::new(empty_level(), empty_level()))
+            } else {
+                None
+            }
+        };
+
+        let common_classes = self.build_commons()?;
+        let classes = self.build_classes(&common_classes)?;
+        let (types, members) = self.build_types()?;
+        let roles = self.build_roles(&types, &members)?;
+
+        let mut users = SymbolTable::with_capacity(self.users.len());
+
+        for (idx, &(ref name, ref role_names)) in self.users.iter().enumerate() {
+            let mut user_roles = Bitmap::create();
+
+            for role in role_names {
+                user_roles.add(lookup(&roles, SymbolKind::Role, role)? - 1);
+            }
+
+            let default_level = if is_mls_supported {
+                Some(empty_level())
+            } else {
+                None
+            };
+
+            let user = User::new(
+                idx as u32 + 1,
+                name.clone(),
+                user_roles,
+                default_level,
+                empty_range(),
+            );
+            insert(&mut users, SymbolKind::User, user)?;
+        }
+
+        let mut booleans = SymbolTable::with_capacity(self.booleans.len());
+
+        for (idx, &(ref name, state)) in self.booleans.iter().enumerate() {
+            let boolean = Boolean::new(idx as u32 + 1, name.clone(), state);
+            insert(&mut booleans, SymbolKind::Boolean, boolean)?;
+        }
+
+        let polcaps = self
+            .polcaps
+            .iter()
+            .filter_map(|id| PolicyCapability::from_id(*id))
+            .collect();
+
+        let mut policy = Policy {
+            version: self.version,
+            config: PolicyConfig {
+                mls_enabled: false,
+                handle_unknown: self.handle_unknown,
+            },
+            polcaps: PolicyCapabilitySet::new(polcaps),
+            profile,
+            avtab: AccessVectorTable::default(),
+            booleans,
+            conditionals: vec![],
+            categories: SymbolTable::with_capacity(0),
+            common_classes,
+            classes,
+            filename_transitions: vec![],
+            ocontexts: ObjectContexts::default(),
+            module: None,
+            range_transitions: vec![],
+            roles,
+            role_transitions: vec![],
+            role_allows: vec![],
+            sensitivities: SymbolTable::with_capacity(0),
+            types,
+            type_attr_map: TypeAttributeMap::default(),
+            users,
+        };
+
+        policy.type_attr_map =
+            TypeAttributeMap::from_attributes(policy.types.num_primary_names(), &members);
+
+        self.build_rules(&mut policy, &members)?;
+
+        for decl in &self.role_allows {
+            let role = lookup(&policy.roles, SymbolKind::Role, &decl.0)?;
+            let new_role = lookup(&policy.roles, SymbolKind::Role, &decl.1)?;
+
+            policy.role_allows.push(RoleAllow::new(role, new_role));
+        }
+
+        for decl in &self.role_transitions {
+            let role = lookup(&policy.roles, SymbolKind::Role, &decl.role)?;
+            let new_role = lookup(&policy.roles, SymbolKind::Role, &decl.new_role)?;
+            let class = lookup(&policy.classes, SymbolKind::Class, &decl.class)?;
+
+            for ty in expand_type(&policy.types, &members, &decl.ty)?.iter() {
+                policy.role_transitions.push(RoleTransition::new(
+                    role,
+                    ty + 1,
+                    new_role,
+                    Some(class),
+                ));
+            }
+        }
+
+        for decl in &self.initial_sids {
+            let context = SecurityContext::new(
+                lookup(&policy.users, SymbolKind::User, &decl.user)?,
+                lookup(&policy.roles, SymbolKind::Role, &decl.role)?,
+                lookup(&policy.types, SymbolKind::Type, &decl.ty)?,
+                empty_range(),
+            );
+
+            policy
+                .ocontexts
+                .initial_sids_mut()
+                .push(InitialSid::new(decl.sid, context));
+        }
+
+        Ok(policy)
+    }
+
+    fn build_commons(&self) -> Result<SymbolTable<Common>, BuildError> {
+        let mut commons = SymbolTable::with_capacity(self.commons.len());
+
+        for (idx, &(ref name, ref perms)) in self.commons.iter().enumerate() {
+            let permissions = build_permissions(name, perms, 0)?;
+            let common = Common::new(idx as u32 + 1, name.clone(), permissions);
+
+            insert(&mut commons, SymbolKind::Common, common)?;
+        }
+
+        Ok(commons)
+    }
+
+    fn build_classes(
+        &self,
+        commons: &SymbolTable<Common>,
+    ) -> Result<SymbolTable<Class>, BuildError> {
+        let mut classes = SymbolTable::with_capacity(self.classes.len());
+
+        for (idx, decl) in self.classes.iter().enumerate() {
+            // Class permissions are numbered after the permissions of the common.
+            let num_common_perms = match decl.common {
+                Some(ref common) => commons
+                    .get_by_name(common)
+                    .ok_or_else(|| BuildError::UnknownSymbol {
+                        kind: SymbolKind::Common,
+                        name: common.clone(),
+                    })?
+                    .permissions()
+                    .num_primary_names(),
+                None => 0,
+            };
+
+            let permissions = build_permissions(&decl.name, &decl.permissions, num_common_perms)?;
+            let class = Class::new(
+                idx as u32 + 1,
+                decl.name.clone(),
+                decl.common.clone(),
+                permissions,
+            );
+
+            insert(&mut classes, SymbolKind::Class, class)?;
+        }
+
+        Ok(classes)
+    }
+
+    /// Builds the types table, along with the member types of each attribute indexed by the
+    /// attribute's 0-based bit.
+    fn build_types(&self) -> Result<(SymbolTable<Type>, Vec<Bitmap>), BuildError> {
+        let mut types = SymbolTable::with_capacity(self.types.len());
+        let mut next_id = 1;
+
+        for decl in self
+            .types
+            .iter()
+            .filter(|decl| decl.flavor != TypeFlavor::Alias)
+        {
+            let ty = Type::new(next_id, decl.name.clone(), decl.flavor);
+
+            insert(&mut types, SymbolKind::Type, ty)?;
+            next_id += 1;
+        }
+
+        for decl in self
+            .types
+            .iter()
+            .filter(|decl| decl.flavor == TypeFlavor::Alias)
+        {
+            let primary = decl.primary.as_ref().map_or("", |primary| primary.as_str());
+            let id = lookup(&types, SymbolKind::Type, primary)?;
+
+            insert(
+                &mut types,
+                SymbolKind::Type,
+                Type::new(id, decl.name.clone(), TypeFlavor::Alias),
+            )?;
+        }
+
+        for name in &self.permissive_types {
+            let id = lookup(&types, SymbolKind::Type, name)?;
+
+            if let Some(ty) = types.get_mut(id) {
+                ty.set_permissive(true);
+            }
+        }
+
+        let mut members = vec![Bitmap::create(); types.num_primary_names() as usize];
+
+        for &(ref ty_name, ref attr_name) in &self.type_attributes {
+            let ty = lookup(&types, SymbolKind::Type, ty_name)?;
+            let attr = types
+                .get_by_name(attr_name)
+                .ok_or_else(|| BuildError::UnknownSymbol {
+                    kind: SymbolKind::Type,
+                    name: attr_name.clone(),
+                })?;
+
+            if !attr.is_attribute() {
+                return Err(BuildError::NotAnAttribute(attr_name.clone()));
+            }
+
+            members[attr.id() as usize - 1].add(ty - 1);
+        }
+
+        Ok((types, members))
+    }
+
+    fn build_roles(
+        &self,
+        types: &SymbolTable<Type>,
+        members: &[Bitmap],
+    ) -> Result<SymbolTable<Role>, BuildError> {
+        let mut roles = SymbolTable::with_capacity(self.roles.len() + 1);
+
+        roles.insert(Role::new(
+            OBJECT_R_VAL,
+            "object_r".to_string(),
+            Bitmap::create(),
+        ));
+
+        for (idx, &(ref name, ref type_names)) in self.roles.iter().enumerate() {
+            let mut role_types = Bitmap::create();
+
+            for ty in type_names {
+                role_types.or_inplace(&expand_type(types, members, ty)?);
+            }
+
+            let role = Role::new(idx as u32 + 2, name.clone(), role_types);
+            insert(&mut roles, SymbolKind::Role, role)?;
+        }
+
+        Ok(roles)
+    }
+
+    fn build_rules(&self, policy: &mut Policy, members: &[Bitmap]) -> Result<(), BuildError> {
+        let mut avtab = AccessVectorTable::default();
+        let mut conditional_avtabs: BTreeMap<u32, AccessVectorTable> = BTreeMap::new();
+
+        for rule in &self.rules {
+            let class = policy.classes.get_by_name(&rule.class).ok_or_else(|| {
+                BuildError::UnknownSymbol {
+                    kind: SymbolKind::Class,
+                    name: rule.class.clone(),
+                }
+            })?;
+
+            let data = match rule.new_type {
+                Some(ref new_type) => lookup(&policy.types, SymbolKind::Type, new_type)?,
+                None => access_vector(policy, class, &rule.permissions)?,
+            };
+
+            let avtab = match rule.boolean {
+                Some(ref boolean) => {
+                    let boolean = lookup(&policy.booleans, SymbolKind::Boolean, boolean)?;
+
+                    conditional_avtabs
+                        .entry(boolean)
+                        .or_insert_with(AccessVectorTable::default)
+                }
+                None => &mut avtab,
+            };
+
+            let source_types = expand_type(&policy.types, members, &rule.source)?;
+            let target_types = if rule.target == SELF_TYPE {
+                None
+            } else {
+                Some(expand_type(&policy.types, members, &rule.target)?)
+            };
+
+            for source_type in source_types.iter() {
+                let self_target = Bitmap::of(&[source_type]);
+                let targets = target_types.as_ref().unwrap_or(&self_target);
+
+                for target_type in targets.iter() {
+                    avtab
+                        .add(
+                            source_type as u16 + 1,
+                            target_type as u16 + 1,
+                            class.id() as u16,
+                            rule.kind,
+                            data,
+                            None,
+                        )
+                        .map_err(|_| BuildError::ConflictingTypeRule {
+                            source_type: symbol_name(&policy.types, source_type + 1),
+                            target_type: symbol_name(&policy.types, target_type + 1),
+                            class: rule.class.clone(),
+                        })?;
+                }
+            }
+        }
+
+        policy.avtab = avtab;
+        policy.conditionals = conditional_avtabs
+            .into_iter()
+            .map(|(boolean, avtab)| {
+                let state = policy
+                    .booleans
+                    .get(boolean)
+                    .map_or(false, |boolean| boolean.is_toggled());
+
+                ConditionalNode::new(
+                    state,
+                    vec![ConditionalExpression::Bool(boolean)],
+                    avtab,
+                    AccessVectorTable::default(),
+                )
+            })
+            .collect();
+
+        Ok(())
+    }
+}
+
+fn to_strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn build_permissions(
+    class: &str,
+    names: &[String],
+    first_id: u32,
+) -> Result<SymbolTable<Permission>, BuildError> {
+    if first_id as usize + names.len() > MAX_PERMISSIONS {
+        return Err(BuildError::TooManyPermissions(class.to_string()));
+    }
+
+    let mut permissions = SymbolTable::with_capacity(names.len());
+
+    for (idx, name) in names.iter().enumerate() {
+        if permissions.get_by_name(name).is_some() {
+            return Err(BuildError::DuplicatePermission {
+                class: class.to_string(),
+                permission: name.clone(),
+            });
+        }
+
+        permissions.insert(Permission::new(first_id + idx as u32 + 1, name.clone()));
+    }
+
+    permissions.set_num_primary_names(first_id + names.len() as u32);
+
+    Ok(permissions)
+}
+
+fn insert<S: Symbol>(
+    table: &mut SymbolTable<S>,
+    kind: SymbolKind,
+    sym: S,
+) -> Result<(), BuildError> {
+    if table.get_by_name(sym.name()).is_some() {
+        return Err(BuildError::DuplicateSymbol {
+            kind,
+            name: sym.name().to_string(),
+        });
+    }
+
+    table.insert(sym);
+
+    Ok(())
+}
+
+/// The 0-based bits of the concrete types named by a type or attribute.
+fn expand_type(
+    types: &SymbolTable<Type>,
+    members: &[Bitmap],
+    name: &str,
+) -> Result<Bitmap, BuildError> {
+    let ty = types
+        .get_by_name(name)
+        .ok_or_else(|| BuildError::UnknownSymbol {
+            kind: SymbolKind::Type,
+            name: name.to_string(),
+        })?;
+
+    if ty.is_attribute() {
+        Ok(members[ty.id() as usize - 1].clone())
+    } else {
+        Ok(Bitmap::of(&[ty.id() - 1]))
+    }
+}
+
+fn symbol_name<S: Symbol>(table: &SymbolTable<S>, id: u32) -> String {
+    table
+        .get(id)
+        .map_or_else(|| id.to_string(), |sym| sym.name().to_string())
+}
@@ -4,9 +4,93 @@ use policydb::Feature;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
 use policydb::Symbol;
 use policydb::SymbolTable;
-use std::io::Read;
+use std::io::{Read, Write};
+
+pub(crate) mod constants {
+    pub const DEFAULT_SOURCE: u32 = 1;
+    pub const DEFAULT_TARGET: u32 = 2;
+
+    pub const DEFAULT_SOURCE_LOW: u32 = 1;
+    pub const DEFAULT_SOURCE_HIGH: u32 = 2;
+    pub const DEFAULT_SOURCE_LOW_HIGH: u32 = 3;
+    pub const DEFAULT_TARGET_LOW: u32 = 4;
+    pub const DEFAULT_TARGET_HIGH: u32 = 5;
+    pub const DEFAULT_TARGET_LOW_HIGH: u32 = 6;
+    pub const DEFAULT_GLBLUB: u32 = 7;
+}
+
+/// The context that the user, role or type of a new object is taken from, as set by the
+/// `default_user`, `default_role` and `default_type` statements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectDefault {
+    Source,
+    Target,
+}
+
+impl ObjectDefault {
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            constants::DEFAULT_SOURCE => Some(ObjectDefault::Source),
+            constants::DEFAULT_TARGET => Some(ObjectDefault::Target),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            ObjectDefault::Source => constants::DEFAULT_SOURCE,
+            ObjectDefault::Target => constants::DEFAULT_TARGET,
+        }
+    }
+}
+
+/// The levels that the range of a new object is taken from, as set by the `default_range`
+/// statement.  `Glblub` uses the overlap of the source and target ranges.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeDefault {
+    SourceLow,
+    SourceHigh,
+    SourceLowHigh,
+    TargetLow,
+    TargetHigh,
+    TargetLowHigh,
+    Glblub,
+}
+
+impl RangeDefault {
+    pub fn from_id(id: u32) -> Option<Self> {
+        use self::constants::*;
+
+        match id {
+            DEFAULT_SOURCE_LOW => Some(RangeDefault::SourceLow),
+            DEFAULT_SOURCE_HIGH => Some(RangeDefault::SourceHigh),
+            DEFAULT_SOURCE_LOW_HIGH => Some(RangeDefault::SourceLowHigh),
+            DEFAULT_TARGET_LOW => Some(RangeDefault::TargetLow),
+            DEFAULT_TARGET_HIGH => Some(RangeDefault::TargetHigh),
+            DEFAULT_TARGET_LOW_HIGH => Some(RangeDefault::TargetLowHigh),
+            DEFAULT_GLBLUB => Some(RangeDefault::Glblub),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        use self::constants::*;
+
+        match *self {
+            RangeDefault::SourceLow => DEFAULT_SOURCE_LOW,
+            RangeDefault::SourceHigh => DEFAULT_SOURCE_HIGH,
+            RangeDefault::SourceLowHigh => DEFAULT_SOURCE_LOW_HIGH,
+            RangeDefault::TargetLow => DEFAULT_TARGET_LOW,
+            RangeDefault::TargetHigh => DEFAULT_TARGET_HIGH,
+            RangeDefault::TargetLowHigh => DEFAULT_TARGET_LOW_HIGH,
+            RangeDefault::Glblub => DEFAULT_GLBLUB,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Common {
@@ -16,6 +100,14 @@ pub struct Common {
 }
 
 impl Common {
+    pub(crate) fn new(id: u32, name: String, permissions: SymbolTable<Permission>) -> Self {
+        Common {
+            id,
+            name,
+            permissions,
+        }
+    }
+
     pub fn permissions(&self) -> &SymbolTable<Permission> {
         &self.permissions
     }
@@ -38,15 +130,26 @@ impl PolicyObject for Common {
         let num_perm_names = reader.read_u32()?;
         let num_perm_elements = reader.read_u32()?;
         let name = reader.read_string(name_len as usize)?;
-        let permissions =
+        let mut permissions =
             reader.read_bare_symbol_table::<Permission>(num_perm_elements as usize)?;
 
+        permissions.set_num_primary_names(num_perm_names);
+
         Ok(Common {
             id,
             name,
             permissions,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_u32(self.id)?;
+        writer.write_u32(self.permissions.num_primary_names())?;
+        writer.write_len(self.permissions.num_entries())?;
+        writer.write_string(&self.name)?;
+        writer.write_bare_symbol_table(&self.permissions)
+    }
 }
 
 #[derive(Debug)]
@@ -65,6 +168,106 @@ pub struct Class {
     default_type: Option<u32>,
 }
 
+impl Class {
+    pub(crate) fn new(
+        id: u32,
+        name: String,
+        common_name: Option<String>,
+        permissions: SymbolTable<Permission>,
+    ) -> Self {
+        Class {
+            id,
+            name,
+            common_name,
+            permissions,
+            constraints: vec![],
+            transition_constraints: vec![],
+            default_user: None,
+            default_role: None,
+            default_range: None,
+            default_type: None,
+        }
+    }
+
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_ref().map(|name| name.as_str())
+    }
+
+    pub fn permissions(&self) -> &SymbolTable<Permission> {
+        &self.permissions
+    }
+
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    /// The `validatetrans` constraints of this class.
+    pub fn transition_constraints(&self) -> &[Constraint] {
+        &self.transition_constraints
+    }
+
+    /// The `constrain` and `validatetrans` constraints of this class.
+    pub(crate) fn all_constraints_mut<'a>(
+        &'a mut self,
+    ) -> impl Iterator<Item = &'a mut Constraint> + 'a {
+        self.constraints
+            .iter_mut()
+            .chain(self.transition_constraints.iter_mut())
+    }
+
+    pub fn default_user(&self) -> Option<ObjectDefault> {
+        self.default_user.and_then(ObjectDefault::from_id)
+    }
+
+    pub fn default_role(&self) -> Option<ObjectDefault> {
+        self.default_role.and_then(ObjectDefault::from_id)
+    }
+
+    pub fn default_type(&self) -> Option<ObjectDefault> {
+        self.default_type.and_then(ObjectDefault::from_id)
+    }
+
+    pub fn default_range(&self) -> Option<RangeDefault> {
+        self.default_range.and_then(RangeDefault::from_id)
+    }
+
+    /// Clears the object defaults that can't be represented by the given policy version, and
+    /// returns the features they needed.
+    pub(crate) fn discard_defaults(&mut self, profile: &CompatibilityProfile) -> Vec<Feature> {
+        fn is_set(default: Option<u32>) -> bool {
+            default.map_or(false, |default| default != 0)
+        }
+
+        let mut discarded = vec![];
+
+        if !profile.supports(Feature::NewObjectDefaults) {
+            if is_set(self.default_user) || is_set(self.default_role) || is_set(self.default_range)
+            {
+                discarded.push(Feature::NewObjectDefaults);
+            }
+
+            self.default_user = None;
+            self.default_role = None;
+            self.default_range = None;
+        } else if !profile.supports(Feature::DefaultRangeGlblub)
+            && self.default_range == Some(constants::DEFAULT_GLBLUB)
+        {
+            discarded.push(Feature::DefaultRangeGlblub);
+            self.default_range = Some(0);
+        }
+
+        if !profile.supports(Feature::DefaultType) {
+            if is_set(self.default_type) {
+                discarded.push(Feature::DefaultType);
+            }
+
+            self.default_type = None;
+        }
+
+        discarded
+    }
+}
+
 impl Symbol for Class {
     fn id(&self) -> u32 {
         self.id
@@ -80,7 +283,7 @@ impl PolicyObject for Class {
         let name_len = reader.read_u32()?;
         let common_name_len = reader.read_u32()?;
         let id = reader.read_u32()?;
-        let _num_perm_names = reader.read_u32()?;
+        let num_perm_names = reader.read_u32()?;
         let num_perm_elements = reader.read_u32()? as usize;
         let num_constraints = reader.read_u32()? as usize;
 
@@ -91,9 +294,12 @@ impl PolicyObject for Class {
             Some(reader.read_string(common_name_len as usize)?)
         };
 
-        let permissions: SymbolTable<Permission> =
+        let mut permissions: SymbolTable<Permission> =
             reader.read_bare_symbol_table(num_perm_elements as usize)?;
 
+        // Permissions inherited from the common are counted, but not stored with the class.
+        permissions.set_num_primary_names(num_perm_names);
+
         let constraints: Vec<Constraint> = reader.read_objects(num_constraints)?;
         let transition_constraints = if reader.profile().supports(Feature::ValidateTransition) {
             let num_transition_constraints = reader.read_u32()? as usize;
@@ -102,7 +308,7 @@ impl PolicyObject for Class {
             vec![]
         };
 
-        let (default_user, default_range, default_role) =
+        let (default_user, default_role, default_range) =
             if reader.profile().supports(Feature::NewObjectDefaults) {
                 (
                     Some(reader.read_u32()?),
@@ -132,6 +338,42 @@ impl PolicyObject for Class {
             default_type,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        let common_name = self.common_name().unwrap_or("");
+
+        writer.write_len(self.name.len())?;
+        writer.write_len(common_name.len())?;
+        writer.write_u32(self.id)?;
+        writer.write_u32(self.permissions.num_primary_names())?;
+        writer.write_len(self.permissions.num_entries())?;
+        writer.write_len(self.constraints.len())?;
+        writer.write_string(&self.name)?;
+        writer.write_string(common_name)?;
+        writer.write_bare_symbol_table(&self.permissions)?;
+        writer.write_objects(&self.constraints)?;
+
+        if writer.profile().supports(Feature::ValidateTransition) {
+            writer.write_len(self.transition_constraints.len())?;
+            writer.write_objects(&self.transition_constraints)?;
+        } else if !self.transition_constraints.is_empty() {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::ValidateTransition,
+            ));
+        }
+
+        if writer.profile().supports(Feature::NewObjectDefaults) {
+            writer.write_u32(self.default_user.unwrap_or(0))?;
+            writer.write_u32(self.default_role.unwrap_or(0))?;
+            writer.write_u32(self.default_range.unwrap_or(0))?;
+        }
+
+        if writer.profile().supports(Feature::DefaultType) {
+            writer.write_u32(self.default_type.unwrap_or(0))?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -140,6 +382,12 @@ pub struct Permission {
     name: String,
 }
 
+impl Permission {
+    pub(crate) fn new(id: u32, name: String) -> Self {
+        Permission { id, name }
+    }
+}
+
 impl PolicyObject for Permission {
     fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
         let name_len = reader.read_u32()?;
@@ -148,6 +396,14 @@ impl PolicyObject for Permission {
 
         Ok(Permission { id, name })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_u32(self.id)?;
+        writer.write_string(&self.name)?;
+
+        Ok(())
+    }
 }
 
 impl Symbol for Permission {
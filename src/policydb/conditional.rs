@@ -1,9 +1,30 @@
+use policydb::AccessVectorTable;
+use policydb::AvRuleList;
 use policydb::Feature;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Remap;
 use policydb::Symbol;
-use std::io::Read;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use std::io::{Read, Write};
+
+pub(crate) mod constants {
+    pub const COND_BOOL: u32 = 1; /* plain bool */
+    pub const COND_NOT: u32 = 2; /* !bool */
+    pub const COND_OR: u32 = 3; /* bool || bool */
+    pub const COND_AND: u32 = 4; /* bool && bool */
+    pub const COND_XOR: u32 = 5; /* bool ^ bool */
+    pub const COND_EQ: u32 = 6; /* bool == bool */
+    pub const COND_NEQ: u32 = 7; /* bool != bool */
+
+    pub const COND_BOOL_FLAGS_TUNABLE: u32 = 0x01;
+
+    pub const COND_NODE_FLAGS_TUNABLE: u32 = 0x01;
+}
 
 #[derive(Debug)]
 pub struct Boolean {
@@ -13,10 +34,40 @@ pub struct Boolean {
     flags: u32,
 }
 
+impl Remap for Boolean {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.id = map.id(SymbolKind::Boolean, self.id);
+    }
+}
+
 impl Boolean {
+    pub(crate) fn new(id: u32, name: String, state: bool) -> Self {
+        Boolean {
+            id,
+            name,
+            state,
+            flags: 0,
+        }
+    }
+
     pub fn is_toggled(&self) -> bool {
         self.state
     }
+
+    pub(crate) fn set_toggled(&mut self, state: bool) {
+        self.state = state;
+    }
+
+    /// Checks if this is a tunable, which is resolved when the policy is built instead of being
+    /// toggled at runtime.  Only modules record tunables.
+    pub fn is_tunable(&self) -> bool {
+        self.flags & constants::COND_BOOL_FLAGS_TUNABLE != 0
+    }
+
+    /// Turns a tunable into a runtime boolean, for tunables that are used alongside booleans.
+    pub(crate) fn clear_tunable(&mut self) {
+        self.flags &= !constants::COND_BOOL_FLAGS_TUNABLE;
+    }
 }
 
 impl Symbol for Boolean {
@@ -49,4 +100,233 @@ impl PolicyObject for Boolean {
             flags,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.id)?;
+        writer.write_u32(self.state as u32)?;
+        writer.write_len(self.name.len())?;
+        writer.write_string(&self.name)?;
+
+        if writer.profile().supports(Feature::TunableSep) {
+            writer.write_u32(self.flags)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single token of a conditional expression, stored in reverse polish notation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionalExpression {
+    /// Push the value of the boolean with the given id.
+    Bool(u32),
+    Not,
+    Or,
+    And,
+    Xor,
+    Eq,
+    Neq,
+}
+
+impl PolicyObject for ConditionalExpression {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let expr_ty = reader.read_u32()?;
+        let boolean = reader.read_u32()?;
+
+        Ok(match expr_ty {
+            constants::COND_BOOL => ConditionalExpression::Bool(boolean),
+            constants::COND_NOT => ConditionalExpression::Not,
+            constants::COND_OR => ConditionalExpression::Or,
+            constants::COND_AND => ConditionalExpression::And,
+            constants::COND_XOR => ConditionalExpression::Xor,
+            constants::COND_EQ => ConditionalExpression::Eq,
+            constants::COND_NEQ => ConditionalExpression::Neq,
+            _ => return Err(PolicyReadError::InvalidConditionalExpression(expr_ty)),
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        let (expr_ty, boolean) = match *self {
+            ConditionalExpression::Bool(boolean) => (constants::COND_BOOL, boolean),
+            ConditionalExpression::Not => (constants::COND_NOT, 0),
+            ConditionalExpression::Or => (constants::COND_OR, 0),
+            ConditionalExpression::And => (constants::COND_AND, 0),
+            ConditionalExpression::Xor => (constants::COND_XOR, 0),
+            ConditionalExpression::Eq => (constants::COND_EQ, 0),
+            ConditionalExpression::Neq => (constants::COND_NEQ, 0),
+        };
+
+        writer.write_u32(expr_ty)?;
+        writer.write_u32(boolean)?;
+
+        Ok(())
+    }
+}
+
+/// A conditional block from the policy's `cond_list`, pairing a boolean expression with the
+/// rules that apply when it evaluates to true or false.  Kernel policies store these rules in an
+/// `AccessVectorTable`, while modules keep the unexpanded `AvRuleList`.
+#[derive(Debug)]
+pub struct ConditionalNode<Rules = AccessVectorTable> {
+    state: bool,
+    expression: Vec<ConditionalExpression>,
+    true_list: Rules,
+    false_list: Rules,
+    flags: u32,
+}
+
+impl<Rules> ConditionalNode<Rules> {
+    pub(crate) fn new(
+        state: bool,
+        expression: Vec<ConditionalExpression>,
+        true_list: Rules,
+        false_list: Rules,
+    ) -> Self {
+        ConditionalNode {
+            state,
+            expression,
+            true_list,
+            false_list,
+            flags: 0,
+        }
+    }
+
+    /// Creates a node with the same expression as this one, holding a different set of rules.
+    pub(crate) fn with_rules<NewRules>(
+        &self,
+        true_list: NewRules,
+        false_list: NewRules,
+    ) -> ConditionalNode<NewRules> {
+        ConditionalNode {
+            state: self.state,
+            expression: self.expression.clone(),
+            true_list,
+            false_list,
+            flags: self.flags,
+        }
+    }
+
+    /// The value of the expression when the policy was written.
+    pub fn state(&self) -> bool {
+        self.state
+    }
+
+    pub fn expression(&self) -> &[ConditionalExpression] {
+        &self.expression
+    }
+
+    pub fn true_list(&self) -> &Rules {
+        &self.true_list
+    }
+
+    pub fn false_list(&self) -> &Rules {
+        &self.false_list
+    }
+
+    pub(crate) fn lists_mut(&mut self) -> (&mut Rules, &mut Rules) {
+        (&mut self.true_list, &mut self.false_list)
+    }
+
+    pub(crate) fn set_state(&mut self, state: bool) {
+        self.state = state;
+    }
+
+    /// Checks if the compiler marked this node as only depending on tunables.
+    pub fn is_tunable(&self) -> bool {
+        self.flags & constants::COND_NODE_FLAGS_TUNABLE != 0
+    }
+
+    /// The ids of the booleans used in the expression.
+    pub fn boolean_ids<'a>(&'a self) -> impl Iterator<Item = u32> + 'a {
+        self.expression.iter().filter_map(|expr| match *expr {
+            ConditionalExpression::Bool(boolean) => Some(boolean),
+            _ => None,
+        })
+    }
+
+    /// Evaluates the expression with the boolean values given by `value_of`, or `None` if the
+    /// expression is malformed.
+    pub fn evaluate<F: Fn(u32) -> bool>(&self, value_of: F) -> Option<bool> {
+        let mut stack: Vec<bool> = Vec::with_capacity(self.expression.len());
+
+        for expr in &self.expression {
+            let value = match *expr {
+                ConditionalExpression::Bool(boolean) => value_of(boolean),
+                ConditionalExpression::Not => !stack.pop()?,
+                _ => {
+                    let rhs = stack.pop()?;
+                    let lhs = stack.pop()?;
+
+                    match *expr {
+                        ConditionalExpression::Or => lhs || rhs,
+                        ConditionalExpression::And => lhs && rhs,
+                        ConditionalExpression::Xor => lhs ^ rhs,
+                        ConditionalExpression::Eq => lhs == rhs,
+                        _ => lhs != rhs,
+                    }
+                }
+            };
+
+            stack.push(value);
+        }
+
+        match stack.len() {
+            1 => stack.pop(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn remap_booleans(&mut self, map: &SymbolMap) {
+        for expr in &mut self.expression {
+            if let ConditionalExpression::Bool(ref mut boolean) = *expr {
+                *boolean = map.id(SymbolKind::Boolean, *boolean);
+            }
+        }
+    }
+}
+
+impl Remap for ConditionalNode<AvRuleList> {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.remap_booleans(map);
+        self.true_list.remap(map);
+        self.false_list.remap(map);
+    }
+}
+
+impl<Rules: PolicyObject> PolicyObject for ConditionalNode<Rules> {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let state = reader.read_u32()? == 1;
+        let num_exprs = reader.read_u32()? as usize;
+        let expression: Vec<ConditionalExpression> = reader.read_objects(num_exprs)?;
+        let true_list: Rules = reader.read_object()?;
+        let false_list: Rules = reader.read_object()?;
+
+        let flags = if reader.profile().supports(Feature::TunableSep) {
+            reader.read_u32()?
+        } else {
+            0
+        };
+
+        Ok(ConditionalNode {
+            state,
+            expression,
+            true_list,
+            false_list,
+            flags,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.state as u32)?;
+        writer.write_len(self.expression.len())?;
+        writer.write_objects(&self.expression)?;
+        writer.write_object(&self.true_list)?;
+        writer.write_object(&self.false_list)?;
+
+        if writer.profile().supports(Feature::TunableSep) {
+            writer.write_u32(self.flags)?;
+        }
+
+        Ok(())
+    }
 }
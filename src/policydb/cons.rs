@@ -1,10 +1,12 @@
 use croaring::Bitmap;
-use policydb::CompatibilityProfile;
+use policydb::Feature;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
 use policydb::TypeSet;
-use std::io::Read;
+use std::io::{Read, Write};
 
 pub(crate) mod constants {
     pub const CEXPR_NOT: u32 = 1; /* not expr */
@@ -12,6 +14,24 @@ pub(crate) mod constants {
     pub const CEXPR_OR: u32 = 3; /* expr or expr */
     pub const CEXPR_ATTR: u32 = 4; /* attr op attr */
     pub const CEXPR_NAMES: u32 = 5; /* attr op names */
+
+    pub const CEXPR_USER: u32 = 1; /* user */
+    pub const CEXPR_ROLE: u32 = 2; /* role */
+    pub const CEXPR_TYPE: u32 = 4; /* type */
+    pub const CEXPR_TARGET: u32 = 8; /* target if set, source otherwise */
+    pub const CEXPR_XTARGET: u32 = 16; /* special 3rd target for validatetrans rule */
+    pub const CEXPR_L1L2: u32 = 32; /* low level 1 vs. low level 2 */
+    pub const CEXPR_L1H2: u32 = 64; /* low level 1 vs. high level 2 */
+    pub const CEXPR_H1L2: u32 = 128; /* high level 1 vs. low level 2 */
+    pub const CEXPR_H1H2: u32 = 256; /* high level 1 vs. high level 2 */
+    pub const CEXPR_L1H1: u32 = 512; /* low level 1 vs. high level 1 */
+    pub const CEXPR_L2H2: u32 = 1024; /* low level 2 vs. high level 2 */
+
+    pub const CEXPR_EQ: u32 = 1; /* == or eq */
+    pub const CEXPR_NEQ: u32 = 2; /* != */
+    pub const CEXPR_DOM: u32 = 3; /* dom */
+    pub const CEXPR_DOMBY: u32 = 4; /* domby  */
+    pub const CEXPR_INCOMP: u32 = 5; /* incomp */
 }
 
 #[derive(Debug)]
@@ -20,6 +40,18 @@ pub struct Constraint {
     pub(crate) expressions: Vec<ConstraintExpression>,
 }
 
+impl Constraint {
+    /// The permissions this constraint applies to, as an access vector of the owning class.
+    pub fn permissions(&self) -> u32 {
+        self.permissions
+    }
+
+    /// The constraint expression, in reverse polish notation.
+    pub fn expressions(&self) -> &[ConstraintExpression] {
+        &self.expressions
+    }
+}
+
 #[derive(Debug)]
 pub enum UnaryOp {
     Not,
@@ -31,13 +63,131 @@ pub enum BinaryOp {
     Or,
 }
 
+/// The operator used to compare the operands of a constraint expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintOp {
+    Eq,
+    Neq,
+    Dom,
+    DomBy,
+    Incomp,
+}
+
+impl ConstraintOp {
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            constants::CEXPR_EQ => Some(ConstraintOp::Eq),
+            constants::CEXPR_NEQ => Some(ConstraintOp::Neq),
+            constants::CEXPR_DOM => Some(ConstraintOp::Dom),
+            constants::CEXPR_DOMBY => Some(ConstraintOp::DomBy),
+            constants::CEXPR_INCOMP => Some(ConstraintOp::Incomp),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            ConstraintOp::Eq => constants::CEXPR_EQ,
+            ConstraintOp::Neq => constants::CEXPR_NEQ,
+            ConstraintOp::Dom => constants::CEXPR_DOM,
+            ConstraintOp::DomBy => constants::CEXPR_DOMBY,
+            ConstraintOp::Incomp => constants::CEXPR_INCOMP,
+        }
+    }
+}
+
+/// The left hand operand of a constraint expression.  In an `Attr` expression the source
+/// attributes (`u1`, `r1`, `t1`) are compared against their target counterpart (`u2`, `r2`, `t2`),
+/// while in a `Names` expression the attribute is compared against a set of names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintAttribute {
+    U1,
+    U2,
+    U3,
+    R1,
+    R2,
+    R3,
+    T1,
+    T2,
+    T3,
+    L1L2,
+    L1H2,
+    H1L2,
+    H1H2,
+    L1H1,
+    L2H2,
+}
+
+impl ConstraintAttribute {
+    pub fn from_id(id: u32) -> Option<Self> {
+        use self::constants::*;
+
+        match id {
+            CEXPR_USER => Some(ConstraintAttribute::U1),
+            x if x == CEXPR_USER | CEXPR_TARGET => Some(ConstraintAttribute::U2),
+            x if x == CEXPR_USER | CEXPR_XTARGET => Some(ConstraintAttribute::U3),
+            CEXPR_ROLE => Some(ConstraintAttribute::R1),
+            x if x == CEXPR_ROLE | CEXPR_TARGET => Some(ConstraintAttribute::R2),
+            x if x == CEXPR_ROLE | CEXPR_XTARGET => Some(ConstraintAttribute::R3),
+            CEXPR_TYPE => Some(ConstraintAttribute::T1),
+            x if x == CEXPR_TYPE | CEXPR_TARGET => Some(ConstraintAttribute::T2),
+            x if x == CEXPR_TYPE | CEXPR_XTARGET => Some(ConstraintAttribute::T3),
+            CEXPR_L1L2 => Some(ConstraintAttribute::L1L2),
+            CEXPR_L1H2 => Some(ConstraintAttribute::L1H2),
+            CEXPR_H1L2 => Some(ConstraintAttribute::H1L2),
+            CEXPR_H1H2 => Some(ConstraintAttribute::H1H2),
+            CEXPR_L1H1 => Some(ConstraintAttribute::L1H1),
+            CEXPR_L2H2 => Some(ConstraintAttribute::L2H2),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        use self::constants::*;
+
+        match *self {
+            ConstraintAttribute::U1 => CEXPR_USER,
+            ConstraintAttribute::U2 => CEXPR_USER | CEXPR_TARGET,
+            ConstraintAttribute::U3 => CEXPR_USER | CEXPR_XTARGET,
+            ConstraintAttribute::R1 => CEXPR_ROLE,
+            ConstraintAttribute::R2 => CEXPR_ROLE | CEXPR_TARGET,
+            ConstraintAttribute::R3 => CEXPR_ROLE | CEXPR_XTARGET,
+            ConstraintAttribute::T1 => CEXPR_TYPE,
+            ConstraintAttribute::T2 => CEXPR_TYPE | CEXPR_TARGET,
+            ConstraintAttribute::T3 => CEXPR_TYPE | CEXPR_XTARGET,
+            ConstraintAttribute::L1L2 => CEXPR_L1L2,
+            ConstraintAttribute::L1H2 => CEXPR_L1H2,
+            ConstraintAttribute::H1L2 => CEXPR_H1L2,
+            ConstraintAttribute::H1H2 => CEXPR_H1H2,
+            ConstraintAttribute::L1H1 => CEXPR_L1H1,
+            ConstraintAttribute::L2H2 => CEXPR_L2H2,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ConstraintExpression {
-    pub(crate) op: u32,
-    pub(crate) attr: u32,
+    pub(crate) op: Option<ConstraintOp>,
+    pub(crate) attr: Option<ConstraintAttribute>,
     pub(crate) kind: ConstraintExpressionKind,
 }
 
+impl ConstraintExpression {
+    /// The comparison operator, present for `Attr` and `Names` expressions.
+    pub fn op(&self) -> Option<ConstraintOp> {
+        self.op
+    }
+
+    /// The compared attribute, present for `Attr` and `Names` expressions.
+    pub fn attr(&self) -> Option<ConstraintAttribute> {
+        self.attr
+    }
+
+    pub fn kind(&self) -> &ConstraintExpressionKind {
+        &self.kind
+    }
+}
+
 #[derive(Debug)]
 pub enum ConstraintExpressionKind {
     Unary(UnaryOp),
@@ -55,10 +205,14 @@ impl PolicyObject for Constraint {
         let num_exprs = reader.read_u32()? as usize;
         let mut expressions: Vec<ConstraintExpression> = Vec::with_capacity(num_exprs);
 
+        // Modules have always recorded the type set used in the source policy, kernel policies
+        // have only done so since `POLICYDB_VERSION_CONSTRAINT_NAMES`.
+        let has_type_names = reader.profile().supports(Feature::ConstraintNames);
+
         for _ in 0..num_exprs {
             let expr_ty = reader.read_u32()?;
-            let attr = reader.read_u32()?;
-            let op = reader.read_u32()?;
+            let attr_id = reader.read_u32()?;
+            let op_id = reader.read_u32()?;
 
             let kind = match expr_ty {
                 constants::CEXPR_NOT => ConstraintExpressionKind::Unary(UnaryOp::Not),
@@ -67,18 +221,37 @@ impl PolicyObject for Constraint {
                 constants::CEXPR_ATTR => ConstraintExpressionKind::Attr,
                 constants::CEXPR_NAMES => {
                     let names = reader.read_object()?;
-                    let types = reader.read_object()?;
-                    let inverse_types = reader.read_object()?;
-                    let flags = reader.read_u32()?;
-                    let type_names = Some(TypeSet::Set {
-                        flags,
-                        types,
-                        inverse_types,
-                    });
+                    let type_names = if has_type_names {
+                        let types = reader.read_object()?;
+                        let inverse_types = reader.read_object()?;
+                        let flags = reader.read_u32()?;
+
+                        Some(TypeSet::Set {
+                            flags,
+                            types,
+                            inverse_types,
+                        })
+                    } else {
+                        None
+                    };
 
                     ConstraintExpressionKind::Names { names, type_names }
                 }
-                _ => return Err(PolicyReadError::InvalidPolicyCapability),
+                _ => return Err(PolicyReadError::InvalidConstraintExpression(expr_ty)),
+            };
+
+            let (attr, op) = match kind {
+                ConstraintExpressionKind::Attr | ConstraintExpressionKind::Names { .. } => (
+                    Some(
+                        ConstraintAttribute::from_id(attr_id)
+                            .ok_or(PolicyReadError::InvalidConstraintAttribute(attr_id))?,
+                    ),
+                    Some(
+                        ConstraintOp::from_id(op_id)
+                            .ok_or(PolicyReadError::InvalidConstraintOperator(op_id))?,
+                    ),
+                ),
+                _ => (None, None),
             };
 
             expressions.push(ConstraintExpression { op, attr, kind });
@@ -89,4 +262,52 @@ impl PolicyObject for Constraint {
             expressions,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        let has_type_names = writer.profile().supports(Feature::ConstraintNames);
+
+        writer.write_u32(self.permissions)?;
+        writer.write_len(self.expressions.len())?;
+
+        for expr in &self.expressions {
+            let expr_ty = match expr.kind {
+                ConstraintExpressionKind::Unary(UnaryOp::Not) => constants::CEXPR_NOT,
+                ConstraintExpressionKind::Binary(BinaryOp::And) => constants::CEXPR_AND,
+                ConstraintExpressionKind::Binary(BinaryOp::Or) => constants::CEXPR_OR,
+                ConstraintExpressionKind::Attr => constants::CEXPR_ATTR,
+                ConstraintExpressionKind::Names { .. } => constants::CEXPR_NAMES,
+            };
+
+            writer.write_u32(expr_ty)?;
+            writer.write_u32(expr.attr.map_or(0, |attr| attr.id()))?;
+            writer.write_u32(expr.op.map_or(0, |op| op.id()))?;
+
+            if let ConstraintExpressionKind::Names {
+                ref names,
+                ref type_names,
+            } = expr.kind
+            {
+                writer.write_object(names)?;
+
+                if has_type_names {
+                    let empty = Bitmap::create();
+                    let (types, inverse_types, flags) = match *type_names {
+                        Some(TypeSet::Set {
+                            ref types,
+                            ref inverse_types,
+                            flags,
+                        }) => (types, inverse_types, flags),
+                        Some(TypeSet::Bitmap(ref types)) => (types, &empty, 0),
+                        None => (&empty, &empty, 0),
+                    };
+
+                    writer.write_object(types)?;
+                    writer.write_object(inverse_types)?;
+                    writer.write_u32(flags)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
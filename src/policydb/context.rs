@@ -0,0 +1,98 @@
+use policydb::Feature;
+use policydb::MlsRange;
+use policydb::Policy;
+use policydb::PolicyObject;
+use policydb::PolicyReadError;
+use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Role;
+use policydb::Type;
+use policydb::User;
+use std::io::{Read, Write};
+
+/// A full security context, `user:role:type[:range]`, as stored in the object context tables.
+#[derive(Debug)]
+pub struct SecurityContext {
+    user: u32,
+    role: u32,
+    ty: u32,
+    range: Option<MlsRange>,
+}
+
+impl SecurityContext {
+    /// Creates a context from user, role and type ids.  Policies that support MLS need a range,
+    /// even when MLS isn't enabled.
+    pub fn new(user: u32, role: u32, ty: u32, range: Option<MlsRange>) -> Self {
+        SecurityContext {
+            user,
+            role,
+            ty,
+            range,
+        }
+    }
+
+    pub fn user<'a>(&self, policy: &'a Policy) -> Option<&'a User> {
+        policy.users().get(self.user)
+    }
+
+    pub fn role<'a>(&self, policy: &'a Policy) -> Option<&'a Role> {
+        policy.roles().get(self.role)
+    }
+
+    pub fn ty<'a>(&self, policy: &'a Policy) -> Option<&'a Type> {
+        policy.types().get(self.ty)
+    }
+
+    pub fn user_id(&self) -> u32 {
+        self.user
+    }
+
+    pub fn role_id(&self) -> u32 {
+        self.role
+    }
+
+    pub fn type_id(&self) -> u32 {
+        self.ty
+    }
+
+    /// The MLS range of this context, present whenever the policy version supports MLS.
+    pub fn range(&self) -> Option<&MlsRange> {
+        self.range.as_ref()
+    }
+}
+
+impl PolicyObject for SecurityContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let user = reader.read_u32()?;
+        let role = reader.read_u32()?;
+        let ty = reader.read_u32()?;
+        let range = if reader.profile().supports(Feature::Mls) {
+            Some(reader.read_object()?)
+        } else {
+            None
+        };
+
+        Ok(SecurityContext {
+            user,
+            role,
+            ty,
+            range,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.user)?;
+        writer.write_u32(self.role)?;
+        writer.write_u32(self.ty)?;
+
+        if writer.profile().supports(Feature::Mls) {
+            match self.range {
+                Some(ref range) => writer.write_object(range)?,
+                None => return Err(PolicyWriteError::UnsupportedFeatureUsed(Feature::Mls)),
+            }
+        }
+
+        Ok(())
+    }
+}
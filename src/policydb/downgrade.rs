@@ -0,0 +1,267 @@
+use policydb::CompatibilityProfile;
+use policydb::Feature;
+use policydb::Policy;
+use policydb::PolicyCapabilitySet;
+use policydb::Symbol;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DowngradeError {
+    /// Only kernel policies can be converted to another version.
+    NotKernelPolicy,
+    /// The target version is unknown, or newer than the policy's current version.
+    InvalidVersion(u32),
+    /// The policy uses features that the target version can't represent, and that can't be
+    /// dropped without changing the access the policy grants.
+    UnrepresentableFeatures(Vec<Feature>),
+}
+
+impl Error for DowngradeError {
+    fn description(&self) -> &str {
+        "Unable to downgrade policy"
+    }
+}
+
+impl fmt::Display for DowngradeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DowngradeError::NotKernelPolicy => write!(f, "policy is not a kernel policy"),
+            DowngradeError::InvalidVersion(version) => {
+                write!(f, "unable to downgrade to policy version {}", version)
+            }
+            DowngradeError::UnrepresentableFeatures(ref features) => write!(
+                f,
+                "features not supported by the target version: {:?}",
+                features
+            ),
+        }
+    }
+}
+
+/// Rules or settings that were dropped because the target version doesn't support them.
+#[derive(Debug)]
+pub struct DiscardedFeature {
+    feature: Feature,
+    count: usize,
+}
+
+impl DiscardedFeature {
+    pub fn feature(&self) -> Feature {
+        self.feature
+    }
+
+    /// The number of rules, settings or entries that were dropped.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// The parts of a policy that were dropped to convert it to an older version.
+#[derive(Debug, Default)]
+pub struct DowngradeReport {
+    discarded: Vec<DiscardedFeature>,
+}
+
+impl DowngradeReport {
+    pub fn discarded(&self) -> &[DiscardedFeature] {
+        &self.discarded
+    }
+
+    fn discard(&mut self, feature: Feature, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        match self
+            .discarded
+            .iter_mut()
+            .find(|discarded| discarded.feature == feature)
+        {
+            Some(discarded) => discarded.count += count,
+            None => self.discarded.push(DiscardedFeature { feature, count }),
+        }
+    }
+}
+
+/// Converts a kernel policy to an older policy version, in the same way as `checkpolicy -c`.
+///
+/// Features that only change how the policy is stored, such as compressed filename transitions
+/// or the attributes that are left out of the types table before version 24, are handled when
+/// the policy is written.  Features that older kernels ignore, such as
+/// filename transitions, extended permissions, permissive types and bounds, are dropped and
+/// listed in the returned report.  If the policy uses features that can't be dropped without
+/// changing its meaning, like MLS or booleans, the conversion fails with the list of them.
+pub fn downgrade(
+    mut policy: Policy,
+    version: u32,
+) -> Result<(Policy, DowngradeReport), DowngradeError> {
+    if !policy.ty().is_kernel_policy() {
+        return Err(DowngradeError::NotKernelPolicy);
+    }
+
+    let profile = CompatibilityProfile::new(policy.ty().clone(), version);
+
+    if !profile.is_supported_version() || version > policy.profile().version() {
+        return Err(DowngradeError::InvalidVersion(version));
+    }
+
+    let unrepresentable = unrepresentable_features(&policy, &profile);
+
+    if !unrepresentable.is_empty() {
+        return Err(DowngradeError::UnrepresentableFeatures(unrepresentable));
+    }
+
+    let mut report = DowngradeReport::default();
+
+    if !profile.supports(Feature::FilenameTransitions) {
+        let num_transitions = policy
+            .filename_transitions
+            .iter()
+            .map(|trans| trans.source_type_ids().count())
+            .sum();
+
+        report.discard(Feature::FilenameTransitions, num_transitions);
+        policy.filename_transitions.clear();
+    }
+
+    if !profile.supports(Feature::XpermsIoctl) {
+        let mut num_entries = policy.avtab.discard_extended();
+
+        for cond in &mut policy.conditionals {
+            let (true_list, false_list) = cond.lists_mut();
+
+            num_entries += true_list.discard_extended() + false_list.discard_extended();
+        }
+
+        report.discard(Feature::XpermsIoctl, num_entries);
+    }
+
+    let process = policy
+        .classes
+        .get_by_name("process")
+        .map(|class| class.id());
+
+    // Older policies only have transitions for processes, and don't record the class.
+    if !profile.supports(Feature::RoleTransitionClass) {
+        let len = policy.role_transitions.len();
+
+        policy
+            .role_transitions
+            .retain(|trans| trans.class_id().is_none() || trans.class_id() == process);
+
+        for trans in &mut policy.role_transitions {
+            trans.clear_class();
+        }
+
+        report.discard(
+            Feature::RoleTransitionClass,
+            len - policy.role_transitions.len(),
+        );
+    }
+
+    if !profile.supports(Feature::RangeTransitionClass) {
+        let len = policy.range_transitions.len();
+
+        policy
+            .range_transitions
+            .retain(|trans| trans.class_id().is_none() || trans.class_id() == process);
+
+        for trans in &mut policy.range_transitions {
+            trans.clear_class();
+        }
+
+        report.discard(
+            Feature::RangeTransitionClass,
+            len - policy.range_transitions.len(),
+        );
+    }
+
+    if !profile.supports(Feature::PermissiveTypes) {
+        let mut num_types = 0;
+
+        for ty in policy.types.all_mut().filter(|ty| ty.is_permissive()) {
+            ty.set_permissive(false);
+            num_types += 1;
+        }
+
+        report.discard(Feature::PermissiveTypes, num_types);
+    }
+
+    if !profile.supports(Feature::PolicyCapabilities) {
+        report.discard(Feature::PolicyCapabilities, policy.polcaps.all().len());
+        policy.polcaps = PolicyCapabilitySet::empty();
+    }
+
+    if !profile.supports(Feature::Boundary) {
+        let mut num_bounds = 0;
+
+        for user in policy.users.all_mut() {
+            num_bounds += user.bounds_id().map_or(0, |_| 1);
+            user.clear_bounds();
+        }
+
+        for role in policy.roles.all_mut() {
+            num_bounds += role.bounds_id().map_or(0, |_| 1);
+            role.clear_bounds();
+        }
+
+        for ty in policy.types.all_mut() {
+            num_bounds += ty.bounds_id().map_or(0, |_| 1);
+            ty.clear_bounds();
+        }
+
+        report.discard(Feature::Boundary, num_bounds);
+    }
+
+    for class in policy.classes.all_mut() {
+        for feature in class.discard_defaults(&profile) {
+            report.discard(feature, 1);
+        }
+    }
+
+    for (feature, num_entries) in policy.ocontexts.discard_unsupported(&profile) {
+        report.discard(feature, num_entries);
+    }
+
+    policy.version = version;
+    policy.profile = profile;
+
+    Ok((policy, report))
+}
+
+/// The features used by a policy that the target profile can't represent.
+fn unrepresentable_features(policy: &Policy, profile: &CompatibilityProfile) -> Vec<Feature> {
+    let mut features = vec![];
+
+    if policy.config().mls_enabled && !profile.supports(Feature::Mls) {
+        features.push(Feature::Mls);
+    }
+
+    let has_booleans = policy.booleans().len() > 0 || !policy.conditionals().is_empty();
+
+    if has_booleans && !profile.supports(Feature::Booleans) {
+        features.push(Feature::Booleans);
+    }
+
+    let has_transition_constraints = policy
+        .classes()
+        .all()
+        .any(|class| !class.transition_constraints().is_empty());
+
+    if has_transition_constraints && !profile.supports(Feature::ValidateTransition) {
+        features.push(Feature::ValidateTransition);
+    }
+
+    // Before `POLICYDB_VERSION_AVTAB` there is no type attribute map, so rules can't refer to
+    // attributes.
+    if policy.type_attributes().next().is_some() && !profile.supports(Feature::AvTab) {
+        features.push(Feature::AvTab);
+    }
+
+    if policy.ocontexts().has_wide_io_mems() && !profile.supports(Feature::XenDevicetree) {
+        features.push(Feature::XenDevicetree);
+    }
+
+    features
+}
@@ -0,0 +1,378 @@
+use policydb::link::access_vector;
+use policydb::role::constants::OBJECT_R_VAL;
+use policydb::symtable::{lookup, UnknownName};
+use policydb::AvRuleKind;
+use policydb::ConstraintExpressionKind;
+use policydb::Feature;
+use policydb::Policy;
+use policydb::Symbol;
+use policydb::SymbolKind;
+use policydb::Type;
+use policydb::TypeFlavor;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum PolicyEditError {
+    /// Only kernel policies can be edited, modules have to be linked and expanded first.
+    NotKernelPolicy,
+    /// The policy version doesn't support what the edit needs.
+    UnsupportedFeature(Feature),
+    /// The rule kind can't be used with this kind of edit.
+    InvalidRuleKind(AvRuleKind),
+    DuplicateSymbol {
+        kind: SymbolKind,
+        name: String,
+    },
+    UnknownSymbol {
+        kind: SymbolKind,
+        name: String,
+    },
+    UnknownPermission {
+        class: String,
+        permission: String,
+    },
+    /// An attribute was given where a type is required.
+    NotAType(String),
+    /// A type was given where an attribute is required.
+    NotAnAttribute(String),
+    /// A type rule for the same source type, target type and class already gives another type.
+    ConflictingTypeRule {
+        source_type: String,
+        target_type: String,
+        class: String,
+    },
+}
+
+impl From<UnknownName> for PolicyEditError {
+    fn from(name: UnknownName) -> Self {
+        match name {
+            UnknownName::Symbol { kind, name } => PolicyEditError::UnknownSymbol { kind, name },
+            UnknownName::Permission { class, permission } => {
+                PolicyEditError::UnknownPermission { class, permission }
+            }
+        }
+    }
+}
+
+impl Error for PolicyEditError {
+    fn description(&self) -> &str {
+        "Unable to edit policy"
+    }
+}
+
+impl fmt::Display for PolicyEditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PolicyEditError::NotKernelPolicy => write!(f, "policy is not a kernel policy"),
+            PolicyEditError::UnsupportedFeature(feature) => {
+                write!(f, "policy version does not support {:?}", feature)
+            }
+            PolicyEditError::InvalidRuleKind(kind) => {
+                write!(f, "{:?} rules can't be added this way", kind)
+            }
+            PolicyEditError::DuplicateSymbol { kind, ref name } => {
+                write!(f, "{:?} {} is already declared", kind, name)
+            }
+            PolicyEditError::UnknownSymbol { kind, ref name } => {
+                write!(f, "{:?} {} is not declared", kind, name)
+            }
+            PolicyEditError::UnknownPermission {
+                ref class,
+                ref permission,
+            } => write!(f, "class {} has no permission {}", class, permission),
+            PolicyEditError::NotAType(ref name) => write!(f, "{} is an attribute", name),
+            PolicyEditError::NotAnAttribute(ref name) => write!(f, "{} is not an attribute", name),
+            PolicyEditError::ConflictingTypeRule {
+                ref source_type,
+                ref target_type,
+                ref class,
+            } => write!(
+                f,
+                "a type rule for types {} and {} on class {} already exists",
+                source_type, target_type, class
+            ),
+        }
+    }
+}
+
+/// Edits of a loaded kernel policy, for patching a policy in place in the same way as
+/// `sepolicy-inject`.  Every edit keeps the symbol ids, the type attribute map and the avtab
+/// consistent with each other, so the policy can be written out again afterwards.
+impl Policy {
+    /// Adds an access vector rule granting, auditing or silencing permissions.  The source and
+    /// target may be types or attributes, and the rule is merged with any existing rule of the
+    /// same kind.
+    pub fn add_av_rule(
+        &mut self,
+        kind: AvRuleKind,
+        source: &str,
+        target: &str,
+        class: &str,
+        permissions: &[&str],
+    ) -> Result<(), PolicyEditError> {
+        match kind {
+            AvRuleKind::Allow
+            | AvRuleKind::AuditAllow
+            | AvRuleKind::AuditDeny
+            | AvRuleKind::DontAudit => {}
+            _ => return Err(PolicyEditError::InvalidRuleKind(kind)),
+        }
+
+        self.check_kernel_policy()?;
+
+        let source_type = lookup(&self.types, SymbolKind::Type, source)?;
+        let target_type = lookup(&self.types, SymbolKind::Type, target)?;
+        let (target_class, av) = self.access_vector(class, permissions)?;
+
+        self.avtab
+            .add(
+                source_type as u16,
+                target_type as u16,
+                target_class as u16,
+                kind,
+                av,
+                None,
+            )
+            .map_err(|_| PolicyEditError::ConflictingTypeRule {
+                source_type: source.to_string(),
+                target_type: target.to_string(),
+                class: class.to_string(),
+            })
+    }
+
+    /// Removes permissions from the unconditional `allow` rule for a source, target and class.
+    /// Returns `false` if the rule didn't allow any of them.  Permissions granted through an
+    /// attribute of the source or target type are not affected.
+    pub fn remove_allowed(
+        &mut self,
+        source: &str,
+        target: &str,
+        class: &str,
+        permissions: &[&str],
+    ) -> Result<bool, PolicyEditError> {
+        self.check_kernel_policy()?;
+
+        let source_type = lookup(&self.types, SymbolKind::Type, source)?;
+        let target_type = lookup(&self.types, SymbolKind::Type, target)?;
+        let (target_class, av) = self.access_vector(class, permissions)?;
+
+        let removed = self.avtab.remove_allowed(
+            source_type as u16,
+            target_type as u16,
+            target_class as u16,
+            av,
+        );
+
+        Ok(removed != 0)
+    }
+
+    /// Adds a `type_transition`, `type_change` or `type_member` rule.
+    pub fn add_type_rule(
+        &mut self,
+        kind: AvRuleKind,
+        source: &str,
+        target: &str,
+        class: &str,
+        new_type: &str,
+    ) -> Result<(), PolicyEditError> {
+        if !kind.is_type_rule() {
+            return Err(PolicyEditError::InvalidRuleKind(kind));
+        }
+
+        self.check_kernel_policy()?;
+
+        let source_type = lookup(&self.types, SymbolKind::Type, source)?;
+        let target_type = lookup(&self.types, SymbolKind::Type, target)?;
+        let target_class = lookup(&self.classes, SymbolKind::Class, class)?;
+        let new_type = self.concrete_type(new_type)?;
+
+        self.avtab
+            .add(
+                source_type as u16,
+                target_type as u16,
+                target_class as u16,
+                kind,
+                new_type,
+                None,
+            )
+            .map_err(|_| PolicyEditError::ConflictingTypeRule {
+                source_type: source.to_string(),
+                target_type: target.to_string(),
+                class: class.to_string(),
+            })
+    }
+
+    /// Marks a domain as permissive, or enforcing again.
+    pub fn set_permissive(&mut self, ty: &str, permissive: bool) -> Result<(), PolicyEditError> {
+        self.check_kernel_policy()?;
+
+        if !self.profile.supports(Feature::PermissiveTypes) {
+            return Err(PolicyEditError::UnsupportedFeature(
+                Feature::PermissiveTypes,
+            ));
+        }
+
+        let id = self.concrete_type(ty)?;
+
+        if let Some(ty) = self.types.get_mut(id) {
+            ty.set_permissive(permissive);
+        }
+
+        Ok(())
+    }
+
+    /// Changes the value a boolean has when the policy is loaded, and which of the rules of the
+    /// conditionals using it are enabled.
+    pub fn set_boolean_default(&mut self, name: &str, state: bool) -> Result<(), PolicyEditError> {
+        self.check_kernel_policy()?;
+
+        let id = lookup(&self.booleans, SymbolKind::Boolean, name)?;
+
+        if let Some(boolean) = self.booleans.get_mut(id) {
+            boolean.set_toggled(state);
+        }
+
+        let booleans = &self.booleans;
+
+        for cond in &mut self.conditionals {
+            if !cond.boolean_ids().any(|boolean| boolean == id) {
+                continue;
+            }
+
+            let value = cond.evaluate(|boolean| {
+                booleans
+                    .get(boolean)
+                    .map_or(false, |boolean| boolean.is_toggled())
+            });
+
+            if let Some(value) = value {
+                cond.set_state(value);
+
+                let (true_list, false_list) = cond.lists_mut();
+                true_list.set_enabled(value);
+                false_list.set_enabled(!value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Declares a new type, authorized for the `object_r` role, and returns its id.
+    pub fn add_type(&mut self, name: &str) -> Result<u32, PolicyEditError> {
+        let id = self.declare_type(name, TypeFlavor::Type)?;
+
+        if let Some(role) = self.roles.get_mut(OBJECT_R_VAL) {
+            role.add_type(id);
+        }
+
+        Ok(id)
+    }
+
+    /// Declares a new type attribute with no members, and returns its id.
+    pub fn add_attribute(&mut self, name: &str) -> Result<u32, PolicyEditError> {
+        if !self.profile.supports(Feature::AvTab) {
+            return Err(PolicyEditError::UnsupportedFeature(Feature::AvTab));
+        }
+
+        self.declare_type(name, TypeFlavor::Attribute)
+    }
+
+    /// Adds a type to an attribute, so that every rule and constraint using the attribute also
+    /// applies to it.
+    pub fn add_type_attribute(&mut self, ty: &str, attr: &str) -> Result<(), PolicyEditError> {
+        self.check_kernel_policy()?;
+
+        let ty = self.concrete_type(ty)?;
+        let attr_id = lookup(&self.types, SymbolKind::Type, attr)?;
+
+        if !self.types.get(attr_id).map_or(false, Type::is_attribute) {
+            return Err(PolicyEditError::NotAnAttribute(attr.to_string()));
+        }
+
+        self.type_attr_map.add(ty, attr_id);
+
+        // Kernel policies name the types of an attribute in constraints rather than the attribute
+        // itself, so the type has to be named wherever the source policy used the attribute.
+        for class in self.classes.all_mut() {
+            for constraint in class.all_constraints_mut() {
+                for expr in &mut constraint.expressions {
+                    if let ConstraintExpressionKind::Names {
+                        ref mut names,
+                        type_names: Some(ref type_names),
+                    } = expr.kind
+                    {
+                        if type_names.lists(attr_id) {
+                            names.add(ty - 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn declare_type(&mut self, name: &str, flavor: TypeFlavor) -> Result<u32, PolicyEditError> {
+        self.check_kernel_policy()?;
+
+        if self.types.contains(name) {
+            return Err(PolicyEditError::DuplicateSymbol {
+                kind: SymbolKind::Type,
+                name: name.to_string(),
+            });
+        }
+
+        let id = self.types.next_id();
+
+        self.types.insert(Type::new(id, name.to_string(), flavor));
+        self.type_attr_map.push_type();
+
+        Ok(id)
+    }
+
+    fn check_kernel_policy(&self) -> Result<(), PolicyEditError> {
+        if self.ty().is_kernel_policy() && self.module.is_none() {
+            Ok(())
+        } else {
+            Err(PolicyEditError::NotKernelPolicy)
+        }
+    }
+
+    /// Looks up a type that isn't an attribute, giving the id of its primary if it's an alias.
+    fn concrete_type(&self, name: &str) -> Result<u32, PolicyEditError> {
+        let ty = self
+            .types
+            .get_by_name(name)
+            .ok_or_else(|| PolicyEditError::UnknownSymbol {
+                kind: SymbolKind::Type,
+                name: name.to_string(),
+            })?;
+
+        if ty.is_attribute() {
+            return Err(PolicyEditError::NotAType(name.to_string()));
+        }
+
+        Ok(ty.id())
+    }
+
+    /// The id of a class, and the access vector for the named permissions of it.
+    fn access_vector(
+        &self,
+        class: &str,
+        permissions: &[&str],
+    ) -> Result<(u32, u32), PolicyEditError> {
+        let class_datum =
+            self.classes
+                .get_by_name(class)
+                .ok_or_else(|| PolicyEditError::UnknownSymbol {
+                    kind: SymbolKind::Class,
+                    name: class.to_string(),
+                })?;
+
+        Ok((
+            class_datum.id(),
+            access_vector(self, class_datum, permissions)?,
+        ))
+    }
+}
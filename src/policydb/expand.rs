@@ -0,0 +1,520 @@
+use croaring::Bitmap;
+use policydb::role::AttributeRoles;
+use policydb::ty::{AttributeTypes, InvalidTypeId};
+use policydb::AccessVectorTable;
+use policydb::AvRuleDeclaration;
+use policydb::AvRuleKind;
+use policydb::AvRuleList;
+use policydb::CompatibilityProfile;
+use policydb::ConditionalExpression;
+use policydb::Feature;
+use policydb::FilenameTransition;
+use policydb::Policy;
+use policydb::PolicyTargetPlatform;
+use policydb::PolicyType;
+use policydb::RangeTransition;
+use policydb::Remap;
+use policydb::RoleAllow;
+use policydb::RoleSet;
+use policydb::RoleTransition;
+use policydb::Symbol;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use policydb::SymbolTable;
+use policydb::TypeAttributeMap;
+use policydb::TypeSet;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ExpandError {
+    /// The policy is not a base module produced by `link`.
+    NotLinked,
+    InvalidVersion(u32),
+    UnsupportedFeatureUsed(Feature),
+    /// Two type rules for the same source type, target type and class give different new types.
+    ConflictingTypeRule {
+        source_type: u32,
+        target_type: u32,
+        class: u32,
+    },
+    /// A type or attribute has an id outside of the types table.
+    InvalidTypeId(u32),
+}
+
+impl From<InvalidTypeId> for ExpandError {
+    fn from(InvalidTypeId(id): InvalidTypeId) -> Self {
+        ExpandError::InvalidTypeId(id)
+    }
+}
+
+impl Error for ExpandError {
+    fn description(&self) -> &str {
+        "Unable to expand policy"
+    }
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExpandError::NotLinked => write!(f, "policy is not a linked base module"),
+            ExpandError::InvalidVersion(version) => {
+                write!(f, "unsupported kernel policy version {}", version)
+            }
+            ExpandError::UnsupportedFeatureUsed(feature) => {
+                write!(f, "{:?} is not supported by the target version", feature)
+            }
+            ExpandError::ConflictingTypeRule {
+                source_type,
+                target_type,
+                class,
+            } => write!(
+                f,
+                "conflicting type rules for types {} and {} on class {}",
+                source_type, target_type, class
+            ),
+            ExpandError::InvalidTypeId(id) => write!(f, "type id {} is not in the types table", id),
+        }
+    }
+}
+
+/// A conditional block that only depends on tunables, and was resolved while expanding.
+#[derive(Debug)]
+pub struct ResolvedTunableBlock {
+    expression: Vec<ConditionalExpression>,
+    result: bool,
+    num_rules: usize,
+}
+
+impl ResolvedTunableBlock {
+    /// The expression guarding the block, in terms of the module's boolean ids.
+    pub fn expression(&self) -> &[ConditionalExpression] {
+        &self.expression
+    }
+
+    /// The value of the expression, which selects whether the true or false list was kept.
+    pub fn result(&self) -> bool {
+        self.result
+    }
+
+    /// The number of rules in the list that was added to the unconditional policy.
+    pub fn num_rules(&self) -> usize {
+        self.num_rules
+    }
+}
+
+/// The decisions made while expanding a module that aren't visible in the kernel policy.
+#[derive(Debug, Default)]
+pub struct ExpandReport {
+    tunable_blocks: Vec<ResolvedTunableBlock>,
+}
+
+impl ExpandReport {
+    pub fn tunable_blocks(&self) -> &[ResolvedTunableBlock] {
+        &self.tunable_blocks
+    }
+}
+
+/// Resolves the type and role sets of module rules into concrete types and roles.
+struct Expander {
+    attr_types: AttributeTypes,
+    attr_roles: AttributeRoles,
+}
+
+impl Expander {
+    fn new(policy: &Policy, decls: &[&AvRuleDeclaration]) -> Result<Self, InvalidTypeId> {
+        // Attribute members are recorded on the global attribute, and on its copy in each
+        // declaration that adds to it.
+        let decl_types = decls.iter().flat_map(|decl| decl.symbols().types().all());
+
+        Ok(Expander {
+            attr_types: AttributeTypes::new(policy, decl_types)?,
+            attr_roles: AttributeRoles::new(policy),
+        })
+    }
+
+    /// The 0-based bits of the concrete types in a type set.
+    fn types(&self, set: &TypeSet) -> Bitmap {
+        set.expand_with(&self.attr_types)
+    }
+
+    /// The 0-based bits of the concrete roles in a role set.
+    fn roles(&self, set: &RoleSet) -> Bitmap {
+        set.expand_with(&self.attr_roles)
+    }
+
+    fn avrules(&self, rules: &AvRuleList) -> Result<AccessVectorTable, ExpandError> {
+        let mut avtab = AccessVectorTable::default();
+        self.add_avrules(&mut avtab, rules)?;
+
+        Ok(avtab)
+    }
+
+    fn add_avrules(
+        &self,
+        avtab: &mut AccessVectorTable,
+        rules: &AvRuleList,
+    ) -> Result<(), ExpandError> {
+        for rule in rules.rules() {
+            let source_types = self.types(rule.source_types());
+            let target_types = self.types(rule.target_types());
+
+            for source_type in source_types.iter() {
+                let self_target = Bitmap::of(&[source_type]);
+                let targets = if rule.is_self() {
+                    &self_target
+                } else {
+                    &target_types
+                };
+
+                for target_type in targets.iter() {
+                    for perm in rule.permissions() {
+                        avtab
+                            .add(
+                                source_type as u16 + 1,
+                                target_type as u16 + 1,
+                                perm.class_id() as u16,
+                                rule.kind(),
+                                perm.data(),
+                                rule.xperms(),
+                            )
+                            .map_err(|_| ExpandError::ConflictingTypeRule {
+                                source_type: source_type + 1,
+                                target_type: target_type + 1,
+                                class: perm.class_id(),
+                            })?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Expands a linked base module into a kernel policy of the given version, in the same way as
+/// libsepol's `expand_module`.
+///
+/// Only the enabled declarations of the module contribute rules.  Attributes in rules are
+/// replaced by their member types, and the type attribute map of the kernel policy is built
+/// from the attribute memberships.
+pub fn expand(policy: Policy, version: u32) -> Result<Policy, ExpandError> {
+    expand_with_report(policy, version).map(|(policy, _)| policy)
+}
+
+/// Expands a linked base module like `expand`, also reporting how tunables were resolved.
+///
+/// Conditional blocks that only depend on tunables are evaluated with the tunables' default
+/// values, and the selected rules are added to the unconditional policy.  Tunables that aren't
+/// used alongside runtime booleans are dropped, and the remaining booleans are renumbered.
+pub fn expand_with_report(
+    mut policy: Policy,
+    version: u32,
+) -> Result<(Policy, ExpandReport), ExpandError> {
+    match *policy.ty() {
+        PolicyType::Module {
+            is_base_module: true,
+            ..
+        } => {}
+        _ => return Err(ExpandError::NotLinked),
+    }
+
+    let profile =
+        CompatibilityProfile::new(PolicyType::Kernel(PolicyTargetPlatform::SELinux), version);
+
+    if !profile.is_supported_version() {
+        return Err(ExpandError::InvalidVersion(version));
+    }
+
+    let module = policy.module.take().ok_or(ExpandError::NotLinked)?;
+    let decls: Vec<&AvRuleDeclaration> = module
+        .blocks()
+        .iter()
+        .flat_map(|block| block.declarations())
+        .filter(|decl| decl.is_enabled())
+        .collect();
+
+    let expander = Expander::new(&policy, &decls)?;
+    let process_class = policy
+        .classes()
+        .get_by_name("process")
+        .map(|class| class.id());
+
+    let mut avtab = AccessVectorTable::default();
+    let mut conditionals = vec![];
+    let mut report = ExpandReport::default();
+    let mut used_booleans = BTreeSet::new();
+    let mut role_transitions = vec![];
+    let mut role_allows = vec![];
+    let mut filename_transitions: BTreeMap<(String, u32, u32, u32), Bitmap> = BTreeMap::new();
+    let mut range_transitions = vec![];
+
+    for range_transition in policy.range_transitions.drain(..) {
+        let class = range_transition.class_id().or(process_class);
+
+        range_transitions.push(RangeTransition::new(
+            range_transition.source_type_id(),
+            range_transition.target_type_id(),
+            class,
+            range_transition.range().clone(),
+        ));
+    }
+
+    for decl in &decls {
+        let uses_xperms =
+            decl.avrules().rules().iter().any(|rule| {
+                rule.kind().is_xperm_rule() && rule.kind() != AvRuleKind::NeverAllowXperm
+            });
+
+        if uses_xperms && !profile.supports(Feature::XpermsIoctl) {
+            return Err(ExpandError::UnsupportedFeatureUsed(Feature::XpermsIoctl));
+        }
+
+        expander.add_avrules(&mut avtab, decl.avrules())?;
+
+        for cond in decl.conditionals() {
+            let is_tunable = |id| policy.booleans.get(id).map_or(false, |b| b.is_tunable());
+
+            if cond.is_tunable() || cond.boolean_ids().all(&is_tunable) {
+                let value_of = |id| policy.booleans.get(id).map_or(false, |b| b.is_toggled());
+                let result = cond.evaluate(value_of).unwrap_or(false);
+                let rules = if result {
+                    cond.true_list()
+                } else {
+                    cond.false_list()
+                };
+
+                expander.add_avrules(&mut avtab, rules)?;
+                report.tunable_blocks.push(ResolvedTunableBlock {
+                    expression: cond.expression().to_vec(),
+                    result,
+                    num_rules: rules.rules().len(),
+                });
+
+                continue;
+            }
+
+            used_booleans.extend(cond.boolean_ids());
+
+            let true_list = expander.avrules(cond.true_list())?;
+            let false_list = expander.avrules(cond.false_list())?;
+
+            conditionals.push(cond.with_rules(true_list, false_list));
+        }
+
+        for rule in decl.role_transitions() {
+            let classes: Vec<u32> = match rule.classes() {
+                Some(classes) => classes.iter().map(|bit| bit + 1).collect(),
+                None => process_class.into_iter().collect(),
+            };
+
+            for role in expander.roles(rule.roles()).iter() {
+                for ty in expander.types(rule.types()).iter() {
+                    for &class in &classes {
+                        role_transitions.push(RoleTransition::new(
+                            role + 1,
+                            ty + 1,
+                            rule.new_role_id(),
+                            Some(class),
+                        ));
+                    }
+                }
+            }
+        }
+
+        for rule in decl.role_allows() {
+            for role in expander.roles(rule.roles()).iter() {
+                for new_role in expander.roles(rule.new_roles()).iter() {
+                    role_allows.push(RoleAllow::new(role + 1, new_role + 1));
+                }
+            }
+        }
+
+        for rule in decl.filename_transitions() {
+            let source_types = expander.types(rule.source_types());
+            let target_types = expander.types(rule.target_types());
+
+            for source_type in source_types.iter() {
+                let self_target = Bitmap::of(&[source_type]);
+                let targets = if rule.is_self() {
+                    &self_target
+                } else {
+                    &target_types
+                };
+
+                for target_type in targets.iter() {
+                    let key = (
+                        rule.name().to_string(),
+                        target_type + 1,
+                        rule.class_id(),
+                        rule.new_type_id(),
+                    );
+
+                    filename_transitions
+                        .entry(key)
+                        .or_insert_with(Bitmap::create)
+                        .add(source_type);
+                }
+            }
+        }
+
+        for rule in decl.range_transitions() {
+            for source_type in expander.types(rule.source_types()).iter() {
+                for target_type in expander.types(rule.target_types()).iter() {
+                    for class in rule.classes().iter() {
+                        range_transitions.push(RangeTransition::new(
+                            source_type + 1,
+                            target_type + 1,
+                            Some(class + 1),
+                            rule.range().clone(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // Older kernels don't record a class for role and range transitions, and only apply them
+    // to processes.
+    if !profile.supports(Feature::RoleTransitionClass) {
+        role_transitions = role_transitions
+            .into_iter()
+            .filter(|trans| trans.class_id() == process_class)
+            .map(|trans| {
+                RoleTransition::new(trans.role_id(), trans.type_id(), trans.new_role_id(), None)
+            })
+            .collect();
+    }
+
+    if !profile.supports(Feature::RangeTransitionClass) {
+        range_transitions = range_transitions
+            .into_iter()
+            .filter(|trans| trans.class_id() == process_class)
+            .map(|trans| {
+                RangeTransition::new(
+                    trans.source_type_id(),
+                    trans.target_type_id(),
+                    None,
+                    trans.range().clone(),
+                )
+            })
+            .collect();
+    }
+
+    // Tunables used together with runtime booleans become booleans themselves, and all other
+    // tunables are dropped.
+    let booleans = ::std::mem::replace(&mut policy.booleans, SymbolTable::with_capacity(0));
+    let mut boolean_map = SymbolMap::new(0);
+
+    for mut boolean in booleans.into_symbols() {
+        if boolean.is_tunable() {
+            if !used_booleans.contains(&boolean.id()) {
+                continue;
+            }
+
+            boolean.clear_tunable();
+        }
+
+        let id = policy.booleans.num_primary_names() + 1;
+        boolean_map.insert(SymbolKind::Boolean, boolean.id(), id);
+        boolean.remap(&boolean_map);
+        policy.booleans.insert(boolean);
+    }
+
+    for cond in &mut conditionals {
+        cond.remap_booleans(&boolean_map);
+    }
+
+    let required_features = [
+        (Feature::Booleans, !conditionals.is_empty()),
+        (
+            Feature::FilenameTransitions,
+            !filename_transitions.is_empty(),
+        ),
+        (Feature::Mls, policy.config.mls_enabled),
+        (
+            Feature::PermissiveTypes,
+            policy.permissive_types().next().is_some(),
+        ),
+    ];
+
+    for &(feature, is_used) in required_features.iter() {
+        if is_used && !profile.supports(feature) {
+            return Err(ExpandError::UnsupportedFeatureUsed(feature));
+        }
+    }
+
+    let mut role_types: BTreeMap<u32, Bitmap> = BTreeMap::new();
+
+    for role in policy.roles.all() {
+        let mut types = expander.types(role.types());
+
+        for decl in &decls {
+            if let Some(decl_role) = decl.symbols().roles().get(role.id()) {
+                types.or_inplace(&expander.types(decl_role.types()));
+            }
+        }
+
+        role_types.insert(role.id(), types);
+    }
+
+    // The types of a role attribute are given to each of its member roles.  The attribute itself
+    // is kept as a plain role so that role ids don't change.
+    for attr in policy.role_attributes() {
+        let types = role_types[&attr.id()].clone();
+
+        for member in attr.member_ids() {
+            if let Some(member_types) = role_types.get_mut(&member) {
+                member_types.or_inplace(&types);
+            }
+        }
+    }
+
+    for role in policy.roles.all_mut() {
+        if let Some(types) = role_types.remove(&role.id()) {
+            role.expand(types);
+        }
+    }
+
+    for user in policy.users.all_mut() {
+        let mut roles = expander.roles(user.roles());
+
+        for decl in &decls {
+            if let Some(decl_user) = decl.symbols().users().get(user.id()) {
+                roles.or_inplace(&expander.roles(decl_user.roles()));
+            }
+        }
+
+        user.expand(roles);
+    }
+
+    for ty in policy.types.all_mut() {
+        ty.expand();
+    }
+
+    for alias in policy.types.aliases_mut() {
+        alias.expand();
+    }
+
+    policy.type_attr_map = TypeAttributeMap::from_attributes(
+        policy.types.num_primary_names(),
+        expander.attr_types.members(),
+    );
+    policy.avtab = avtab;
+    policy.conditionals = conditionals;
+    policy.role_transitions = role_transitions;
+    policy.role_allows = role_allows;
+    policy.filename_transitions = filename_transitions
+        .into_iter()
+        .map(|((name, target_type, class, new_type), source_types)| {
+            FilenameTransition::new(name, source_types, target_type, class, new_type)
+        })
+        .collect();
+    policy.range_transitions = range_transitions;
+    policy.version = version;
+    policy.profile = profile;
+
+    Ok((policy, report))
+}
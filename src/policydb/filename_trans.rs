@@ -0,0 +1,185 @@
+use croaring::Bitmap;
+use policydb::Class;
+use policydb::Policy;
+use policydb::PolicyObject;
+use policydb::PolicyReadError;
+use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Type;
+use std::io::{Read, Write};
+
+/// A name-based `type_transition` rule.  Policies before `POLICYDB_VERSION_COMP_FTRANS` store
+/// one rule per source type, while newer policies share a rule between all source types that
+/// transition to the same type.  Both layouts decode into this representation.
+#[derive(Debug)]
+pub struct FilenameTransition {
+    name: String,
+    source_types: Bitmap,
+    target_type: u32,
+    class: u32,
+    new_type: u32,
+}
+
+impl FilenameTransition {
+    pub(crate) fn new(
+        name: String,
+        source_types: Bitmap,
+        target_type: u32,
+        class: u32,
+        new_type: u32,
+    ) -> Self {
+        FilenameTransition {
+            name,
+            source_types,
+            target_type,
+            class,
+            new_type,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// The ids of the source types this rule applies to.
+    pub fn source_type_ids<'a>(&'a self) -> impl Iterator<Item = u32> + 'a {
+        self.source_types.iter().map(|bit| bit + 1)
+    }
+
+    pub(crate) fn has_source_type(&self, ty: u32) -> bool {
+        ty > 0 && self.source_types.contains(ty - 1)
+    }
+
+    pub fn source_types<'a>(&'a self, policy: &'a Policy) -> impl Iterator<Item = &'a Type> + 'a {
+        self.source_type_ids()
+            .filter_map(move |id| policy.types().get(id))
+    }
+
+    pub fn target_type<'a>(&self, policy: &'a Policy) -> Option<&'a Type> {
+        policy.types().get(self.target_type)
+    }
+
+    pub fn class<'a>(&self, policy: &'a Policy) -> Option<&'a Class> {
+        policy.classes().get(self.class)
+    }
+
+    pub fn new_type<'a>(&self, policy: &'a Policy) -> Option<&'a Type> {
+        policy.types().get(self.new_type)
+    }
+
+    pub fn target_type_id(&self) -> u32 {
+        self.target_type
+    }
+
+    pub fn class_id(&self) -> u32 {
+        self.class
+    }
+
+    pub fn new_type_id(&self) -> u32 {
+        self.new_type
+    }
+
+    /// Decodes a single entry of the compressed filename transition table, which may expand to
+    /// several rules sharing the same name, target type and class.
+    pub fn decode_compressed<R: Read>(
+        reader: &mut PolicyReader<R>,
+    ) -> Result<Vec<Self>, PolicyReadError> {
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read_string(name_len)?;
+        let target_type = reader.read_u32()?;
+        let class = reader.read_u32()?;
+        let num_datums = reader.read_u32()? as usize;
+        let mut transitions = Vec::with_capacity(num_datums);
+
+        for _ in 0..num_datums {
+            let source_types: Bitmap = reader.read_object()?;
+            let new_type = reader.read_u32()?;
+
+            transitions.push(FilenameTransition {
+                name: name.clone(),
+                source_types,
+                target_type,
+                class,
+                new_type,
+            });
+        }
+
+        Ok(transitions)
+    }
+
+    /// Checks if two rules are stored in the same entry of the compressed filename transition
+    /// table.
+    pub(crate) fn shares_entry_with(&self, other: &FilenameTransition) -> bool {
+        self.name == other.name
+            && self.target_type == other.target_type
+            && self.class == other.class
+    }
+
+    /// Encodes rules sharing the same name, target type and class as a single entry of the
+    /// compressed filename transition table.
+    pub fn encode_compressed<W: Write>(
+        writer: &mut PolicyWriter<W>,
+        transitions: &[Self],
+    ) -> Result<(), PolicyWriteError> {
+        let first = match transitions.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+
+        writer.write_len(first.name.len())?;
+        writer.write_string(&first.name)?;
+        writer.write_u32(first.target_type)?;
+        writer.write_u32(first.class)?;
+        writer.write_len(transitions.len())?;
+
+        for transition in transitions {
+            writer.write_object(&transition.source_types)?;
+            writer.write_u32(transition.new_type)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PolicyObject for FilenameTransition {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read_string(name_len)?;
+        let source_type = reader.read_u32()?;
+        let target_type = reader.read_u32()?;
+        let class = reader.read_u32()?;
+        let new_type = reader.read_u32()?;
+
+        // Type ids start at 1, so a source type of 0 can't refer to a type.
+        let source_bit = source_type
+            .checked_sub(1)
+            .ok_or(PolicyReadError::InvalidFilenameTransition)?;
+
+        let mut source_types = Bitmap::create();
+        source_types.add(source_bit);
+
+        Ok(FilenameTransition {
+            name,
+            source_types,
+            target_type,
+            class,
+            new_type,
+        })
+    }
+
+    /// Writes one rule for each of the source types, as in the format before
+    /// `POLICYDB_VERSION_COMP_FTRANS`.
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        for source_type in self.source_type_ids() {
+            writer.write_len(self.name.len())?;
+            writer.write_string(&self.name)?;
+            writer.write_u32(source_type)?;
+            writer.write_u32(self.target_type)?;
+            writer.write_u32(self.class)?;
+            writer.write_u32(self.new_type)?;
+        }
+
+        Ok(())
+    }
+}
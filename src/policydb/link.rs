@@ -0,0 +1,673 @@
+use croaring::Bitmap;
+use policydb::symtable::UnknownName;
+use policydb::AvRuleDeclaration;
+use policydb::Class;
+use policydb::ModulePolicy;
+use policydb::Policy;
+use policydb::PolicyType;
+use policydb::Scope;
+use policydb::ScopeKind;
+use policydb::Symbol;
+use policydb::SymbolKind;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+
+/// Rewrites the symbol ids held by a module object to the ids of the policy it is linked into.
+pub(crate) trait Remap {
+    fn remap(&mut self, map: &SymbolMap);
+}
+
+/// Maps the symbol, permission and declaration ids of a module onto the ids used by the policy
+/// it is being linked into.  Ids without a mapping become 0.
+#[derive(Debug)]
+pub(crate) struct SymbolMap {
+    ids: Vec<Vec<u32>>,
+    permissions: Vec<Vec<u32>>,
+    decl_offset: u32,
+}
+
+impl SymbolMap {
+    pub(crate) fn new(decl_offset: u32) -> Self {
+        SymbolMap {
+            ids: vec![vec![]; SymbolKind::all().len()],
+            permissions: vec![],
+            decl_offset,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, kind: SymbolKind, from: u32, to: u32) {
+        insert_id(&mut self.ids[kind.index()], from, to);
+    }
+
+    fn insert_permission(&mut self, class: u32, from: u32, to: u32) {
+        let idx = class as usize - 1;
+
+        if self.permissions.len() <= idx {
+            self.permissions.resize(idx + 1, vec![]);
+        }
+
+        insert_id(&mut self.permissions[idx], from, to);
+    }
+
+    pub(crate) fn id(&self, kind: SymbolKind, id: u32) -> u32 {
+        lookup_id(&self.ids[kind.index()], id)
+    }
+
+    /// Maps a bitmap of 0-based symbol bits, dropping any symbols without a mapping.
+    pub(crate) fn bitmap(&self, kind: SymbolKind, bitmap: &Bitmap) -> Bitmap {
+        bitmap
+            .iter()
+            .map(|bit| self.id(kind, bit + 1))
+            .filter(|id| *id != 0)
+            .map(|id| id - 1)
+            .collect()
+    }
+
+    pub(crate) fn permission(&self, class: u32, perm: u32) -> u32 {
+        class
+            .checked_sub(1)
+            .and_then(|idx| self.permissions.get(idx as usize))
+            .map_or(0, |perms| lookup_id(perms, perm))
+    }
+
+    /// Maps an access vector of the module class with the given id.
+    pub(crate) fn access_vector(&self, class: u32, av: u32) -> u32 {
+        (0..32)
+            .filter(|bit| av & (1 << bit) != 0)
+            .map(|bit| self.permission(class, bit + 1))
+            .filter(|perm| *perm != 0 && *perm <= 32)
+            .fold(0, |av, perm| av | 1 << (perm - 1))
+    }
+
+    /// Maps a bitmap of 0-based permission bits of the module class with the given id.
+    pub(crate) fn permission_bitmap(&self, class: u32, perms: &Bitmap) -> Bitmap {
+        perms
+            .iter()
+            .map(|bit| self.permission(class, bit + 1))
+            .filter(|perm| *perm != 0)
+            .map(|perm| perm - 1)
+            .collect()
+    }
+
+    pub(crate) fn decl_id(&self, id: u32) -> u32 {
+        id + self.decl_offset
+    }
+}
+
+fn insert_id(ids: &mut Vec<u32>, from: u32, to: u32) {
+    let idx = from as usize - 1;
+
+    if ids.len() <= idx {
+        ids.resize(idx + 1, 0);
+    }
+
+    ids[idx] = to;
+}
+
+fn lookup_id(ids: &[u32], id: u32) -> u32 {
+    id.checked_sub(1)
+        .and_then(|idx| ids.get(idx as usize))
+        .cloned()
+        .unwrap_or(0)
+}
+
+/// A symbol or permission that a module requires another module to declare.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Requirement {
+    Symbol { kind: SymbolKind, name: String },
+    Permission { class: String, permission: String },
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+    /// The policy passed as the base is not a base module.
+    NotBaseModule,
+    /// The policy passed as a module is a kernel policy or a base module.
+    NotModule,
+    DuplicateDeclaration {
+        module: String,
+        kind: SymbolKind,
+        name: String,
+    },
+    /// A module declared a kind of symbol that only the base module may declare.
+    InvalidDeclaration {
+        module: String,
+        kind: SymbolKind,
+        name: String,
+    },
+    UnsatisfiedRequirement {
+        module: String,
+        requirement: Requirement,
+    },
+}
+
+impl Error for LinkError {
+    fn description(&self) -> &str {
+        "Unable to link policy modules"
+    }
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LinkError::NotBaseModule => write!(f, "policy is not a base module"),
+            LinkError::NotModule => write!(f, "policy is not a non-base module"),
+            LinkError::DuplicateDeclaration {
+                ref module,
+                kind,
+                ref name,
+            } => write!(
+                f,
+                "{}: duplicate declaration of {:?} {}",
+                module, kind, name
+            ),
+            LinkError::InvalidDeclaration {
+                ref module,
+                kind,
+                ref name,
+            } => write!(f, "{}: modules may not declare {:?} {}", module, kind, name),
+            LinkError::UnsatisfiedRequirement {
+                ref module,
+                ref requirement,
+            } => write!(f, "{}: unsatisfied requirement {:?}", module, requirement),
+        }
+    }
+}
+
+/// The requirements of a single declaration, and those that could not be resolved against the
+/// symbols of the linked policy.
+struct DeclRequirements {
+    module: String,
+    requirements: Vec<Requirement>,
+    unresolved: Vec<Requirement>,
+}
+
+/// Links a base module and a set of non-base modules into a single base module, in the same way
+/// as libsepol's `link_modules`.
+///
+/// Symbols declared by the modules are added to the base with new ids, and every rule is
+/// rewritten to use the ids of the base.  Each optional block then has its first declaration
+/// whose requirements are satisfied enabled, and the requirements of the global blocks are
+/// checked.  The result can be expanded into a kernel policy with `expand`.
+pub fn link(mut base: Policy, modules: Vec<Policy>) -> Result<Policy, LinkError> {
+    match *base.ty() {
+        PolicyType::Module {
+            is_base_module: true,
+            ..
+        } => {}
+        _ => return Err(LinkError::NotBaseModule),
+    }
+
+    let (mut blocks, base_scopes) = base
+        .module
+        .take()
+        .ok_or(LinkError::NotBaseModule)?
+        .into_parts();
+
+    let mut scopes: Vec<BTreeMap<String, Scope>> = base_scopes
+        .into_iter()
+        .map(|scopes| {
+            scopes
+                .into_iter()
+                .map(|scope| (scope.name().to_string(), scope))
+                .collect()
+        })
+        .collect();
+
+    let mut requirements = BTreeMap::new();
+
+    for decl in blocks.iter().flat_map(|block| block.declarations()) {
+        requirements.insert(decl.id(), decl_requirements(&base, "base", decl, None));
+    }
+
+    let mut decl_offset = max_decl_id(blocks.iter().flat_map(|block| block.declarations()));
+    let mut names: Vec<BTreeMap<String, u32>> = SymbolKind::all()
+        .iter()
+        .map(|kind| {
+            symbol_names(&base, *kind)
+                .into_iter()
+                .map(|(id, name)| (name, id))
+                .collect()
+        })
+        .collect();
+    let mut num_symbols: Vec<u32> = SymbolKind::all()
+        .iter()
+        .map(|kind| num_symbols(&base, *kind))
+        .collect();
+
+    // Allocate ids for the symbols declared by each module before resolving any requirements,
+    // so that modules can require symbols declared by modules that come after them.
+    let mut maps = Vec::with_capacity(modules.len());
+    let mut declared_names = Vec::with_capacity(modules.len());
+
+    for module in &modules {
+        let module_name = match *module.ty() {
+            PolicyType::Module {
+                is_base_module: false,
+                ref name,
+                ..
+            } => name.clone(),
+            _ => return Err(LinkError::NotModule),
+        };
+
+        let module_data = module.module().ok_or(LinkError::NotModule)?;
+        let mut map = SymbolMap::new(decl_offset);
+        let mut declared = vec![];
+
+        decl_offset += max_decl_id(
+            module_data
+                .blocks()
+                .iter()
+                .flat_map(|block| block.declarations()),
+        );
+
+        for &kind in SymbolKind::all() {
+            let kind_declared: BTreeSet<String> = module_data
+                .scopes(kind)
+                .iter()
+                .filter(|scope| scope.kind() == ScopeKind::Declared)
+                .map(|scope| scope.name().to_string())
+                .collect();
+
+            for (id, name) in symbol_names(module, kind) {
+                if !kind_declared.contains(&name) {
+                    continue;
+                }
+
+                let mergeable = match kind {
+                    SymbolKind::Role | SymbolKind::User => true,
+                    SymbolKind::Type => module.types.get(id).map_or(false, |ty| ty.is_attribute()),
+                    SymbolKind::Boolean => false,
+                    _ => {
+                        return Err(LinkError::InvalidDeclaration {
+                            module: module_name,
+                            kind,
+                            name,
+                        })
+                    }
+                };
+
+                let linked_id = match names[kind.index()].get(&name).cloned() {
+                    Some(linked_id) if mergeable => linked_id,
+                    Some(_) => {
+                        return Err(LinkError::DuplicateDeclaration {
+                            module: module_name,
+                            kind,
+                            name,
+                        })
+                    }
+                    None => {
+                        num_symbols[kind.index()] += 1;
+                        num_symbols[kind.index()]
+                    }
+                };
+
+                names[kind.index()].insert(name, linked_id);
+                map.insert(kind, id, linked_id);
+            }
+
+            declared.push(kind_declared);
+        }
+
+        maps.push(map);
+        declared_names.push(declared);
+    }
+
+    // Resolve the symbols and permissions each module requires by name.
+    for (module, map) in modules.iter().zip(maps.iter_mut()) {
+        for &kind in SymbolKind::all() {
+            for (id, name) in symbol_names(module, kind) {
+                if map.id(kind, id) != 0 {
+                    continue;
+                }
+
+                if let Some(&linked_id) = names[kind.index()].get(&name) {
+                    map.insert(kind, id, linked_id);
+                }
+            }
+        }
+
+        for class in module.classes().all() {
+            let linked_class = match base.classes().get(map.id(SymbolKind::Class, class.id())) {
+                Some(linked_class) => linked_class,
+                None => continue,
+            };
+
+            for (perm, name) in class_permissions(module, class) {
+                if let Some(linked_perm) = permission_value(&base, linked_class, &name) {
+                    map.insert_permission(class.id(), perm, linked_perm);
+                }
+            }
+        }
+
+        let module_name = match *module.ty() {
+            PolicyType::Module { ref name, .. } => name.as_str(),
+            _ => "",
+        };
+
+        for decl in module
+            .module()
+            .into_iter()
+            .flat_map(|module| module.blocks())
+            .flat_map(|block| block.declarations())
+        {
+            requirements.insert(
+                map.decl_id(decl.id()),
+                decl_requirements(module, module_name, decl, Some(map)),
+            );
+        }
+    }
+
+    for ((module, map), declared) in modules.into_iter().zip(maps).zip(declared_names) {
+        let is_declared = |kind: SymbolKind, name: &str| declared[kind.index()].contains(name);
+
+        for mut ty in module.types.into_symbols() {
+            if !is_declared(SymbolKind::Type, ty.name()) {
+                continue;
+            }
+
+            ty.remap(&map);
+
+            if ty.is_alias() {
+                base.types.insert(ty);
+                continue;
+            }
+
+            if let Some(existing) = base.types.get_mut(ty.id()) {
+                existing.merge(ty);
+                continue;
+            }
+
+            base.types.insert(ty);
+        }
+
+        for mut role in module.roles.into_symbols() {
+            if !is_declared(SymbolKind::Role, role.name()) {
+                continue;
+            }
+
+            role.remap(&map);
+
+            if let Some(existing) = base.roles.get_mut(role.id()) {
+                existing.merge(role);
+                continue;
+            }
+
+            base.roles.insert(role);
+        }
+
+        for mut user in module.users.into_symbols() {
+            if !is_declared(SymbolKind::User, user.name()) {
+                continue;
+            }
+
+            user.remap(&map);
+
+            if let Some(existing) = base.users.get_mut(user.id()) {
+                existing.merge(user);
+                continue;
+            }
+
+            base.users.insert(user);
+        }
+
+        for mut boolean in module.booleans.into_symbols() {
+            if is_declared(SymbolKind::Boolean, boolean.name()) {
+                boolean.remap(&map);
+                base.booleans.insert(boolean);
+            }
+        }
+
+        if let Some(module_data) = module.module {
+            let (module_blocks, module_scopes) = module_data.into_parts();
+
+            for mut block in module_blocks {
+                block.remap(&map);
+                blocks.push(block);
+            }
+
+            for (idx, module_scopes) in module_scopes.into_iter().enumerate() {
+                for mut scope in module_scopes {
+                    scope.remap(&map);
+
+                    if let Some(existing) = scopes[idx].get_mut(scope.name()) {
+                        existing.merge(scope);
+                        continue;
+                    }
+
+                    scopes[idx].insert(scope.name().to_string(), scope);
+                }
+            }
+        }
+    }
+
+    let is_declared_by = |kind: SymbolKind, name: &str, enabled: &BTreeSet<u32>| {
+        scopes[kind.index()].get(name).map_or(false, |scope| {
+            scope.kind() == ScopeKind::Declared
+                && scope.decl_ids().iter().any(|id| enabled.contains(id))
+        })
+    };
+
+    let unsatisfied = |decl: u32, enabled: &BTreeSet<u32>| {
+        let requirements = &requirements[&decl];
+
+        requirements.unresolved.first().cloned().or_else(|| {
+            requirements
+                .requirements
+                .iter()
+                .find(|requirement| match **requirement {
+                    Requirement::Symbol { kind, ref name } => !is_declared_by(kind, name, enabled),
+                    Requirement::Permission { ref class, .. } => {
+                        !is_declared_by(SymbolKind::Class, class, enabled)
+                    }
+                })
+                .cloned()
+        })
+    };
+
+    let mut enabled: BTreeSet<u32> = blocks
+        .iter()
+        .filter(|block| !block.is_optional())
+        .flat_map(|block| block.declarations())
+        .map(|decl| decl.id())
+        .collect();
+
+    loop {
+        let mut changed = false;
+
+        for block in blocks.iter().filter(|block| block.is_optional()) {
+            let decls = block.declarations();
+
+            if decls.iter().any(|decl| enabled.contains(&decl.id())) {
+                continue;
+            }
+
+            let satisfied = decls
+                .iter()
+                .map(|decl| decl.id())
+                .find(|decl| unsatisfied(*decl, &enabled).is_none());
+
+            if let Some(decl) = satisfied {
+                enabled.insert(decl);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    for block in blocks.iter().filter(|block| !block.is_optional()) {
+        for decl in block.declarations() {
+            if let Some(requirement) = unsatisfied(decl.id(), &enabled) {
+                return Err(LinkError::UnsatisfiedRequirement {
+                    module: requirements[&decl.id()].module.clone(),
+                    requirement,
+                });
+            }
+        }
+    }
+
+    for block in &mut blocks {
+        for decl in block.declarations_mut() {
+            let is_enabled = enabled.contains(&decl.id());
+            decl.set_enabled(is_enabled);
+        }
+    }
+
+    let scopes = scopes
+        .into_iter()
+        .map(|scopes| scopes.into_iter().map(|(_, scope)| scope).collect())
+        .collect();
+
+    base.module = Some(ModulePolicy::new(blocks, scopes));
+
+    Ok(base)
+}
+
+fn decl_requirements(
+    policy: &Policy,
+    module: &str,
+    decl: &AvRuleDeclaration,
+    map: Option<&SymbolMap>,
+) -> DeclRequirements {
+    let required = decl.required();
+    let mut requirements = vec![];
+    let mut unresolved = vec![];
+
+    for &kind in SymbolKind::all() {
+        for id in required.symbol_ids(kind) {
+            let name = policy.symbol_name(kind, id).unwrap_or_default().to_string();
+            let requirement = Requirement::Symbol { kind, name };
+
+            if map.map_or(false, |map| map.id(kind, id) == 0) {
+                unresolved.push(requirement.clone());
+            }
+
+            requirements.push(requirement);
+        }
+    }
+
+    for class in policy.classes().all() {
+        let perm_names = class_permissions(policy, class);
+
+        for perm in required.class_permission_ids(class.id()) {
+            let permission = perm_names
+                .iter()
+                .find(|&&(id, _)| id == perm)
+                .map(|&(_, ref name)| name.clone())
+                .unwrap_or_default();
+            let requirement = Requirement::Permission {
+                class: class.name().to_string(),
+                permission,
+            };
+
+            if map.map_or(false, |map| map.permission(class.id(), perm) == 0) {
+                unresolved.push(requirement.clone());
+            }
+
+            requirements.push(requirement);
+        }
+    }
+
+    DeclRequirements {
+        module: module.to_string(),
+        requirements,
+        unresolved,
+    }
+}
+
+/// The symbols and permissions required by a declaration, by name.
+pub(crate) fn required_by(policy: &Policy, decl: &AvRuleDeclaration) -> Vec<Requirement> {
+    decl_requirements(policy, "", decl, None).requirements
+}
+
+fn max_decl_id<'a, I: Iterator<Item = &'a AvRuleDeclaration>>(decls: I) -> u32 {
+    decls.map(|decl| decl.id()).max().unwrap_or(0)
+}
+
+fn symbol_names(policy: &Policy, kind: SymbolKind) -> Vec<(u32, String)> {
+    fn names<'a, S: 'a + Symbol, I: Iterator<Item = &'a S>>(symbols: I) -> Vec<(u32, String)> {
+        symbols
+            .map(|sym| (sym.id(), sym.name().to_string()))
+            .collect()
+    }
+
+    match kind {
+        SymbolKind::Common => names(policy.common_classes().all()),
+        SymbolKind::Class => names(policy.classes().all()),
+        SymbolKind::Role => names(policy.roles().all()),
+        SymbolKind::Type => names(policy.types().all()),
+        SymbolKind::User => names(policy.users().all()),
+        SymbolKind::Boolean => names(policy.booleans().all()),
+        SymbolKind::Sensitivity => names(policy.sensitivities().all()),
+        SymbolKind::Category => names(policy.categories().all()),
+    }
+}
+
+fn num_symbols(policy: &Policy, kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Common => policy.common_classes().num_primary_names(),
+        SymbolKind::Class => policy.classes().num_primary_names(),
+        SymbolKind::Role => policy.roles().num_primary_names(),
+        SymbolKind::Type => policy.types().num_primary_names(),
+        SymbolKind::User => policy.users().num_primary_names(),
+        SymbolKind::Boolean => policy.booleans().num_primary_names(),
+        SymbolKind::Sensitivity => policy.sensitivities().num_primary_names(),
+        SymbolKind::Category => policy.categories().num_primary_names(),
+    }
+}
+
+/// The values and names of every permission of a class, including those inherited from its
+/// common.
+fn class_permissions(policy: &Policy, class: &Class) -> Vec<(u32, String)> {
+    let common_perms = class
+        .common_name()
+        .and_then(|name| policy.common_classes().get_by_name(name))
+        .into_iter()
+        .flat_map(|common| common.permissions().all());
+
+    class
+        .permissions()
+        .all()
+        .chain(common_perms)
+        .map(|perm| (perm.id(), perm.name().to_string()))
+        .collect()
+}
+
+pub(crate) fn permission_value(policy: &Policy, class: &Class, name: &str) -> Option<u32> {
+    class
+        .permissions()
+        .get_by_name(name)
+        .or_else(|| {
+            class
+                .common_name()
+                .and_then(|common| policy.common_classes().get_by_name(common))
+                .and_then(|common| common.permissions().get_by_name(name))
+        })
+        .map(|perm| perm.id())
+}
+
+/// The access vector for the named permissions of a class, including those of its common.
+pub(crate) fn access_vector<S: AsRef<str>>(
+    policy: &Policy,
+    class: &Class,
+    permissions: &[S],
+) -> Result<u32, UnknownName> {
+    let mut av = 0;
+
+    for perm in permissions {
+        let value = permission_value(policy, class, perm.as_ref()).ok_or_else(|| {
+            UnknownName::Permission {
+                class: class.name().to_string(),
+                permission: perm.as_ref().to_string(),
+            }
+        })?;
+
+        av |= 1 << (value - 1);
+    }
+
+    Ok(av)
+}
@@ -1,12 +1,21 @@
 use croaring::Bitmap;
+use policydb::Class;
 use policydb::CompatibilityProfile;
+use policydb::Feature;
+use policydb::Policy;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Remap;
 use policydb::Symbol;
-use std::io::Read;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use policydb::Type;
+use std::io::{Read, Write};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct MlsLevel {
     sensitivity: u32,
     categories: Bitmap,
@@ -22,6 +31,11 @@ impl PolicyObject for MlsLevel {
             categories,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.sensitivity)?;
+        writer.write_object(&self.categories)
+    }
 }
 
 impl MlsLevel {
@@ -42,9 +56,70 @@ impl MlsLevel {
             categories,
         })
     }
+
+    /// Writes the level in the form read by `decode_expanded`, as runs of consecutive categories.
+    pub fn encode_expanded<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+    ) -> Result<(), PolicyWriteError> {
+        let mut runs: Vec<(u32, u32)> = vec![];
+
+        for cat in self.categories.iter() {
+            match runs.last_mut() {
+                Some(&mut (_, ref mut high)) if *high + 1 == cat => *high = cat,
+                _ => runs.push((cat, cat)),
+            }
+        }
+
+        writer.write_u32(self.sensitivity)?;
+        writer.write_len(runs.len())?;
+
+        for (low, high) in runs {
+            writer.write_u32(low)?;
+            writer.write_u32(high)?;
+        }
+
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+impl Remap for MlsLevel {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.sensitivity = map.id(SymbolKind::Sensitivity, self.sensitivity);
+        self.categories = map.bitmap(SymbolKind::Category, &self.categories);
+    }
+}
+
+impl MlsLevel {
+    /// Creates a level from a sensitivity id and the 0-based bits of its categories.
+    pub fn new(sensitivity: u32, categories: Bitmap) -> Self {
+        MlsLevel {
+            sensitivity,
+            categories,
+        }
+    }
+
+    pub fn sensitivity(&self) -> u32 {
+        self.sensitivity
+    }
+
+    pub fn categories(&self) -> &Bitmap {
+        &self.categories
+    }
+
+    /// Checks if this level is at least as sensitive as `other`, and has all of its categories.
+    pub fn dominates(&self, other: &MlsLevel) -> bool {
+        self.sensitivity >= other.sensitivity && other.categories.is_subset(&self.categories)
+    }
+}
+
+impl PartialEq for MlsLevel {
+    fn eq(&self, other: &MlsLevel) -> bool {
+        self.sensitivity == other.sensitivity && self.categories == other.categories
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct MlsRange {
     low: MlsLevel,
     high: MlsLevel,
@@ -74,6 +149,27 @@ impl PolicyObject for MlsRange {
             },
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        let is_single_level = self.low.sensitivity == self.high.sensitivity
+            && self.low.categories == self.high.categories;
+        let items = if is_single_level { 1 } else { 2 };
+
+        writer.write_u32(items)?;
+        writer.write_u32(self.low.sensitivity)?;
+
+        if items > 1 {
+            writer.write_u32(self.high.sensitivity)?;
+        }
+
+        writer.write_object(&self.low.categories)?;
+
+        if items > 1 {
+            writer.write_object(&self.high.categories)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl MlsRange {
@@ -83,6 +179,35 @@ impl MlsRange {
 
         Ok(MlsRange { low, high })
     }
+
+    pub fn encode_expanded<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+    ) -> Result<(), PolicyWriteError> {
+        self.low.encode_expanded(writer)?;
+        self.high.encode_expanded(writer)
+    }
+}
+
+impl Remap for MlsRange {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.low.remap(map);
+        self.high.remap(map);
+    }
+}
+
+impl MlsRange {
+    pub fn new(low: MlsLevel, high: MlsLevel) -> Self {
+        MlsRange { low, high }
+    }
+
+    pub fn low(&self) -> &MlsLevel {
+        &self.low
+    }
+
+    pub fn high(&self) -> &MlsLevel {
+        &self.high
+    }
 }
 
 #[derive(Debug)]
@@ -93,6 +218,13 @@ pub struct Sensitivity {
     is_alias: bool,
 }
 
+impl Sensitivity {
+    /// The level of this sensitivity with every category that may be used with it.
+    pub fn level(&self) -> &MlsLevel {
+        &self.level
+    }
+}
+
 impl Symbol for Sensitivity {
     fn id(&self) -> u32 {
         self.id
@@ -101,6 +233,10 @@ impl Symbol for Sensitivity {
     fn name(&self) -> &str {
         self.name.as_str()
     }
+
+    fn is_alias(&self) -> bool {
+        self.is_alias
+    }
 }
 
 impl PolicyObject for Sensitivity {
@@ -111,12 +247,19 @@ impl PolicyObject for Sensitivity {
         let level: MlsLevel = reader.read_object()?;
 
         Ok(Sensitivity {
-            id: 0,
+            id: level.sensitivity,
             name,
             level,
             is_alias,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_u32(self.is_alias as u32)?;
+        writer.write_string(&self.name)?;
+        writer.write_object(&self.level)
+    }
 }
 
 #[derive(Debug)]
@@ -134,6 +277,10 @@ impl Symbol for Category {
     fn name(&self) -> &str {
         self.name.as_str()
     }
+
+    fn is_alias(&self) -> bool {
+        self.is_alias
+    }
 }
 
 impl PolicyObject for Category {
@@ -145,4 +292,123 @@ impl PolicyObject for Category {
 
         Ok(Category { id, name, is_alias })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_u32(self.id)?;
+        writer.write_u32(self.is_alias as u32)?;
+        writer.write_string(&self.name)?;
+
+        Ok(())
+    }
+}
+
+/// A `range_transition` rule, selecting the MLS range of a new object or process.
+#[derive(Debug)]
+pub struct RangeTransition {
+    source_type: u32,
+    target_type: u32,
+    class: Option<u32>,
+    range: MlsRange,
+}
+
+impl RangeTransition {
+    pub(crate) fn new(
+        source_type: u32,
+        target_type: u32,
+        class: Option<u32>,
+        range: MlsRange,
+    ) -> Self {
+        RangeTransition {
+            source_type,
+            target_type,
+            class,
+            range,
+        }
+    }
+
+    pub fn source_type<'a>(&self, policy: &'a Policy) -> Option<&'a Type> {
+        policy.types().get(self.source_type)
+    }
+
+    pub fn target_type<'a>(&self, policy: &'a Policy) -> Option<&'a Type> {
+        policy.types().get(self.target_type)
+    }
+
+    /// Drops the class of a `process` transition, for policies that only have process range
+    /// transitions.
+    pub(crate) fn clear_class(&mut self) {
+        self.class = None;
+    }
+
+    /// The class this transition applies to.  Policies older than `POLICYDB_VERSION_RANGETRANS`
+    /// don't record a class and only apply to `process`.
+    pub fn class<'a>(&self, policy: &'a Policy) -> Option<&'a Class> {
+        match self.class {
+            Some(class) => policy.classes().get(class),
+            None => policy.classes().get_by_name("process"),
+        }
+    }
+
+    pub fn source_type_id(&self) -> u32 {
+        self.source_type
+    }
+
+    pub fn target_type_id(&self) -> u32 {
+        self.target_type
+    }
+
+    pub fn class_id(&self) -> Option<u32> {
+        self.class
+    }
+
+    pub fn range(&self) -> &MlsRange {
+        &self.range
+    }
+}
+
+impl PolicyObject for RangeTransition {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let source_type = reader.read_u32()?;
+        let target_type = reader.read_u32()?;
+        let class = if reader.profile().supports(Feature::RangeTransitionClass) {
+            Some(reader.read_u32()?)
+        } else {
+            None
+        };
+
+        let range = reader.read_object()?;
+
+        Ok(RangeTransition {
+            source_type,
+            target_type,
+            class,
+            range,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.source_type)?;
+        writer.write_u32(self.target_type)?;
+
+        if writer.profile().supports(Feature::RangeTransitionClass) {
+            // Older policies only had process range transitions.
+            let class = match self.class {
+                Some(class) => class,
+                None => {
+                    return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                        Feature::RangeTransitionClass,
+                    ))
+                }
+            };
+
+            writer.write_u32(class)?;
+        } else if self.class.is_some() {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::RangeTransitionClass,
+            ));
+        }
+
+        writer.write_object(&self.range)
+    }
 }
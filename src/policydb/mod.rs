@@ -1,41 +1,94 @@
+mod avrule;
 mod avtab;
 mod bitmap;
+mod builder;
 mod class;
 mod conditional;
 mod cons;
+mod context;
+mod downgrade;
+mod edit;
+mod expand;
+mod filename_trans;
+mod link;
 mod mls;
+mod module;
+mod ocontext;
+mod package;
 mod polcap;
 mod profile;
 mod reader;
+mod requires;
 mod role;
+mod services;
 mod symtable;
 mod ty;
 mod user;
+mod writer;
 
+pub use self::avrule::{
+    AvRule, AvRuleKind, AvRuleList, ClassPermission, ExtendedPermissions, FilenameTransitionRule,
+    RangeTransitionRule, RoleAllowRule, RoleTransitionRule,
+};
 pub use self::avtab::{
     AccessVector, AccessVectorTable, AccessVectorTableEntry, AccessVectorTableKey,
 };
-pub use self::class::{Class, Common, Permission};
-pub use self::conditional::Boolean;
-pub use self::cons::{Constraint, ConstraintExpression, ConstraintExpressionKind};
-pub use self::mls::{Category, MlsLevel, MlsRange, Sensitivity};
+pub use self::bitmap::{EBitmap, EBitmapNode};
+pub use self::builder::{BuildError, PolicyBuilder};
+pub use self::class::{Class, Common, ObjectDefault, Permission, RangeDefault};
+pub use self::conditional::{Boolean, ConditionalExpression, ConditionalNode};
+pub use self::cons::{
+    BinaryOp, Constraint, ConstraintAttribute, ConstraintExpression, ConstraintExpressionKind,
+    ConstraintOp, UnaryOp,
+};
+pub use self::context::SecurityContext;
+pub use self::downgrade::{downgrade, DiscardedFeature, DowngradeError, DowngradeReport};
+pub use self::edit::PolicyEditError;
+pub use self::expand::{
+    expand, expand_with_report, ExpandError, ExpandReport, ResolvedTunableBlock,
+};
+pub use self::filename_trans::FilenameTransition;
+pub use self::link::{link, LinkError, Requirement};
+pub use self::mls::{Category, MlsLevel, MlsRange, RangeTransition, Sensitivity};
+pub use self::module::{
+    AvRuleBlock, AvRuleDeclaration, DeclaredSymbols, ModulePolicy, Scope, ScopeIndex, ScopeKind,
+};
+pub use self::ocontext::{
+    FileSystemContext, FsUseBehavior, FsUseContext, GenfsContext, IbEndPortContext, IbPkeyContext,
+    InitialSid, NetworkInterfaceContext, Node6Context, NodeContext, ObjectContexts, PortContext,
+};
+pub use self::package::{FileContextSpec, PolicyPackage, SeUserMapping};
 pub use self::polcap::{PolicyCapability, PolicyCapabilitySet};
 pub use self::profile::{CompatibilityProfile, Feature};
 pub use self::reader::{PolicyReadError, PolicyReader};
-pub use self::symtable::{Symbol, SymbolTable};
-pub use self::{role::Role, role::RoleSet, ty::Type, ty::TypeSet, user::User};
+pub use self::requires::{module_requirements, ModuleRequirements, SymbolNames};
+pub use self::services::{AccessDecision, ExtendedPermissionDecision, IoctlDecision, LabelError};
+pub use self::symtable::{Symbol, SymbolKind, SymbolTable};
+pub use self::writer::{PolicyWriteError, PolicyWriter};
+pub use self::{
+    role::Role, role::RoleAllow, role::RoleFlavor, role::RoleSet, role::RoleTransition,
+    ty::InvalidTypeId, ty::Type, ty::TypeAttributeMap, ty::TypeFlavor, ty::TypeSet, user::User,
+};
 
-use std::io::Read;
+pub(crate) use self::link::{Remap, SymbolMap};
+
+use std::io::{Read, Write};
 
 pub(crate) mod constants {
     pub(crate) const PLATFORM_SELINUX: &str = "SE Linux";
 
+    pub(crate) const PLATFORM_SELINUX_MODULE: &str = "SE Linux Module";
+
     pub(crate) const PLATFORM_XEN: &str = "XenFlask";
 
     pub(crate) const SELINUX_MAGIC_NUMBER: u32 = 0xf97cff8c;
 
     pub(crate) const SELINUX_MOD_MAGIC_NUMBER: u32 = 0xf97cff8d;
 
+    pub(crate) const POLICY_BASE: u32 = 1;
+
+    pub(crate) const POLICY_MOD: u32 = 2;
+
     pub(crate) const CONFIG_MLS_ENABLED: u32 = 0x00000001;
 
     pub(crate) const CONFIG_REJECT_UNKNOWN: u32 = 0x00000002;
@@ -48,22 +101,33 @@ pub enum PolicyError {}
 #[derive(Debug)]
 pub struct Policy {
     version: u32,
+    config: PolicyConfig,
     polcaps: PolicyCapabilitySet,
     profile: CompatibilityProfile,
     avtab: AccessVectorTable,
     booleans: SymbolTable<Boolean>,
+    conditionals: Vec<ConditionalNode>,
     categories: SymbolTable<Category>,
     common_classes: SymbolTable<Common>,
     classes: SymbolTable<Class>,
+    filename_transitions: Vec<FilenameTransition>,
+    ocontexts: ObjectContexts,
+    module: Option<ModulePolicy>,
+    range_transitions: Vec<RangeTransition>,
     roles: SymbolTable<Role>,
+    role_transitions: Vec<RoleTransition>,
+    role_allows: Vec<RoleAllow>,
     sensitivities: SymbolTable<Sensitivity>,
     types: SymbolTable<Type>,
+    type_attr_map: TypeAttributeMap,
     users: SymbolTable<User>,
 }
 
 pub trait PolicyObject: Sized {
     fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError>;
 
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError>;
+
     fn decode_collection<R: Read>(
         reader: &mut PolicyReader<R>,
         profile: &CompatibilityProfile,
@@ -80,10 +144,29 @@ pub trait PolicyObject: Sized {
 }
 
 impl Policy {
+    pub fn avtab(&self) -> &AccessVectorTable {
+        &self.avtab
+    }
+
     pub fn booleans(&self) -> &SymbolTable<Boolean> {
         &self.booleans
     }
 
+    /// The booleans that can be toggled at runtime, excluding tunables.
+    pub fn runtime_booleans<'a>(&'a self) -> impl Iterator<Item = &'a Boolean> + 'a {
+        self.booleans.all().filter(|boolean| !boolean.is_tunable())
+    }
+
+    /// The tunables of a module, which share the boolean table but are resolved when the policy
+    /// is built.
+    pub fn tunables<'a>(&'a self) -> impl Iterator<Item = &'a Boolean> + 'a {
+        self.booleans.all().filter(|boolean| boolean.is_tunable())
+    }
+
+    pub fn conditionals(&self) -> &[ConditionalNode] {
+        &self.conditionals
+    }
+
     pub fn categories(&self) -> &SymbolTable<Category> {
         &self.categories
     }
@@ -96,10 +179,46 @@ impl Policy {
         &self.classes
     }
 
+    pub fn filename_transitions(&self) -> &[FilenameTransition] {
+        &self.filename_transitions
+    }
+
+    /// The avrule blocks and scope tables of a modular policy, or `None` for kernel policies.
+    pub fn module(&self) -> Option<&ModulePolicy> {
+        self.module.as_ref()
+    }
+
+    pub fn ocontexts(&self) -> &ObjectContexts {
+        &self.ocontexts
+    }
+
+    pub fn range_transitions(&self) -> &[RangeTransition] {
+        &self.range_transitions
+    }
+
     pub fn roles(&self) -> &SymbolTable<Role> {
         &self.roles
     }
 
+    pub fn role_transitions(&self) -> &[RoleTransition] {
+        &self.role_transitions
+    }
+
+    pub fn role_allows(&self) -> &[RoleAllow] {
+        &self.role_allows
+    }
+
+    /// The role attributes declared in this policy.  These only exist in modules, as they are
+    /// expanded away in kernel policies.
+    pub fn role_attributes<'a>(&'a self) -> impl Iterator<Item = &'a Role> + 'a {
+        self.roles.all().filter(|role| role.is_attribute())
+    }
+
+    /// The types marked as permissive domains in this policy.
+    pub fn permissive_types<'a>(&'a self) -> impl Iterator<Item = &'a Type> + 'a {
+        self.types.all().filter(|ty| ty.is_permissive())
+    }
+
     pub fn sensitivities(&self) -> &SymbolTable<Sensitivity> {
         &self.sensitivities
     }
@@ -108,10 +227,57 @@ impl Policy {
         &self.types
     }
 
+    /// The type attributes declared in this policy.
+    pub fn type_attributes<'a>(&'a self) -> impl Iterator<Item = &'a Type> + 'a {
+        self.types.all().filter(|ty| ty.is_attribute())
+    }
+
+    /// The attributes that the given type is a member of, excluding the type itself.
+    pub fn attributes_of<'a>(&'a self, ty: &Type) -> impl Iterator<Item = &'a Type> + 'a {
+        let id = ty.id();
+
+        self.type_attr_map
+            .attributes_of(id)
+            .filter(move |attr| *attr != id)
+            .filter_map(move |attr| self.types.get(attr))
+    }
+
+    /// The types that are members of the given attribute, excluding the attribute itself.
+    pub fn types_of<'a>(&'a self, attr: &Type) -> impl Iterator<Item = &'a Type> + 'a {
+        let id = attr.id();
+
+        self.type_attr_map
+            .types_of(id)
+            .filter(move |ty| *ty != id)
+            .filter_map(move |ty| self.types.get(ty))
+    }
+
+    pub fn type_attr_map(&self) -> &TypeAttributeMap {
+        &self.type_attr_map
+    }
+
     pub fn users(&self) -> &SymbolTable<User> {
         &self.users
     }
 
+    /// The name of the symbol with the given kind and id.
+    pub fn symbol_name(&self, kind: SymbolKind, id: u32) -> Option<&str> {
+        match kind {
+            SymbolKind::Common => self.common_classes.get(id).map(Symbol::name),
+            SymbolKind::Class => self.classes.get(id).map(Symbol::name),
+            SymbolKind::Role => self.roles.get(id).map(Symbol::name),
+            SymbolKind::Type => self.types.get(id).map(Symbol::name),
+            SymbolKind::User => self.users.get(id).map(Symbol::name),
+            SymbolKind::Boolean => self.booleans.get(id).map(Symbol::name),
+            SymbolKind::Sensitivity => self.sensitivities.get(id).map(Symbol::name),
+            SymbolKind::Category => self.categories.get(id).map(Symbol::name),
+        }
+    }
+
+    pub fn config(&self) -> &PolicyConfig {
+        &self.config
+    }
+
     pub fn profile(&self) -> &CompatibilityProfile {
         &self.profile
     }
@@ -138,6 +304,8 @@ pub enum PolicyTargetPlatform {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PolicyType {
     Kernel(PolicyTargetPlatform),
+    /// A policy module.  Only non-base modules have a name and version, these are empty for the
+    /// base module.
     Module {
         is_base_module: bool,
         name: String,
@@ -154,8 +322,51 @@ impl PolicyType {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// How the kernel treats classes and permissions that it knows about but that aren't defined in
+/// the policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandleUnknown {
+    Deny,
+    Reject,
+    Allow,
+}
+
+/// The policy-wide settings stored in the `config` word of the policy header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct PolicyConfig {
     pub mls_enabled: bool,
-    pub allow_unknowns: bool,
+    pub handle_unknown: HandleUnknown,
+}
+
+impl PolicyConfig {
+    pub fn from_bits(config: u32) -> Self {
+        let handle_unknown = if config & constants::CONFIG_REJECT_UNKNOWN != 0 {
+            HandleUnknown::Reject
+        } else if config & constants::CONFIG_ALLOW_UNKNOWN != 0 {
+            HandleUnknown::Allow
+        } else {
+            HandleUnknown::Deny
+        };
+
+        PolicyConfig {
+            mls_enabled: config & constants::CONFIG_MLS_ENABLED != 0,
+            handle_unknown,
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        let mls = if self.mls_enabled {
+            constants::CONFIG_MLS_ENABLED
+        } else {
+            0
+        };
+
+        let handle_unknown = match self.handle_unknown {
+            HandleUnknown::Deny => 0,
+            HandleUnknown::Reject => constants::CONFIG_REJECT_UNKNOWN,
+            HandleUnknown::Allow => constants::CONFIG_ALLOW_UNKNOWN,
+        };
+
+        mls | handle_unknown
+    }
 }
@@ -0,0 +1,605 @@
+use croaring::Bitmap;
+use policydb::AvRuleList;
+use policydb::Boolean;
+use policydb::Category;
+use policydb::Class;
+use policydb::Common;
+use policydb::ConditionalNode;
+use policydb::Feature;
+use policydb::FilenameTransitionRule;
+use policydb::PolicyObject;
+use policydb::PolicyReadError;
+use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::RangeTransitionRule;
+use policydb::Remap;
+use policydb::Role;
+use policydb::RoleAllowRule;
+use policydb::RoleTransitionRule;
+use policydb::Sensitivity;
+use policydb::Symbol;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use policydb::SymbolTable;
+use policydb::Type;
+use policydb::User;
+use std::io::{Read, Write};
+
+pub(crate) mod constants {
+    pub const SCOPE_REQ: u32 = 1;
+    pub const SCOPE_DECL: u32 = 2;
+}
+
+/// The parts of a modular policy that follow the global symbol tables: the avrule blocks holding
+/// the module's rules and declarations, and the scope tables recording where each symbol is
+/// declared or required.
+#[derive(Debug)]
+pub struct ModulePolicy {
+    blocks: Vec<AvRuleBlock>,
+    scopes: Vec<Vec<Scope>>,
+}
+
+impl ModulePolicy {
+    pub(crate) fn new(blocks: Vec<AvRuleBlock>, scopes: Vec<Vec<Scope>>) -> Self {
+        ModulePolicy { blocks, scopes }
+    }
+
+    pub(crate) fn into_parts(self) -> (Vec<AvRuleBlock>, Vec<Vec<Scope>>) {
+        (self.blocks, self.scopes)
+    }
+
+    pub fn blocks(&self) -> &[AvRuleBlock] {
+        &self.blocks
+    }
+
+    /// The global block, which holds every rule outside of an `optional` block.
+    pub fn global_block(&self) -> Option<&AvRuleBlock> {
+        self.blocks.first()
+    }
+
+    /// Finds the declaration with the given id in any of this module's blocks.
+    pub fn declaration(&self, id: u32) -> Option<&AvRuleDeclaration> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.declarations())
+            .find(|decl| decl.id() == id)
+    }
+
+    pub fn scopes(&self, kind: SymbolKind) -> &[Scope] {
+        &self.scopes[kind.index()]
+    }
+}
+
+impl PolicyObject for ModulePolicy {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let num_blocks = reader.read_u32()? as usize;
+        let mut blocks: Vec<AvRuleBlock> = reader.read_objects(num_blocks)?;
+
+        // Blocks don't record whether they are optional: every block after the global one is.
+        for block in blocks.iter_mut().skip(1) {
+            block.optional = true;
+        }
+        let mut scopes = Vec::with_capacity(SymbolKind::all().len());
+
+        for _ in SymbolKind::all() {
+            let num_scopes = reader.read_u32()? as usize;
+            scopes.push(reader.read_objects(num_scopes)?);
+        }
+
+        Ok(ModulePolicy { blocks, scopes })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.blocks.len())?;
+        writer.write_objects(&self.blocks)?;
+
+        for scopes in &self.scopes {
+            writer.write_len(scopes.len())?;
+            writer.write_objects(scopes)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A block of alternative declarations.  The global block always has a single declaration,
+/// while `optional` blocks have one declaration per `else` branch.
+#[derive(Debug)]
+pub struct AvRuleBlock {
+    optional: bool,
+    declarations: Vec<AvRuleDeclaration>,
+}
+
+impl AvRuleBlock {
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    pub fn declarations(&self) -> &[AvRuleDeclaration] {
+        &self.declarations
+    }
+
+    pub(crate) fn declarations_mut(&mut self) -> &mut [AvRuleDeclaration] {
+        &mut self.declarations
+    }
+}
+
+impl Remap for AvRuleBlock {
+    fn remap(&mut self, map: &SymbolMap) {
+        for decl in &mut self.declarations {
+            decl.remap(map);
+        }
+    }
+}
+
+impl PolicyObject for AvRuleBlock {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let num_declarations = reader.read_u32()? as usize;
+        let declarations = reader.read_objects(num_declarations)?;
+
+        Ok(AvRuleBlock {
+            optional: false,
+            declarations,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.declarations.len())?;
+        writer.write_objects(&self.declarations)
+    }
+}
+
+/// A single branch of an avrule block, with the rules and symbols it declares and the symbols it
+/// requires from other modules.
+#[derive(Debug)]
+pub struct AvRuleDeclaration {
+    id: u32,
+    enabled: bool,
+    conditionals: Vec<ConditionalNode<AvRuleList>>,
+    avrules: AvRuleList,
+    role_transitions: Vec<RoleTransitionRule>,
+    role_allows: Vec<RoleAllowRule>,
+    filename_transitions: Vec<FilenameTransitionRule>,
+    range_transitions: Vec<RangeTransitionRule>,
+    required: ScopeIndex,
+    declared: ScopeIndex,
+    symbols: DeclaredSymbols,
+}
+
+impl AvRuleDeclaration {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Checks if the linker enabled this declaration when the module was written.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn conditionals(&self) -> &[ConditionalNode<AvRuleList>] {
+        &self.conditionals
+    }
+
+    pub fn avrules(&self) -> &AvRuleList {
+        &self.avrules
+    }
+
+    pub fn role_transitions(&self) -> &[RoleTransitionRule] {
+        &self.role_transitions
+    }
+
+    pub fn role_allows(&self) -> &[RoleAllowRule] {
+        &self.role_allows
+    }
+
+    pub fn filename_transitions(&self) -> &[FilenameTransitionRule] {
+        &self.filename_transitions
+    }
+
+    pub fn range_transitions(&self) -> &[RangeTransitionRule] {
+        &self.range_transitions
+    }
+
+    /// The symbols this declaration requires to be declared elsewhere.
+    pub fn required(&self) -> &ScopeIndex {
+        &self.required
+    }
+
+    /// The symbols declared by this declaration.
+    pub fn declared(&self) -> &ScopeIndex {
+        &self.declared
+    }
+
+    /// The symbols local to this declaration.
+    pub fn symbols(&self) -> &DeclaredSymbols {
+        &self.symbols
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+impl Remap for AvRuleDeclaration {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.id = map.decl_id(self.id);
+
+        for cond in &mut self.conditionals {
+            cond.remap(map);
+        }
+
+        self.avrules.remap(map);
+
+        for rule in &mut self.role_transitions {
+            rule.remap(map);
+        }
+
+        for rule in &mut self.role_allows {
+            rule.remap(map);
+        }
+
+        for rule in &mut self.filename_transitions {
+            rule.remap(map);
+        }
+
+        for rule in &mut self.range_transitions {
+            rule.remap(map);
+        }
+
+        self.required.remap(map);
+        self.declared.remap(map);
+        self.symbols.remap(map);
+    }
+}
+
+impl PolicyObject for AvRuleDeclaration {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let id = reader.read_u32()?;
+        let enabled = reader.read_u32()? == 1;
+
+        let num_conditionals = reader.read_u32()? as usize;
+        let conditionals = reader.read_objects(num_conditionals)?;
+        let avrules = reader.read_object()?;
+        let num_role_transitions = reader.read_u32()? as usize;
+        let role_transitions = reader.read_objects(num_role_transitions)?;
+        let num_role_allows = reader.read_u32()? as usize;
+        let role_allows = reader.read_objects(num_role_allows)?;
+
+        let filename_transitions = if reader.profile().supports(Feature::FilenameTransitions) {
+            let num_filename_transitions = reader.read_u32()? as usize;
+            reader.read_objects(num_filename_transitions)?
+        } else {
+            vec![]
+        };
+
+        // Range transition rules moved into declarations with `MOD_POLICYDB_VERSION_RANGETRANS`.
+        let range_transitions = if reader.profile().supports(Feature::RangeTransitionClass) {
+            let num_range_transitions = reader.read_u32()? as usize;
+            reader.read_objects(num_range_transitions)?
+        } else {
+            vec![]
+        };
+
+        let required = reader.read_object()?;
+        let declared = reader.read_object()?;
+        let symbols = reader.read_object()?;
+
+        Ok(AvRuleDeclaration {
+            id,
+            enabled,
+            conditionals,
+            avrules,
+            role_transitions,
+            role_allows,
+            filename_transitions,
+            range_transitions,
+            required,
+            declared,
+            symbols,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.id)?;
+        writer.write_u32(self.enabled as u32)?;
+        writer.write_len(self.conditionals.len())?;
+        writer.write_objects(&self.conditionals)?;
+        writer.write_object(&self.avrules)?;
+        writer.write_len(self.role_transitions.len())?;
+        writer.write_objects(&self.role_transitions)?;
+        writer.write_len(self.role_allows.len())?;
+        writer.write_objects(&self.role_allows)?;
+
+        if writer.profile().supports(Feature::FilenameTransitions) {
+            writer.write_len(self.filename_transitions.len())?;
+            writer.write_objects(&self.filename_transitions)?;
+        } else if !self.filename_transitions.is_empty() {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::FilenameTransitions,
+            ));
+        }
+
+        if writer.profile().supports(Feature::RangeTransitionClass) {
+            writer.write_len(self.range_transitions.len())?;
+            writer.write_objects(&self.range_transitions)?;
+        } else if !self.range_transitions.is_empty() {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::RangeTransitionClass,
+            ));
+        }
+
+        writer.write_object(&self.required)?;
+        writer.write_object(&self.declared)?;
+        writer.write_object(&self.symbols)
+    }
+}
+
+/// A set of symbol ids for each kind of symbol, along with the permissions used from each class.
+#[derive(Debug)]
+pub struct ScopeIndex {
+    symbols: Vec<Bitmap>,
+    class_permissions: Vec<Bitmap>,
+}
+
+impl ScopeIndex {
+    /// The ids of the symbols of the given kind in this index.
+    pub fn symbol_ids<'a>(&'a self, kind: SymbolKind) -> impl Iterator<Item = u32> + 'a {
+        self.symbols[kind.index()].iter().map(|bit| bit + 1)
+    }
+
+    pub fn contains(&self, kind: SymbolKind, id: u32) -> bool {
+        id > 0 && self.symbols[kind.index()].contains(id - 1)
+    }
+
+    /// The ids of the permissions in this index for the class with the given id.
+    pub fn class_permission_ids<'a>(&'a self, class: u32) -> impl Iterator<Item = u32> + 'a {
+        class
+            .checked_sub(1)
+            .and_then(|idx| self.class_permissions.get(idx as usize))
+            .into_iter()
+            .flat_map(|perms| perms.iter())
+            .map(|bit| bit + 1)
+    }
+}
+
+impl Remap for ScopeIndex {
+    fn remap(&mut self, map: &SymbolMap) {
+        for (idx, symbols) in self.symbols.iter_mut().enumerate() {
+            *symbols = map.bitmap(SymbolKind::all()[idx], symbols);
+        }
+
+        let mut class_permissions = vec![];
+
+        for (idx, perms) in self.class_permissions.iter().enumerate() {
+            let class = idx as u32 + 1;
+            let linked_class = map.id(SymbolKind::Class, class) as usize;
+
+            if linked_class == 0 {
+                continue;
+            }
+
+            if class_permissions.len() < linked_class {
+                class_permissions.resize(linked_class, Bitmap::create());
+            }
+
+            class_permissions[linked_class - 1] = map.permission_bitmap(class, perms);
+        }
+
+        self.class_permissions = class_permissions;
+    }
+}
+
+impl PolicyObject for ScopeIndex {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let mut symbols = Vec::with_capacity(SymbolKind::all().len());
+
+        for _ in SymbolKind::all() {
+            symbols.push(reader.read_object()?);
+        }
+
+        let num_classes = reader.read_u32()? as usize;
+        let class_permissions = reader.read_objects(num_classes)?;
+
+        Ok(ScopeIndex {
+            symbols,
+            class_permissions,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_objects(&self.symbols)?;
+        writer.write_len(self.class_permissions.len())?;
+        writer.write_objects(&self.class_permissions)
+    }
+}
+
+/// The symbol tables local to an `AvRuleDeclaration`.
+#[derive(Debug)]
+pub struct DeclaredSymbols {
+    common_classes: SymbolTable<Common>,
+    classes: SymbolTable<Class>,
+    roles: SymbolTable<Role>,
+    types: SymbolTable<Type>,
+    users: SymbolTable<User>,
+    booleans: SymbolTable<Boolean>,
+    sensitivities: SymbolTable<Sensitivity>,
+    categories: SymbolTable<Category>,
+}
+
+impl DeclaredSymbols {
+    pub fn common_classes(&self) -> &SymbolTable<Common> {
+        &self.common_classes
+    }
+
+    pub fn classes(&self) -> &SymbolTable<Class> {
+        &self.classes
+    }
+
+    pub fn roles(&self) -> &SymbolTable<Role> {
+        &self.roles
+    }
+
+    pub fn types(&self) -> &SymbolTable<Type> {
+        &self.types
+    }
+
+    pub fn users(&self) -> &SymbolTable<User> {
+        &self.users
+    }
+
+    pub fn booleans(&self) -> &SymbolTable<Boolean> {
+        &self.booleans
+    }
+
+    pub fn sensitivities(&self) -> &SymbolTable<Sensitivity> {
+        &self.sensitivities
+    }
+
+    pub fn categories(&self) -> &SymbolTable<Category> {
+        &self.categories
+    }
+}
+
+impl Remap for DeclaredSymbols {
+    fn remap(&mut self, map: &SymbolMap) {
+        fn remap_table<S: Symbol + Remap>(table: &mut SymbolTable<S>, map: &SymbolMap) {
+            let old_table = ::std::mem::replace(table, SymbolTable::with_capacity(0));
+
+            for mut sym in old_table.into_symbols() {
+                sym.remap(map);
+
+                if sym.id() != 0 {
+                    table.insert(sym);
+                }
+            }
+        }
+
+        // Declarations only add to the roles, types, users and booleans of a module, the other
+        // tables repeat global symbols and are dropped.
+        self.common_classes = SymbolTable::with_capacity(0);
+        self.classes = SymbolTable::with_capacity(0);
+        self.sensitivities = SymbolTable::with_capacity(0);
+        self.categories = SymbolTable::with_capacity(0);
+
+        remap_table(&mut self.roles, map);
+        remap_table(&mut self.types, map);
+        remap_table(&mut self.users, map);
+        remap_table(&mut self.booleans, map);
+    }
+}
+
+impl PolicyObject for DeclaredSymbols {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        Ok(DeclaredSymbols {
+            common_classes: reader.read_symbol_table()?,
+            classes: reader.read_symbol_table()?,
+            roles: reader.read_symbol_table()?,
+            types: reader.read_symbol_table()?,
+            users: reader.read_symbol_table()?,
+            booleans: reader.read_symbol_table()?,
+            sensitivities: reader.read_symbol_table()?,
+            categories: reader.read_symbol_table()?,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_symbol_table(&self.common_classes)?;
+        writer.write_symbol_table(&self.classes)?;
+        writer.write_symbol_table(&self.roles)?;
+        writer.write_symbol_table(&self.types)?;
+        writer.write_symbol_table(&self.users)?;
+        writer.write_symbol_table(&self.booleans)?;
+        writer.write_symbol_table(&self.sensitivities)?;
+        writer.write_symbol_table(&self.categories)
+    }
+}
+
+/// Whether a symbol is declared or only required by a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeKind {
+    Required,
+    Declared,
+}
+
+/// An entry in a module's scope table, listing the declarations that declare or require a
+/// symbol.
+#[derive(Debug)]
+pub struct Scope {
+    name: String,
+    kind: ScopeKind,
+    decl_ids: Vec<u32>,
+}
+
+impl Scope {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn kind(&self) -> ScopeKind {
+        self.kind
+    }
+
+    pub fn decl_ids(&self) -> &[u32] {
+        &self.decl_ids
+    }
+
+    /// Merges the scope of the same symbol from another module into this one.  Once a symbol is
+    /// declared, only the declarations that declare it are kept.
+    pub(crate) fn merge(&mut self, other: Scope) {
+        match (self.kind, other.kind) {
+            (ScopeKind::Required, ScopeKind::Declared) => *self = other,
+            (ScopeKind::Declared, ScopeKind::Required) => {}
+            _ => self.decl_ids.extend(other.decl_ids),
+        }
+    }
+}
+
+impl Remap for Scope {
+    fn remap(&mut self, map: &SymbolMap) {
+        for decl_id in &mut self.decl_ids {
+            *decl_id = map.decl_id(*decl_id);
+        }
+    }
+}
+
+impl PolicyObject for Scope {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read_string(name_len)?;
+        let kind = match reader.read_u32()? {
+            constants::SCOPE_REQ => ScopeKind::Required,
+            constants::SCOPE_DECL => ScopeKind::Declared,
+            kind => return Err(PolicyReadError::InvalidScope(kind)),
+        };
+
+        let num_decl_ids = reader.read_u32()? as usize;
+        let mut decl_ids = Vec::with_capacity(num_decl_ids);
+
+        for _ in 0..num_decl_ids {
+            decl_ids.push(reader.read_u32()?);
+        }
+
+        Ok(Scope {
+            name,
+            kind,
+            decl_ids,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_string(&self.name)?;
+        writer.write_u32(match self.kind {
+            ScopeKind::Required => constants::SCOPE_REQ,
+            ScopeKind::Declared => constants::SCOPE_DECL,
+        })?;
+        writer.write_len(self.decl_ids.len())?;
+
+        for decl_id in &self.decl_ids {
+            writer.write_u32(*decl_id)?;
+        }
+
+        Ok(())
+    }
+}
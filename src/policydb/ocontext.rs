@@ -0,0 +1,1019 @@
+use policydb::writer::group_consecutive;
+use policydb::Class;
+use policydb::CompatibilityProfile;
+use policydb::Feature;
+use policydb::Policy;
+use policydb::PolicyObject;
+use policydb::PolicyReadError;
+use policydb::PolicyReader;
+use policydb::PolicyTargetPlatform;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::SecurityContext;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::RangeInclusive;
+
+pub(crate) mod constants {
+    pub const OCON_ISID: u32 = 0; /* initial SIDs */
+    pub const OCON_FS: u32 = 1; /* unlabeled file systems */
+    pub const OCON_PORT: u32 = 2; /* TCP and UDP port numbers */
+    pub const OCON_NETIF: u32 = 3; /* network interfaces */
+    pub const OCON_NODE: u32 = 4; /* nodes */
+    pub const OCON_FSUSE: u32 = 5; /* fs_use */
+    pub const OCON_NODE6: u32 = 6; /* IPv6 nodes */
+    pub const OCON_IBPKEY: u32 = 7; /* Infiniband PKEY */
+    pub const OCON_IBENDPORT: u32 = 8; /* Infiniband End Port */
+
+    pub const OCON_XEN_ISID: u32 = 0; /* initial SIDs */
+    pub const OCON_XEN_PIRQ: u32 = 1; /* physical irqs */
+    pub const OCON_XEN_IOPORT: u32 = 2; /* io ports */
+    pub const OCON_XEN_IOMEM: u32 = 3; /* io memory */
+    pub const OCON_XEN_PCIDEVICE: u32 = 4; /* pci devices */
+    pub const OCON_XEN_DEVICETREE: u32 = 5; /* device tree node */
+
+    pub const SECURITY_FS_USE_XATTR: u32 = 1; /* use xattr */
+    pub const SECURITY_FS_USE_TRANS: u32 = 2; /* use transition SIDs, e.g. devpts/tmpfs */
+    pub const SECURITY_FS_USE_TASK: u32 = 3; /* use task SIDs, e.g. pipefs/sockfs */
+    pub const SECURITY_FS_USE_GENFS: u32 = 4; /* use the genfs support */
+    pub const SECURITY_FS_USE_NONE: u32 = 5; /* no labeling support */
+    pub const SECURITY_FS_USE_MNTPOINT: u32 = 6; /* use mountpoint labeling */
+}
+
+/// The context assigned to one of the kernel's initial SIDs.
+#[derive(Debug)]
+pub struct InitialSid {
+    sid: u32,
+    context: SecurityContext,
+}
+
+impl InitialSid {
+    pub(crate) fn new(sid: u32, context: SecurityContext) -> Self {
+        InitialSid { sid, context }
+    }
+
+    pub fn sid(&self) -> u32 {
+        self.sid
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for InitialSid {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let sid = reader.read_u32()?;
+        let context = reader.read_object()?;
+
+        Ok(InitialSid { sid, context })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.sid)?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A `fscon` statement, labelling a filesystem that doesn't support `fs_use`.
+#[derive(Debug)]
+pub struct FileSystemContext {
+    name: String,
+    context: SecurityContext,
+    file_context: SecurityContext,
+}
+
+impl FileSystemContext {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+
+    pub fn file_context(&self) -> &SecurityContext {
+        &self.file_context
+    }
+}
+
+impl PolicyObject for FileSystemContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read_string(name_len)?;
+        let context = reader.read_object()?;
+        let file_context = reader.read_object()?;
+
+        Ok(FileSystemContext {
+            name,
+            context,
+            file_context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_string(&self.name)?;
+        writer.write_object(&self.context)?;
+        writer.write_object(&self.file_context)
+    }
+}
+
+/// A `portcon` statement, labelling a range of ports for a given IP protocol.
+#[derive(Debug)]
+pub struct PortContext {
+    protocol: u32,
+    low_port: u16,
+    high_port: u16,
+    context: SecurityContext,
+}
+
+impl PortContext {
+    /// The IP protocol number, e.g. `6` for TCP or `17` for UDP.
+    pub fn protocol(&self) -> u32 {
+        self.protocol
+    }
+
+    pub fn low_port(&self) -> u16 {
+        self.low_port
+    }
+
+    pub fn high_port(&self) -> u16 {
+        self.high_port
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for PortContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let protocol = reader.read_u32()?;
+        let low_port = reader.read_u32()? as u16;
+        let high_port = reader.read_u32()? as u16;
+        let context = reader.read_object()?;
+
+        Ok(PortContext {
+            protocol,
+            low_port,
+            high_port,
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.protocol)?;
+        writer.write_u32(u32::from(self.low_port))?;
+        writer.write_u32(u32::from(self.high_port))?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A `netifcon` statement, labelling a network interface and the packets received on it.
+#[derive(Debug)]
+pub struct NetworkInterfaceContext {
+    name: String,
+    interface_context: SecurityContext,
+    packet_context: SecurityContext,
+}
+
+impl NetworkInterfaceContext {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn interface_context(&self) -> &SecurityContext {
+        &self.interface_context
+    }
+
+    pub fn packet_context(&self) -> &SecurityContext {
+        &self.packet_context
+    }
+}
+
+impl PolicyObject for NetworkInterfaceContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read_string(name_len)?;
+        let interface_context = reader.read_object()?;
+        let packet_context = reader.read_object()?;
+
+        Ok(NetworkInterfaceContext {
+            name,
+            interface_context,
+            packet_context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_string(&self.name)?;
+        writer.write_object(&self.interface_context)?;
+        writer.write_object(&self.packet_context)
+    }
+}
+
+/// A `nodecon` statement for an IPv4 address and netmask.
+#[derive(Debug)]
+pub struct NodeContext {
+    address: Ipv4Addr,
+    mask: Ipv4Addr,
+    context: SecurityContext,
+}
+
+impl NodeContext {
+    pub fn address(&self) -> &Ipv4Addr {
+        &self.address
+    }
+
+    pub fn mask(&self) -> &Ipv4Addr {
+        &self.mask
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for NodeContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        // Addresses are stored in network byte order.
+        let mut address = [0u8; 4];
+        let mut mask = [0u8; 4];
+
+        reader.read_bytes(&mut address)?;
+        reader.read_bytes(&mut mask)?;
+
+        let context = reader.read_object()?;
+
+        Ok(NodeContext {
+            address: Ipv4Addr::from(address),
+            mask: Ipv4Addr::from(mask),
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_bytes(&self.address.octets())?;
+        writer.write_bytes(&self.mask.octets())?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A `nodecon` statement for an IPv6 address and netmask.
+#[derive(Debug)]
+pub struct Node6Context {
+    address: Ipv6Addr,
+    mask: Ipv6Addr,
+    context: SecurityContext,
+}
+
+impl Node6Context {
+    pub fn address(&self) -> &Ipv6Addr {
+        &self.address
+    }
+
+    pub fn mask(&self) -> &Ipv6Addr {
+        &self.mask
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for Node6Context {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let mut address = [0u8; 16];
+        let mut mask = [0u8; 16];
+
+        reader.read_bytes(&mut address)?;
+        reader.read_bytes(&mut mask)?;
+
+        let context = reader.read_object()?;
+
+        Ok(Node6Context {
+            address: Ipv6Addr::from(address),
+            mask: Ipv6Addr::from(mask),
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_bytes(&self.address.octets())?;
+        writer.write_bytes(&self.mask.octets())?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// The labelling behaviour selected by an `fs_use_*` statement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsUseBehavior {
+    Xattr,
+    Trans,
+    Task,
+    Genfs,
+    None,
+    Mountpoint,
+}
+
+impl FsUseBehavior {
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            constants::SECURITY_FS_USE_XATTR => Some(FsUseBehavior::Xattr),
+            constants::SECURITY_FS_USE_TRANS => Some(FsUseBehavior::Trans),
+            constants::SECURITY_FS_USE_TASK => Some(FsUseBehavior::Task),
+            constants::SECURITY_FS_USE_GENFS => Some(FsUseBehavior::Genfs),
+            constants::SECURITY_FS_USE_NONE => Some(FsUseBehavior::None),
+            constants::SECURITY_FS_USE_MNTPOINT => Some(FsUseBehavior::Mountpoint),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            FsUseBehavior::Xattr => constants::SECURITY_FS_USE_XATTR,
+            FsUseBehavior::Trans => constants::SECURITY_FS_USE_TRANS,
+            FsUseBehavior::Task => constants::SECURITY_FS_USE_TASK,
+            FsUseBehavior::Genfs => constants::SECURITY_FS_USE_GENFS,
+            FsUseBehavior::None => constants::SECURITY_FS_USE_NONE,
+            FsUseBehavior::Mountpoint => constants::SECURITY_FS_USE_MNTPOINT,
+        }
+    }
+}
+
+/// An `fs_use_xattr`, `fs_use_task` or `fs_use_trans` statement.
+#[derive(Debug)]
+pub struct FsUseContext {
+    behavior: FsUseBehavior,
+    fs_type: String,
+    context: SecurityContext,
+}
+
+impl FsUseContext {
+    pub fn behavior(&self) -> FsUseBehavior {
+        self.behavior
+    }
+
+    pub fn fs_type(&self) -> &str {
+        self.fs_type.as_str()
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for FsUseContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let behavior_id = reader.read_u32()?;
+        let behavior = FsUseBehavior::from_id(behavior_id)
+            .ok_or(PolicyReadError::InvalidFsUseBehavior(behavior_id))?;
+        let name_len = reader.read_u32()? as usize;
+        let fs_type = reader.read_string(name_len)?;
+        let context = reader.read_object()?;
+
+        Ok(FsUseContext {
+            behavior,
+            fs_type,
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.behavior.id())?;
+        writer.write_len(self.fs_type.len())?;
+        writer.write_string(&self.fs_type)?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// An `ibpkeycon` statement, labelling a range of Infiniband partition keys on a subnet.
+#[derive(Debug)]
+pub struct IbPkeyContext {
+    subnet_prefix: u64,
+    low_pkey: u16,
+    high_pkey: u16,
+    context: SecurityContext,
+}
+
+impl IbPkeyContext {
+    pub fn subnet_prefix(&self) -> u64 {
+        self.subnet_prefix
+    }
+
+    pub fn low_pkey(&self) -> u16 {
+        self.low_pkey
+    }
+
+    pub fn high_pkey(&self) -> u16 {
+        self.high_pkey
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for IbPkeyContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        // The subnet prefix is stored in network byte order.
+        let mut subnet_prefix = [0u8; 8];
+        reader.read_bytes(&mut subnet_prefix)?;
+
+        let low_pkey = reader.read_u32()? as u16;
+        let high_pkey = reader.read_u32()? as u16;
+        let context = reader.read_object()?;
+
+        Ok(IbPkeyContext {
+            subnet_prefix: u64::from_be_bytes(subnet_prefix),
+            low_pkey,
+            high_pkey,
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_bytes(&self.subnet_prefix.to_be_bytes())?;
+        writer.write_u32(u32::from(self.low_pkey))?;
+        writer.write_u32(u32::from(self.high_pkey))?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// An `ibendportcon` statement, labelling a port on an Infiniband device.
+#[derive(Debug)]
+pub struct IbEndPortContext {
+    device_name: String,
+    port: u8,
+    context: SecurityContext,
+}
+
+impl IbEndPortContext {
+    pub fn device_name(&self) -> &str {
+        self.device_name.as_str()
+    }
+
+    pub fn port(&self) -> u8 {
+        self.port
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for IbEndPortContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let name_len = reader.read_u32()? as usize;
+        let port = reader.read_u32()? as u8;
+        let device_name = reader.read_string(name_len)?;
+        let context = reader.read_object()?;
+
+        Ok(IbEndPortContext {
+            device_name,
+            port,
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.device_name.len())?;
+        writer.write_u32(u32::from(self.port))?;
+        writer.write_string(&self.device_name)?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A Xen `pirqcon` statement, labelling a physical IRQ.
+#[derive(Debug)]
+pub struct PirqContext {
+    pirq: u32,
+    context: SecurityContext,
+}
+
+impl PirqContext {
+    pub fn pirq(&self) -> u32 {
+        self.pirq
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for PirqContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let pirq = reader.read_u32()?;
+        let context = reader.read_object()?;
+
+        Ok(PirqContext { pirq, context })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.pirq)?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A Xen `ioportcon` statement, labelling an inclusive range of I/O ports.
+#[derive(Debug)]
+pub struct IoPortContext {
+    ports: RangeInclusive<u32>,
+    context: SecurityContext,
+}
+
+impl IoPortContext {
+    pub fn ports(&self) -> &RangeInclusive<u32> {
+        &self.ports
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for IoPortContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let low_port = reader.read_u32()?;
+        let high_port = reader.read_u32()?;
+        let context = reader.read_object()?;
+
+        Ok(IoPortContext {
+            ports: low_port..=high_port,
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(*self.ports.start())?;
+        writer.write_u32(*self.ports.end())?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A Xen `iomemcon` statement, labelling an inclusive range of I/O memory page frames.
+#[derive(Debug)]
+pub struct IoMemContext {
+    addresses: RangeInclusive<u64>,
+    context: SecurityContext,
+}
+
+impl IoMemContext {
+    pub fn addresses(&self) -> &RangeInclusive<u64> {
+        &self.addresses
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for IoMemContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        // Addresses were widened to 64 bits alongside the introduction of device tree labelling.
+        let (low_address, high_address) = if reader.profile().supports(Feature::XenDevicetree) {
+            (reader.read_u64()?, reader.read_u64()?)
+        } else {
+            (reader.read_u32()? as u64, reader.read_u32()? as u64)
+        };
+
+        let context = reader.read_object()?;
+
+        Ok(IoMemContext {
+            addresses: low_address..=high_address,
+            context,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        if writer.profile().supports(Feature::XenDevicetree) {
+            writer.write_u64(*self.addresses.start())?;
+            writer.write_u64(*self.addresses.end())?;
+        } else {
+            writer.write_u32(*self.addresses.start() as u32)?;
+            writer.write_u32(*self.addresses.end() as u32)?;
+        }
+
+        writer.write_object(&self.context)
+    }
+}
+
+/// A Xen `pcidevicecon` statement, labelling a PCI device by its bus/device/function number.
+#[derive(Debug)]
+pub struct PciDeviceContext {
+    device: u32,
+    context: SecurityContext,
+}
+
+impl PciDeviceContext {
+    pub fn device(&self) -> u32 {
+        self.device
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for PciDeviceContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let device = reader.read_u32()?;
+        let context = reader.read_object()?;
+
+        Ok(PciDeviceContext { device, context })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.device)?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A Xen `devicetreecon` statement, labelling a device tree node by its path.
+#[derive(Debug)]
+pub struct DeviceTreeContext {
+    path: String,
+    context: SecurityContext,
+}
+
+impl DeviceTreeContext {
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+impl PolicyObject for DeviceTreeContext {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let path_len = reader.read_u32()? as usize;
+        let path = reader.read_string(path_len)?;
+        let context = reader.read_object()?;
+
+        Ok(DeviceTreeContext { path, context })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.path.len())?;
+        writer.write_string(&self.path)?;
+        writer.write_object(&self.context)
+    }
+}
+
+/// A `genfscon` statement, labelling a path on a filesystem that uses genfs labelling.
+#[derive(Debug)]
+pub struct GenfsContext {
+    fs_type: String,
+    path: String,
+    class: u32,
+    context: SecurityContext,
+}
+
+impl GenfsContext {
+    pub fn fs_type(&self) -> &str {
+        self.fs_type.as_str()
+    }
+
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// The class this entry is restricted to, or `None` if it applies to all file classes.
+    pub fn class<'a>(&self, policy: &'a Policy) -> Option<&'a Class> {
+        policy.classes().get(self.class)
+    }
+
+    pub fn class_id(&self) -> u32 {
+        self.class
+    }
+
+    pub fn context(&self) -> &SecurityContext {
+        &self.context
+    }
+}
+
+/// The object context tables of a policy, labelling objects that aren't labelled by a type
+/// transition or by the object's parent.
+#[derive(Debug, Default)]
+pub struct ObjectContexts {
+    initial_sids: Vec<InitialSid>,
+    filesystems: Vec<FileSystemContext>,
+    ports: Vec<PortContext>,
+    network_interfaces: Vec<NetworkInterfaceContext>,
+    nodes: Vec<NodeContext>,
+    fs_uses: Vec<FsUseContext>,
+    nodes6: Vec<Node6Context>,
+    ib_pkeys: Vec<IbPkeyContext>,
+    ib_end_ports: Vec<IbEndPortContext>,
+    pirqs: Vec<PirqContext>,
+    io_ports: Vec<IoPortContext>,
+    io_mems: Vec<IoMemContext>,
+    pci_devices: Vec<PciDeviceContext>,
+    device_trees: Vec<DeviceTreeContext>,
+    genfs: Vec<GenfsContext>,
+}
+
+impl ObjectContexts {
+    pub fn initial_sids(&self) -> &[InitialSid] {
+        &self.initial_sids
+    }
+
+    pub(crate) fn initial_sids_mut(&mut self) -> &mut Vec<InitialSid> {
+        &mut self.initial_sids
+    }
+
+    pub fn filesystems(&self) -> &[FileSystemContext] {
+        &self.filesystems
+    }
+
+    pub fn ports(&self) -> &[PortContext] {
+        &self.ports
+    }
+
+    pub fn network_interfaces(&self) -> &[NetworkInterfaceContext] {
+        &self.network_interfaces
+    }
+
+    pub fn nodes(&self) -> &[NodeContext] {
+        &self.nodes
+    }
+
+    pub fn fs_uses(&self) -> &[FsUseContext] {
+        &self.fs_uses
+    }
+
+    pub fn nodes6(&self) -> &[Node6Context] {
+        &self.nodes6
+    }
+
+    pub fn ib_pkeys(&self) -> &[IbPkeyContext] {
+        &self.ib_pkeys
+    }
+
+    pub fn ib_end_ports(&self) -> &[IbEndPortContext] {
+        &self.ib_end_ports
+    }
+
+    pub fn pirqs(&self) -> &[PirqContext] {
+        &self.pirqs
+    }
+
+    pub fn io_ports(&self) -> &[IoPortContext] {
+        &self.io_ports
+    }
+
+    pub fn io_mems(&self) -> &[IoMemContext] {
+        &self.io_mems
+    }
+
+    pub fn pci_devices(&self) -> &[PciDeviceContext] {
+        &self.pci_devices
+    }
+
+    pub fn device_trees(&self) -> &[DeviceTreeContext] {
+        &self.device_trees
+    }
+
+    pub fn genfs(&self) -> &[GenfsContext] {
+        &self.genfs
+    }
+
+    /// Removes the tables that the given policy version doesn't have, returning the feature
+    /// each needed and how many entries were removed.
+    pub(crate) fn discard_unsupported(
+        &mut self,
+        profile: &CompatibilityProfile,
+    ) -> Vec<(Feature, usize)> {
+        fn discard<T>(table: &mut Vec<T>, feature: Feature, discarded: &mut Vec<(Feature, usize)>) {
+            if !table.is_empty() {
+                discarded.push((feature, table.len()));
+                table.clear();
+            }
+        }
+
+        let mut discarded = vec![];
+
+        if !profile.supports(Feature::Ipv6) {
+            discard(&mut self.nodes6, Feature::Ipv6, &mut discarded);
+        }
+
+        if !profile.supports(Feature::Infiniband) {
+            let num_entries = self.ib_pkeys.len() + self.ib_end_ports.len();
+
+            if num_entries > 0 {
+                discarded.push((Feature::Infiniband, num_entries));
+            }
+
+            self.ib_pkeys.clear();
+            self.ib_end_ports.clear();
+        }
+
+        if !profile.supports(Feature::XenDevicetree) {
+            discard(
+                &mut self.device_trees,
+                Feature::XenDevicetree,
+                &mut discarded,
+            );
+        }
+
+        discarded
+    }
+
+    /// Checks if any I/O memory range needs the 64-bit addresses added alongside device tree
+    /// labelling.
+    pub(crate) fn has_wide_io_mems(&self) -> bool {
+        let max = u64::from(u32::max_value());
+
+        self.io_mems
+            .iter()
+            .any(|io_mem| *io_mem.addresses.end() > max)
+    }
+
+    /// Decodes `num_tables` object context tables followed by the genfs table.
+    pub fn decode_tables<R: Read>(
+        reader: &mut PolicyReader<R>,
+        num_tables: u32,
+    ) -> Result<Self, PolicyReadError> {
+        let platform = reader.profile().platform();
+        let mut ocontexts = ObjectContexts::default();
+
+        for table in 0..num_tables {
+            match platform {
+                PolicyTargetPlatform::SELinux => ocontexts.decode_selinux_table(reader, table)?,
+                PolicyTargetPlatform::Xen => ocontexts.decode_xen_table(reader, table)?,
+            }
+        }
+
+        let num_fs_types = reader.read_u32()? as usize;
+
+        for _ in 0..num_fs_types {
+            let fs_type_len = reader.read_u32()? as usize;
+            let fs_type = reader.read_string(fs_type_len)?;
+            let num_entries = reader.read_u32()? as usize;
+
+            for _ in 0..num_entries {
+                let path_len = reader.read_u32()? as usize;
+                let path = reader.read_string(path_len)?;
+                let class = reader.read_u32()?;
+                let context = reader.read_object()?;
+
+                ocontexts.genfs.push(GenfsContext {
+                    fs_type: fs_type.clone(),
+                    path,
+                    class,
+                    context,
+                });
+            }
+        }
+
+        Ok(ocontexts)
+    }
+
+    /// Encodes `num_tables` object context tables followed by the genfs table, the inverse of
+    /// `decode_tables`.
+    pub fn encode_tables<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+        num_tables: u32,
+    ) -> Result<(), PolicyWriteError> {
+        let platform = writer.profile().platform();
+
+        for table in 0..num_tables {
+            match platform {
+                PolicyTargetPlatform::SELinux => self.encode_selinux_table(writer, table)?,
+                PolicyTargetPlatform::Xen => self.encode_xen_table(writer, table)?,
+            }
+        }
+
+        let fs_types = group_consecutive(&self.genfs, |a, b| a.fs_type == b.fs_type);
+
+        writer.write_len(fs_types.len())?;
+
+        for entries in fs_types {
+            let fs_type = &entries[0].fs_type;
+
+            writer.write_len(fs_type.len())?;
+            writer.write_string(fs_type)?;
+            writer.write_len(entries.len())?;
+
+            for entry in entries {
+                writer.write_len(entry.path.len())?;
+                writer.write_string(&entry.path)?;
+                writer.write_u32(entry.class)?;
+                writer.write_object(&entry.context)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_selinux_table<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+        table: u32,
+    ) -> Result<(), PolicyWriteError> {
+        match table {
+            constants::OCON_ISID => encode_table(writer, &self.initial_sids),
+            constants::OCON_FS => encode_table(writer, &self.filesystems),
+            constants::OCON_PORT => encode_table(writer, &self.ports),
+            constants::OCON_NETIF => encode_table(writer, &self.network_interfaces),
+            constants::OCON_NODE => encode_table(writer, &self.nodes),
+            constants::OCON_FSUSE => encode_table(writer, &self.fs_uses),
+            constants::OCON_NODE6 => encode_table(writer, &self.nodes6),
+            constants::OCON_IBPKEY => encode_table(writer, &self.ib_pkeys),
+            constants::OCON_IBENDPORT => encode_table(writer, &self.ib_end_ports),
+            _ => Err(PolicyWriteError::InvalidVersion(writer.profile().version())),
+        }
+    }
+
+    fn encode_xen_table<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+        table: u32,
+    ) -> Result<(), PolicyWriteError> {
+        match table {
+            constants::OCON_XEN_ISID => encode_table(writer, &self.initial_sids),
+            constants::OCON_XEN_PIRQ => encode_table(writer, &self.pirqs),
+            constants::OCON_XEN_IOPORT => encode_table(writer, &self.io_ports),
+            constants::OCON_XEN_IOMEM => encode_table(writer, &self.io_mems),
+            constants::OCON_XEN_PCIDEVICE => encode_table(writer, &self.pci_devices),
+            constants::OCON_XEN_DEVICETREE => encode_table(writer, &self.device_trees),
+            _ => Err(PolicyWriteError::InvalidVersion(writer.profile().version())),
+        }
+    }
+
+    fn decode_selinux_table<R: Read>(
+        &mut self,
+        reader: &mut PolicyReader<R>,
+        table: u32,
+    ) -> Result<(), PolicyReadError> {
+        let required_feature = match table {
+            constants::OCON_NODE6 => Some(Feature::Ipv6),
+            constants::OCON_IBPKEY | constants::OCON_IBENDPORT => Some(Feature::Infiniband),
+            _ => None,
+        };
+
+        if let Some(feature) = required_feature {
+            if !reader.profile().supports(feature) {
+                return Err(PolicyReadError::InvalidObjectContextTable(table));
+            }
+        }
+
+        let num_entries = reader.read_u32()? as usize;
+
+        match table {
+            constants::OCON_ISID => self.initial_sids = reader.read_objects(num_entries)?,
+            constants::OCON_FS => self.filesystems = reader.read_objects(num_entries)?,
+            constants::OCON_PORT => self.ports = reader.read_objects(num_entries)?,
+            constants::OCON_NETIF => self.network_interfaces = reader.read_objects(num_entries)?,
+            constants::OCON_NODE => self.nodes = reader.read_objects(num_entries)?,
+            constants::OCON_FSUSE => self.fs_uses = reader.read_objects(num_entries)?,
+            constants::OCON_NODE6 => self.nodes6 = reader.read_objects(num_entries)?,
+            constants::OCON_IBPKEY => self.ib_pkeys = reader.read_objects(num_entries)?,
+            constants::OCON_IBENDPORT => self.ib_end_ports = reader.read_objects(num_entries)?,
+            _ => return Err(PolicyReadError::InvalidObjectContextTable(table)),
+        }
+
+        Ok(())
+    }
+
+    fn decode_xen_table<R: Read>(
+        &mut self,
+        reader: &mut PolicyReader<R>,
+        table: u32,
+    ) -> Result<(), PolicyReadError> {
+        if table == constants::OCON_XEN_DEVICETREE
+            && !reader.profile().supports(Feature::XenDevicetree)
+        {
+            return Err(PolicyReadError::InvalidObjectContextTable(table));
+        }
+
+        let num_entries = reader.read_u32()? as usize;
+
+        match table {
+            constants::OCON_XEN_ISID => self.initial_sids = reader.read_objects(num_entries)?,
+            constants::OCON_XEN_PIRQ => self.pirqs = reader.read_objects(num_entries)?,
+            constants::OCON_XEN_IOPORT => self.io_ports = reader.read_objects(num_entries)?,
+            constants::OCON_XEN_IOMEM => self.io_mems = reader.read_objects(num_entries)?,
+            constants::OCON_XEN_PCIDEVICE => self.pci_devices = reader.read_objects(num_entries)?,
+            constants::OCON_XEN_DEVICETREE => {
+                self.device_trees = reader.read_objects(num_entries)?
+            }
+            _ => return Err(PolicyReadError::InvalidObjectContextTable(table)),
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_table<W: Write, O: PolicyObject>(
+    writer: &mut PolicyWriter<W>,
+    table: &[O],
+) -> Result<(), PolicyWriteError> {
+    writer.write_len(table.len())?;
+    writer.write_objects(table)
+}
@@ -0,0 +1,315 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use policydb::constants::{SELINUX_MAGIC_NUMBER, SELINUX_MOD_MAGIC_NUMBER};
+use policydb::Policy;
+use policydb::PolicyReadError;
+use policydb::PolicyReader;
+use std::io::{self, Read, Write};
+use std::str;
+
+pub(crate) mod constants {
+    pub const SEPOL_MODULE_PACKAGE_MAGIC: u32 = 0xf97cff8f;
+    pub const SEPOL_MODULE_PACKAGE_VERSION: u32 = 1;
+
+    pub const SEPOL_PACKAGE_SECTION_FC: u32 = 0xf97cff90;
+    pub const SEPOL_PACKAGE_SECTION_SEUSER: u32 = 0x97cff91;
+    pub const SEPOL_PACKAGE_SECTION_USER_EXTRA: u32 = 0x97cff92;
+    pub const SEPOL_PACKAGE_SECTION_NETFILTER: u32 = 0x97cff93;
+}
+
+use self::constants::*;
+
+/// A policy package (`.pp`), bundling a modular policy with the file contexts, seusers, user
+/// prefixes and netfilter contexts that ship alongside it.
+#[derive(Debug)]
+pub struct PolicyPackage {
+    policy: Policy,
+    policy_data: Vec<u8>,
+    file_contexts: Option<Vec<u8>>,
+    seusers: Option<Vec<u8>>,
+    user_extra: Option<Vec<u8>>,
+    netfilter_contexts: Option<Vec<u8>>,
+}
+
+impl PolicyPackage {
+    /// Creates a package with no extra sections from the binary representation of a modular
+    /// policy.
+    pub fn new(policy_data: Vec<u8>) -> Result<Self, PolicyReadError> {
+        let policy = PolicyReader::new(policy_data.as_slice()).read_policy()?;
+
+        if policy.ty().is_kernel_policy() {
+            return Err(PolicyReadError::InvalidMagicCode(SELINUX_MAGIC_NUMBER));
+        }
+
+        Ok(PolicyPackage {
+            policy,
+            policy_data,
+            file_contexts: None,
+            seusers: None,
+            user_extra: None,
+            netfilter_contexts: None,
+        })
+    }
+
+    /// Reads a package, decoding the policydb section with a `PolicyReader`.
+    pub fn read<R: Read>(mut input: R) -> Result<Self, PolicyReadError> {
+        let mut data = vec![];
+        input.read_to_end(&mut data)?;
+
+        let mut header = data.as_slice();
+        let magic = header.read_u32::<LittleEndian>()?;
+
+        if magic != SEPOL_MODULE_PACKAGE_MAGIC {
+            return Err(PolicyReadError::InvalidMagicCode(magic));
+        }
+
+        let version = header.read_u32::<LittleEndian>()?;
+
+        if version != SEPOL_MODULE_PACKAGE_VERSION {
+            return Err(PolicyReadError::InvalidPackageVersion(version));
+        }
+
+        let num_sections = header.read_u32::<LittleEndian>()? as usize;
+        let mut offsets = Vec::with_capacity(num_sections + 1);
+
+        for _ in 0..num_sections {
+            offsets.push(header.read_u32::<LittleEndian>()? as usize);
+        }
+
+        offsets.push(data.len());
+
+        let header_len = data.len() - header.len();
+        let valid_offsets = num_sections > 0
+            && offsets[0] >= header_len
+            && offsets.windows(2).all(|pair| pair[0] + 4 <= pair[1]);
+
+        if !valid_offsets {
+            return Err(PolicyReadError::InvalidPackageOffsets);
+        }
+
+        let mut policy_data = None;
+        let mut file_contexts = None;
+        let mut seusers = None;
+        let mut user_extra = None;
+        let mut netfilter_contexts = None;
+
+        for pair in offsets.windows(2) {
+            let section = &data[pair[0]..pair[1]];
+            let magic = (&section[..4]).read_u32::<LittleEndian>()?;
+            let contents = section[4..].to_vec();
+
+            // The policydb section is the only one whose magic number is part of its contents.
+            let slot = match magic {
+                SELINUX_MOD_MAGIC_NUMBER => {
+                    policy_data = Some(section.to_vec());
+                    continue;
+                }
+                SEPOL_PACKAGE_SECTION_FC => &mut file_contexts,
+                SEPOL_PACKAGE_SECTION_SEUSER => &mut seusers,
+                SEPOL_PACKAGE_SECTION_USER_EXTRA => &mut user_extra,
+                SEPOL_PACKAGE_SECTION_NETFILTER => &mut netfilter_contexts,
+                _ => return Err(PolicyReadError::InvalidPackageSection(magic)),
+            };
+
+            if slot.is_some() {
+                return Err(PolicyReadError::InvalidPackageSection(magic));
+            }
+
+            *slot = Some(contents);
+        }
+
+        let policy_data = policy_data.ok_or(PolicyReadError::InvalidPackageSection(
+            SELINUX_MOD_MAGIC_NUMBER,
+        ))?;
+
+        Ok(PolicyPackage {
+            file_contexts,
+            seusers,
+            user_extra,
+            netfilter_contexts,
+            ..PolicyPackage::new(policy_data)?
+        })
+    }
+
+    /// Writes this package out, with the policydb section first followed by any extra sections.
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut sections = vec![(None, self.policy_data.as_slice())];
+        let extra_sections = [
+            (SEPOL_PACKAGE_SECTION_FC, &self.file_contexts),
+            (SEPOL_PACKAGE_SECTION_SEUSER, &self.seusers),
+            (SEPOL_PACKAGE_SECTION_USER_EXTRA, &self.user_extra),
+            (SEPOL_PACKAGE_SECTION_NETFILTER, &self.netfilter_contexts),
+        ];
+
+        for &(magic, data) in extra_sections.iter() {
+            if let Some(ref data) = *data {
+                sections.push((Some(magic), data.as_slice()));
+            }
+        }
+
+        out.write_u32::<LittleEndian>(SEPOL_MODULE_PACKAGE_MAGIC)?;
+        out.write_u32::<LittleEndian>(SEPOL_MODULE_PACKAGE_VERSION)?;
+        out.write_u32::<LittleEndian>(sections.len() as u32)?;
+
+        let mut offset = 12 + 4 * sections.len();
+
+        for &(magic, data) in &sections {
+            out.write_u32::<LittleEndian>(offset as u32)?;
+            offset += data.len() + magic.map_or(0, |_| 4);
+        }
+
+        for &(magic, data) in &sections {
+            if let Some(magic) = magic {
+                out.write_u32::<LittleEndian>(magic)?;
+            }
+
+            out.write_all(data)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// The binary representation of the packaged policy.
+    pub fn policy_data(&self) -> &[u8] {
+        &self.policy_data
+    }
+
+    pub fn file_contexts(&self) -> Option<&[u8]> {
+        self.file_contexts.as_ref().map(Vec::as_slice)
+    }
+
+    pub fn set_file_contexts(&mut self, data: Option<Vec<u8>>) {
+        self.file_contexts = data;
+    }
+
+    /// The file context specifications in this package, skipping blank lines and comments.
+    pub fn file_context_specs(&self) -> Result<Vec<FileContextSpec>, str::Utf8Error> {
+        Ok(config_lines(&self.file_contexts)?
+            .filter_map(FileContextSpec::parse)
+            .collect())
+    }
+
+    pub fn seusers(&self) -> Option<&[u8]> {
+        self.seusers.as_ref().map(Vec::as_slice)
+    }
+
+    pub fn set_seusers(&mut self, data: Option<Vec<u8>>) {
+        self.seusers = data;
+    }
+
+    /// The login to SELinux user mappings in this package, skipping blank lines and comments.
+    pub fn seuser_mappings(&self) -> Result<Vec<SeUserMapping>, str::Utf8Error> {
+        Ok(config_lines(&self.seusers)?
+            .filter_map(SeUserMapping::parse)
+            .collect())
+    }
+
+    pub fn user_extra(&self) -> Option<&[u8]> {
+        self.user_extra.as_ref().map(Vec::as_slice)
+    }
+
+    pub fn set_user_extra(&mut self, data: Option<Vec<u8>>) {
+        self.user_extra = data;
+    }
+
+    pub fn netfilter_contexts(&self) -> Option<&[u8]> {
+        self.netfilter_contexts.as_ref().map(Vec::as_slice)
+    }
+
+    pub fn set_netfilter_contexts(&mut self, data: Option<Vec<u8>>) {
+        self.netfilter_contexts = data;
+    }
+}
+
+fn config_lines<'a>(
+    data: &'a Option<Vec<u8>>,
+) -> Result<impl Iterator<Item = &'a str> + 'a, str::Utf8Error> {
+    let text = match *data {
+        Some(ref data) => str::from_utf8(data)?,
+        None => "",
+    };
+
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#')))
+}
+
+/// A line from a `file_contexts` section, e.g. `/usr/bin/foo -- system_u:object_r:foo_exec_t:s0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileContextSpec {
+    path: String,
+    file_type: Option<String>,
+    context: String,
+}
+
+impl FileContextSpec {
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (path, file_type, context) = match fields.len() {
+            2 => (fields[0], None, fields[1]),
+            3 => (fields[0], Some(fields[1].to_string()), fields[2]),
+            _ => return None,
+        };
+
+        Some(FileContextSpec {
+            path: path.to_string(),
+            file_type,
+            context: context.to_string(),
+        })
+    }
+
+    /// The regular expression matched against file paths.
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// The file type flag (`--`, `-d`, ...), if the specification is restricted to one.
+    pub fn file_type(&self) -> Option<&str> {
+        self.file_type.as_ref().map(String::as_str)
+    }
+
+    /// The security context, or `<<none>>` for paths that should not be labeled.
+    pub fn context(&self) -> &str {
+        self.context.as_str()
+    }
+}
+
+/// A line from a `seusers` section, e.g. `__default__:user_u:s0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeUserMapping {
+    name: String,
+    seuser: String,
+    range: Option<String>,
+}
+
+impl SeUserMapping {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, ':');
+        let name = fields.next()?.to_string();
+        let seuser = fields.next()?.to_string();
+        let range = fields.next().map(str::to_string);
+
+        Some(SeUserMapping {
+            name,
+            seuser,
+            range,
+        })
+    }
+
+    /// The login name, a `%group`, or `__default__`.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn seuser(&self) -> &str {
+        self.seuser.as_str()
+    }
+
+    pub fn range(&self) -> Option<&str> {
+        self.range.as_ref().map(String::as_str)
+    }
+}
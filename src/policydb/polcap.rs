@@ -4,7 +4,9 @@ use policydb::Feature;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
-use std::io::Read;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use std::io::{Read, Write};
 use std::str::FromStr;
 
 macro_rules! polcaps (
@@ -118,4 +120,18 @@ impl PolicyObject for PolicyCapabilitySet {
             Ok(PolicyCapabilitySet::empty())
         }
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        if writer.profile().supports(Feature::PolicyCapabilities) {
+            let bitmap: Bitmap = self.polcaps.iter().map(PolicyCapability::id).collect();
+
+            writer.write_object(&bitmap)
+        } else if !self.polcaps.is_empty() {
+            Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::PolicyCapabilities,
+            ))
+        } else {
+            Ok(())
+        }
+    }
 }
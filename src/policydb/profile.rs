@@ -22,6 +22,12 @@ const POLICYDB_VERSION_CONSTRAINT_NAMES: u32 = 29;
 const POLICYDB_VERSION_XEN_DEVICETREE: u32 = 30;
 const POLICYDB_VERSION_XPERMS_IOCTL: u32 = 30;
 const POLICYDB_VERSION_INFINIBAND: u32 = 31;
+const POLICYDB_VERSION_GLBLUB: u32 = 32;
+const POLICYDB_VERSION_COMP_FTRANS: u32 = 33;
+
+const POLICYDB_VERSION_MIN: u32 = POLICYDB_VERSION_BASE;
+const POLICYDB_VERSION_MAX: u32 = POLICYDB_VERSION_COMP_FTRANS;
+const POLICYDB_XEN_VERSION_MIN: u32 = POLICYDB_VERSION_BOUNDARY;
 
 const MOD_POLICYDB_VERSION_BASE: u32 = 4;
 const MOD_POLICYDB_VERSION_VALIDATETRANS: u32 = 5;
@@ -41,6 +47,11 @@ const MOD_POLICYDB_VERSION_DEFAULT_TYPE: u32 = 16;
 const MOD_POLICYDB_VERSION_CONSTRAINT_NAMES: u32 = 17;
 const MOD_POLICYDB_VERSION_XPERMS_IOCTL: u32 = 18;
 const MOD_POLICYDB_VERSION_INFINIBAND: u32 = 19;
+const MOD_POLICYDB_VERSION_GLBLUB: u32 = 20;
+const MOD_POLICYDB_VERSION_SELF_TYPETRANS: u32 = 21;
+
+const MOD_POLICYDB_VERSION_MIN: u32 = MOD_POLICYDB_VERSION_BASE;
+const MOD_POLICYDB_VERSION_MAX: u32 = MOD_POLICYDB_VERSION_SELF_TYPETRANS;
 
 enum FeatureRequirement {
     Version {
@@ -48,25 +59,72 @@ enum FeatureRequirement {
         module: Option<u32>,
     },
     Platform(PolicyTargetPlatform),
+    /// Only kernel policies and base modules carry this data, non-base modules never do.
+    KernelOrBaseModule,
 }
 
-#[derive(Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub enum Feature {
+    Booleans,
+    Ipv6,
+    NetlinkClasses,
+    Infiniband,
+    XenDevicetree,
+    RoleTransitionClass,
+    RangeTransitionClass,
+    FilenameTransitions,
+    CompressedFilenameTransitions,
+    SelfTypeTransition,
     PermissiveTypes,
     PolicyCapabilities,
     ValidateTransition,
     NewObjectDefaults,
     DefaultType,
+    DefaultRangeGlblub,
+    ConstraintNames,
     Boundary,
     BoundaryAlias,
     Mls,
     MlsUsers,
+    RoleAttributes,
     TunableSep,
     AvTab,
     XpermsIoctl,
 }
 
+const ALL_FEATURES: &[Feature] = &[
+    Feature::Booleans,
+    Feature::Ipv6,
+    Feature::NetlinkClasses,
+    Feature::Infiniband,
+    Feature::XenDevicetree,
+    Feature::RoleTransitionClass,
+    Feature::RangeTransitionClass,
+    Feature::FilenameTransitions,
+    Feature::CompressedFilenameTransitions,
+    Feature::SelfTypeTransition,
+    Feature::PermissiveTypes,
+    Feature::PolicyCapabilities,
+    Feature::ValidateTransition,
+    Feature::NewObjectDefaults,
+    Feature::DefaultType,
+    Feature::DefaultRangeGlblub,
+    Feature::ConstraintNames,
+    Feature::Boundary,
+    Feature::BoundaryAlias,
+    Feature::Mls,
+    Feature::MlsUsers,
+    Feature::RoleAttributes,
+    Feature::TunableSep,
+    Feature::AvTab,
+    Feature::XpermsIoctl,
+];
+
 impl Feature {
+    pub fn all() -> &'static [Feature] {
+        ALL_FEATURES
+    }
+
     fn requirements(&self) -> Vec<FeatureRequirement> {
         fn kernel_only(ver: u32) -> FeatureRequirement {
             FeatureRequirement::Version {
@@ -93,26 +151,78 @@ impl Feature {
             FeatureRequirement::Platform(platform)
         }
 
+        fn kernel_or_base_module() -> FeatureRequirement {
+            FeatureRequirement::KernelOrBaseModule
+        }
+
         match *self {
+            Feature::Booleans => vec![version(POLICYDB_VERSION_BOOL, MOD_POLICYDB_VERSION_BASE)],
+            Feature::Ipv6 => vec![version(POLICYDB_VERSION_IPV6, MOD_POLICYDB_VERSION_BASE)],
+            Feature::NetlinkClasses => {
+                vec![version(POLICYDB_VERSION_NLCLASS, MOD_POLICYDB_VERSION_BASE)]
+            }
+            Feature::Infiniband => vec![version(
+                POLICYDB_VERSION_INFINIBAND,
+                MOD_POLICYDB_VERSION_INFINIBAND,
+            )],
+            Feature::XenDevicetree => vec![
+                kernel_only(POLICYDB_VERSION_XEN_DEVICETREE),
+                platform(PolicyTargetPlatform::Xen),
+            ],
+            Feature::RoleTransitionClass => vec![version(
+                POLICYDB_VERSION_ROLETRANS,
+                MOD_POLICYDB_VERSION_ROLETRANS,
+            )],
+            Feature::RangeTransitionClass => vec![version(
+                POLICYDB_VERSION_RANGETRANS,
+                MOD_POLICYDB_VERSION_RANGETRANS,
+            )],
+            Feature::FilenameTransitions => vec![version(
+                POLICYDB_VERSION_FILENAME_TRANS,
+                MOD_POLICYDB_VERSION_FILENAME_TRANS,
+            )],
+            Feature::CompressedFilenameTransitions => {
+                vec![kernel_only(POLICYDB_VERSION_COMP_FTRANS)]
+            }
             Feature::PolicyCapabilities => vec![version(
                 POLICYDB_VERSION_POLCAP,
                 MOD_POLICYDB_VERSION_POLCAP,
             )],
+            Feature::SelfTypeTransition => vec![module_only(MOD_POLICYDB_VERSION_SELF_TYPETRANS)],
             Feature::PermissiveTypes => vec![version(
                 POLICYDB_VERSION_PERMISSIVE,
                 MOD_POLICYDB_VERSION_PERMISSIVE,
             )],
-            Feature::ValidateTransition => vec![version(
-                POLICYDB_VERSION_VALIDATETRANS,
-                MOD_POLICYDB_VERSION_VALIDATETRANS,
-            )],
-            Feature::DefaultType => vec![version(
-                POLICYDB_VERSION_DEFAULT_TYPE,
-                MOD_POLICYDB_VERSION_DEFAULT_TYPE,
-            )],
-            Feature::NewObjectDefaults => vec![version(
-                POLICYDB_VERSION_NEW_OBJECT_DEFAULTS,
-                MOD_POLICYDB_VERSION_NEW_OBJECT_DEFAULTS,
+            Feature::ValidateTransition => vec![
+                version(
+                    POLICYDB_VERSION_VALIDATETRANS,
+                    MOD_POLICYDB_VERSION_VALIDATETRANS,
+                ),
+                kernel_or_base_module(),
+            ],
+            Feature::DefaultType => vec![
+                version(
+                    POLICYDB_VERSION_DEFAULT_TYPE,
+                    MOD_POLICYDB_VERSION_DEFAULT_TYPE,
+                ),
+                kernel_or_base_module(),
+            ],
+            Feature::NewObjectDefaults => vec![
+                version(
+                    POLICYDB_VERSION_NEW_OBJECT_DEFAULTS,
+                    MOD_POLICYDB_VERSION_NEW_OBJECT_DEFAULTS,
+                ),
+                kernel_or_base_module(),
+            ],
+            Feature::DefaultRangeGlblub => vec![
+                version(POLICYDB_VERSION_GLBLUB, MOD_POLICYDB_VERSION_GLBLUB),
+                kernel_or_base_module(),
+            ],
+            // Modules have always stored constraint names, `MOD_POLICYDB_VERSION_CONSTRAINT_NAMES`
+            // only marks the version where they started being used.
+            Feature::ConstraintNames => vec![version(
+                POLICYDB_VERSION_CONSTRAINT_NAMES,
+                MOD_POLICYDB_VERSION_BASE,
             )],
             Feature::Boundary => vec![version(
                 POLICYDB_VERSION_BOUNDARY,
@@ -121,6 +231,7 @@ impl Feature {
             Feature::BoundaryAlias => vec![module_only(MOD_POLICYDB_VERSION_BOUNDARY_ALIAS)],
             Feature::Mls => vec![version(POLICYDB_VERSION_MLS, MOD_POLICYDB_VERSION_MLS)],
             Feature::MlsUsers => vec![module_only(MOD_POLICYDB_VERSION_MLS_USERS)],
+            Feature::RoleAttributes => vec![module_only(MOD_POLICYDB_VERSION_ROLEATTRIB)],
             Feature::TunableSep => vec![module_only(MOD_POLICYDB_VERSION_TUNABLE_SEP)],
             Feature::AvTab => vec![kernel_only(POLICYDB_VERSION_AVTAB)],
             Feature::XpermsIoctl => vec![version(
@@ -156,14 +267,86 @@ impl CompatibilityProfile {
             {
                 self.version >= *ver
             }
+            FeatureRequirement::Platform(platform) => self.platform() == *platform,
+            FeatureRequirement::KernelOrBaseModule => match self.ty {
+                PolicyType::Module { is_base_module, .. } => is_base_module,
+                PolicyType::Kernel(_) => true,
+            },
             _ => false,
         })
     }
 
+    /// The platform targeted by this policy.  Modules are always built for SELinux.
+    pub fn platform(&self) -> PolicyTargetPlatform {
+        match self.ty {
+            PolicyType::Kernel(platform) => platform,
+            PolicyType::Module { .. } => PolicyTargetPlatform::SELinux,
+        }
+    }
+
+    /// Checks if this policy version is one that we know how to read and write.
+    pub fn is_supported_version(&self) -> bool {
+        let (min, max) = match self.ty {
+            PolicyType::Kernel(PolicyTargetPlatform::SELinux) => {
+                (POLICYDB_VERSION_MIN, POLICYDB_VERSION_MAX)
+            }
+            PolicyType::Kernel(PolicyTargetPlatform::Xen) => {
+                (POLICYDB_XEN_VERSION_MIN, POLICYDB_VERSION_MAX)
+            }
+            PolicyType::Module { .. } => (MOD_POLICYDB_VERSION_MIN, MOD_POLICYDB_VERSION_MAX),
+        };
+
+        self.version >= min && self.version <= max
+    }
+
+    /// The number of symbol tables a policy with this profile contains.  Modules always contain
+    /// every table, including the sensitivities and categories when MLS isn't supported.
+    pub fn num_symbol_tables(&self) -> u32 {
+        if !self.ty.is_kernel_policy() {
+            8
+        } else if !self.supports(Feature::Booleans) {
+            5
+        } else if !self.supports(Feature::Mls) {
+            6
+        } else {
+            8
+        }
+    }
+
+    /// The number of object context tables a policy with this profile contains.
+    pub fn num_ocontext_tables(&self) -> u32 {
+        match self.ty {
+            PolicyType::Module {
+                is_base_module: false,
+                ..
+            } => 0,
+            PolicyType::Kernel(PolicyTargetPlatform::Xen) => {
+                if self.supports(Feature::XenDevicetree) {
+                    6
+                } else {
+                    5
+                }
+            }
+            _ => {
+                if self.supports(Feature::Infiniband) {
+                    9
+                } else if self.supports(Feature::Ipv6) {
+                    7
+                } else {
+                    6
+                }
+            }
+        }
+    }
+
     pub fn ty(&self) -> &PolicyType {
         &self.ty
     }
 
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     pub fn new(ty: PolicyType, version: u32) -> Self {
         CompatibilityProfile { ty, version }
     }
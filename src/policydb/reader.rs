@@ -18,9 +18,28 @@ pub struct PolicyReader<R: Read> {
 #[derive(Debug)]
 pub enum PolicyReadError {
     InvalidAccessVectorSpecifier,
+    InvalidAvRule(u32),
+    InvalidBitmap,
+    InvalidConditionalExpression(u32),
+    InvalidConstraintAttribute(u32),
+    InvalidConstraintExpression(u32),
+    InvalidConstraintOperator(u32),
+    InvalidFilenameTransition,
+    InvalidFsUseBehavior(u32),
     InvalidMagicCode(u32),
+    InvalidObjectContextTable(u32),
+    InvalidPackageOffsets,
+    InvalidPackageSection(u32),
+    InvalidPackageVersion(u32),
     InvalidPolicyCapability,
+    InvalidRoleFlavor(u32),
+    InvalidScope(u32),
+    InvalidTableSizes {
+        symbol_tables: u32,
+        ocontext_tables: u32,
+    },
     InvalidTargetPlatform(String),
+    InvalidTypeFlavor(u32),
     InvalidVersion(u32),
     InputError(IoError),
     UnsupportedFeatureUsed(Feature),
@@ -65,6 +84,10 @@ impl<R: Read> PolicyReader<R> {
         self.buf.read_u64::<LittleEndian>()
     }
 
+    pub fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), IoError> {
+        self.buf.read_exact(buf)
+    }
+
     pub fn read_string(&mut self, len: usize) -> Result<String, IoError> {
         let mut value = String::with_capacity(len);
         let data = &mut self.buf;
@@ -94,11 +117,13 @@ impl<R: Read> PolicyReader<R> {
     where
         S: Symbol,
     {
-        let _num_primary_names = self.read_u32()?;
+        let num_primary_names = self.read_u32()?;
         let num_elements = self.read_u32()?;
-        let profile = self.profile();
+        let mut table: SymbolTable<S> = self.read_bare_symbol_table(num_elements as usize)?;
 
-        self.read_bare_symbol_table(num_elements as usize)
+        table.set_num_primary_names(num_primary_names);
+
+        Ok(table)
     }
 
     pub fn read_object<O: PolicyObject>(&mut self) -> Result<O, PolicyReadError> {
@@ -126,10 +151,11 @@ impl<R: Read> PolicyReader<R> {
 
         // If the policy file is a module there will be an additional entry
         // present indicating if it's the base module.
-        let is_base_module = ty_opcode == SELINUX_MOD_MAGIC_NUMBER && self.read_u32()? == 1;
+        let is_base_module =
+            ty_opcode == SELINUX_MOD_MAGIC_NUMBER && self.read_u32()? == POLICY_BASE;
 
         let version = self.read_u32()?;
-        let config = self.read_u32()?;
+        let config = PolicyConfig::from_bits(self.read_u32()?);
         let num_sym_tables = self.read_u32()?;
         let num_ocon_tables = self.read_u32()?;
 
@@ -139,6 +165,11 @@ impl<R: Read> PolicyReader<R> {
                 PLATFORM_XEN => PolicyTargetPlatform::Xen,
                 _ => return Err(PolicyReadError::InvalidTargetPlatform(platform)),
             }),
+            SELINUX_MOD_MAGIC_NUMBER if is_base_module => PolicyType::Module {
+                is_base_module,
+                name: String::new(),
+                version: String::new(),
+            },
             SELINUX_MOD_MAGIC_NUMBER => {
                 let name_len = self.read_u32()?;
                 let name = self.read_string(name_len as usize)?;
@@ -154,38 +185,154 @@ impl<R: Read> PolicyReader<R> {
             _ => return Err(PolicyReadError::InvalidMagicCode(ty_opcode)),
         };
 
-        self.profile = Some(CompatibilityProfile::new(ty, version));
+        let profile = CompatibilityProfile::new(ty, version);
+
+        if !profile.is_supported_version() {
+            return Err(PolicyReadError::InvalidVersion(version));
+        }
+
+        if profile.num_symbol_tables() != num_sym_tables
+            || profile.num_ocontext_tables() != num_ocon_tables
+        {
+            return Err(PolicyReadError::InvalidTableSizes {
+                symbol_tables: num_sym_tables,
+                ocontext_tables: num_ocon_tables,
+            });
+        }
+
+        self.profile = Some(profile);
 
+        // MLS fields are present in every policy from `POLICYDB_VERSION_MLS` onwards, whether or
+        // not MLS is enabled.  Older policies used an incompatible MLS format, which the kernel
+        // refuses to load.
+        if config.mls_enabled && !self.profile().supports(Feature::Mls) {
+            return Err(PolicyReadError::UnsupportedFeatureUsed(Feature::Mls));
+        }
+
+        let is_kernel_policy = self.profile().ty().is_kernel_policy();
         let polcaps: PolicyCapabilitySet = self.read_object()?;
-        let permissive_type_map: Option<Bitmap> =
-            if self.profile().supports(Feature::PermissiveTypes) {
-                Some(self.read_object()?)
+        let permissive_types: Bitmap =
+            if is_kernel_policy && self.profile().supports(Feature::PermissiveTypes) {
+                self.read_object()?
             } else {
-                None
+                Bitmap::create()
             };
 
         let common_classes: SymbolTable<Common> = self.read_symbol_table()?;
         let classes: SymbolTable<Class> = self.read_symbol_table()?;
         let roles: SymbolTable<Role> = self.read_symbol_table()?;
-        let types: SymbolTable<Type> = self.read_symbol_table()?;
+        let mut types: SymbolTable<Type> = self.read_symbol_table()?;
+
+        // Unlike other type bitmaps, the permissive map is indexed by type id.
+        for id in permissive_types.iter() {
+            if let Some(ty) = types.get_mut(id) {
+                ty.set_permissive(true);
+            }
+        }
+
         let users: SymbolTable<User> = self.read_symbol_table()?;
-        let booleans: SymbolTable<Boolean> = self.read_symbol_table()?;
-        let sensitivities: SymbolTable<Sensitivity> = self.read_symbol_table()?;
-        let categories: SymbolTable<Category> = self.read_symbol_table()?;
-        let avtab: AccessVectorTable = self.read_object()?;
+        let booleans: SymbolTable<Boolean> = if self.profile().supports(Feature::Booleans) {
+            self.read_symbol_table()?
+        } else {
+            SymbolTable::with_capacity(0)
+        };
+
+        let (sensitivities, categories) =
+            if !is_kernel_policy || self.profile().supports(Feature::Mls) {
+                (self.read_symbol_table()?, self.read_symbol_table()?)
+            } else {
+                (SymbolTable::with_capacity(0), SymbolTable::with_capacity(0))
+            };
+
+        let mut avtab = AccessVectorTable::default();
+        let mut conditionals = vec![];
+        let mut role_transitions = vec![];
+        let mut role_allows = vec![];
+        let mut filename_transitions = vec![];
+        let mut module = None;
+
+        if is_kernel_policy {
+            avtab = self.read_object()?;
+            conditionals = if self.profile().supports(Feature::Booleans) {
+                let num_conditionals = self.read_u32()? as usize;
+                self.read_objects(num_conditionals)?
+            } else {
+                vec![]
+            };
+
+            let num_role_transitions = self.read_u32()? as usize;
+            role_transitions = self.read_objects(num_role_transitions)?;
+            let num_role_allows = self.read_u32()? as usize;
+            role_allows = self.read_objects(num_role_allows)?;
+
+            filename_transitions = if self
+                .profile()
+                .supports(Feature::CompressedFilenameTransitions)
+            {
+                let num_entries = self.read_u32()? as usize;
+                let mut transitions = Vec::with_capacity(num_entries);
+
+                for _ in 0..num_entries {
+                    transitions.extend(FilenameTransition::decode_compressed(&mut self)?);
+                }
+
+                transitions
+            } else if self.profile().supports(Feature::FilenameTransitions) {
+                let num_transitions = self.read_u32()? as usize;
+                self.read_objects(num_transitions)?
+            } else {
+                vec![]
+            };
+        } else {
+            module = Some(self.read_object()?);
+        }
+
+        let ocontexts = ObjectContexts::decode_tables(&mut self, num_ocon_tables)?;
+
+        // Base modules kept range transitions in the kernel format until they moved into avrule
+        // declarations with `MOD_POLICYDB_VERSION_RANGETRANS`.
+        let has_range_transitions = match *self.profile().ty() {
+            PolicyType::Kernel(_) => true,
+            PolicyType::Module { is_base_module, .. } => {
+                is_base_module && !self.profile().supports(Feature::RangeTransitionClass)
+            }
+        };
+
+        let range_transitions: Vec<RangeTransition> =
+            if has_range_transitions && self.profile().supports(Feature::Mls) {
+                let num_range_transitions = self.read_u32()? as usize;
+                self.read_objects(num_range_transitions)?
+            } else {
+                vec![]
+            };
+
+        let type_attr_map = if is_kernel_policy {
+            TypeAttributeMap::decode_for_types(&mut self, types.num_primary_names())?
+        } else {
+            TypeAttributeMap::default()
+        };
 
         Ok(Policy {
             version,
+            config,
             polcaps,
             profile: self.profile.expect("uninitialized"),
             avtab,
             booleans,
+            conditionals,
             categories,
             common_classes,
             classes,
+            filename_transitions,
+            ocontexts,
+            module,
+            range_transitions,
             roles,
+            role_transitions,
+            role_allows,
             sensitivities,
             types,
+            type_attr_map,
             users,
         })
     }
@@ -0,0 +1,280 @@
+use policydb::link::{permission_value, required_by};
+use policydb::AvRuleDeclaration;
+use policydb::LinkError;
+use policydb::Policy;
+use policydb::PolicyType;
+use policydb::Requirement;
+use policydb::ScopeKind;
+use policydb::Symbol;
+use policydb::SymbolKind;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A set of symbol and permission names, with types split into plain types and attributes.
+#[derive(Debug, Default)]
+pub struct SymbolNames {
+    symbols: BTreeMap<SymbolKind, BTreeSet<String>>,
+    attributes: BTreeSet<String>,
+    permissions: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl SymbolNames {
+    fn insert(&mut self, policy: &Policy, requirement: Requirement) {
+        match requirement {
+            Requirement::Symbol {
+                kind: SymbolKind::Type,
+                name,
+            } => {
+                let is_attribute = policy
+                    .types()
+                    .get_by_name(&name)
+                    .map_or(false, |ty| ty.is_attribute());
+
+                if is_attribute {
+                    self.attributes.insert(name);
+                } else {
+                    self.symbols
+                        .entry(SymbolKind::Type)
+                        .or_default()
+                        .insert(name);
+                }
+            }
+            Requirement::Symbol { kind, name } => {
+                self.symbols.entry(kind).or_default().insert(name);
+            }
+            Requirement::Permission { class, permission } => {
+                self.permissions
+                    .entry(class)
+                    .or_default()
+                    .insert(permission);
+            }
+        }
+    }
+
+    /// The names of the symbols of the given kind.  Attributes are included with types.
+    pub fn names<'a>(&'a self, kind: SymbolKind) -> impl Iterator<Item = &'a str> + 'a {
+        let attributes = match kind {
+            SymbolKind::Type => Some(self.attributes.iter()),
+            _ => None,
+        };
+
+        self.symbols
+            .get(&kind)
+            .into_iter()
+            .flat_map(|names| names.iter())
+            .chain(attributes.into_iter().flat_map(|names| names))
+            .map(String::as_str)
+    }
+
+    /// The names of the types, excluding attributes.
+    pub fn types<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.symbols
+            .get(&SymbolKind::Type)
+            .into_iter()
+            .flat_map(|names| names.iter())
+            .map(String::as_str)
+    }
+
+    pub fn attributes<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.attributes.iter().map(String::as_str)
+    }
+
+    pub fn roles<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.names(SymbolKind::Role)
+    }
+
+    pub fn users<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.names(SymbolKind::User)
+    }
+
+    pub fn classes<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.names(SymbolKind::Class)
+    }
+
+    pub fn booleans<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.names(SymbolKind::Boolean)
+    }
+
+    /// The classes with permissions in this set, and the names of those permissions.
+    pub fn permissions<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a str, impl Iterator<Item = &'a str> + 'a)> + 'a {
+        self.permissions
+            .iter()
+            .map(|(class, perms)| (class.as_str(), perms.iter().map(String::as_str)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.values().all(|names| names.is_empty())
+            && self.attributes.is_empty()
+            && self.permissions.is_empty()
+    }
+}
+
+/// The symbols a module declares and requires, as recorded in its scope tables and in the
+/// require statements of its avrule blocks.
+///
+/// Requirements of the global block must be met for the module to be linked.  Requirements that
+/// only appear in optional blocks disable those blocks when they aren't met.
+#[derive(Debug)]
+pub struct ModuleRequirements {
+    module: String,
+    declared: SymbolNames,
+    required: SymbolNames,
+    optional_required: SymbolNames,
+    global_requirements: BTreeSet<Requirement>,
+    declared_names: Vec<BTreeSet<String>>,
+}
+
+impl ModuleRequirements {
+    /// Builds the requirements report of a policy module.  The base module may also be given, in
+    /// which case it lists the requirements of the optional blocks of the base.
+    pub fn new(module: &Policy) -> Result<Self, LinkError> {
+        let name = match *module.ty() {
+            PolicyType::Module {
+                is_base_module: true,
+                ..
+            } => "base".to_string(),
+            PolicyType::Module { ref name, .. } => name.clone(),
+            _ => return Err(LinkError::NotModule),
+        };
+
+        let module_data = module.module().ok_or(LinkError::NotModule)?;
+        let mut declared = SymbolNames::default();
+        let mut required = SymbolNames::default();
+        let mut optional_required = SymbolNames::default();
+        let mut global_requirements = BTreeSet::new();
+
+        for block in module_data.blocks() {
+            for decl in block.declarations() {
+                for requirement in declared_by(module, decl) {
+                    declared.insert(module, requirement);
+                }
+
+                for requirement in required_by(module, decl) {
+                    if block.is_optional() {
+                        optional_required.insert(module, requirement);
+                    } else {
+                        global_requirements.insert(requirement.clone());
+                        required.insert(module, requirement);
+                    }
+                }
+            }
+        }
+
+        let declared_names = SymbolKind::all()
+            .iter()
+            .map(|kind| {
+                module_data
+                    .scopes(*kind)
+                    .iter()
+                    .filter(|scope| scope.kind() == ScopeKind::Declared)
+                    .map(|scope| scope.name().to_string())
+                    .collect()
+            })
+            .collect();
+
+        Ok(ModuleRequirements {
+            module: name,
+            declared,
+            required,
+            optional_required,
+            global_requirements,
+            declared_names,
+        })
+    }
+
+    pub fn module(&self) -> &str {
+        self.module.as_str()
+    }
+
+    pub fn declared(&self) -> &SymbolNames {
+        &self.declared
+    }
+
+    /// The symbols and permissions required by the global block of the module.
+    pub fn required(&self) -> &SymbolNames {
+        &self.required
+    }
+
+    /// The symbols and permissions required by the optional blocks of the module.
+    pub fn optional_required(&self) -> &SymbolNames {
+        &self.optional_required
+    }
+
+    /// The requirements of the global block that are neither declared by the module itself nor
+    /// by the given base, which would make linking the module fail.
+    ///
+    /// The base may be a base module, in which case only symbols declared in its scope tables
+    /// count, or a kernel policy.
+    pub fn unsatisfied(&self, base: &Policy) -> Vec<Requirement> {
+        self.global_requirements
+            .iter()
+            .filter(|requirement| !self.is_satisfied(base, requirement))
+            .cloned()
+            .collect()
+    }
+
+    fn is_satisfied(&self, base: &Policy, requirement: &Requirement) -> bool {
+        match *requirement {
+            Requirement::Symbol { kind, ref name } => {
+                self.declared_names[kind.index()].contains(name) || declares(base, kind, name)
+            }
+            Requirement::Permission {
+                ref class,
+                ref permission,
+            } => {
+                if self.declared_names[SymbolKind::Class.index()].contains(class) {
+                    return true;
+                }
+
+                declares(base, SymbolKind::Class, class)
+                    && base
+                        .classes()
+                        .get_by_name(class)
+                        .and_then(|class| permission_value(base, class, permission))
+                        .is_some()
+            }
+        }
+    }
+}
+
+/// Builds the requirements report of each module, in the order they are given.
+pub fn module_requirements(modules: &[Policy]) -> Result<Vec<ModuleRequirements>, LinkError> {
+    modules.iter().map(ModuleRequirements::new).collect()
+}
+
+fn declared_by(policy: &Policy, decl: &AvRuleDeclaration) -> Vec<Requirement> {
+    let declared = decl.declared();
+
+    SymbolKind::all()
+        .iter()
+        .flat_map(|&kind| {
+            declared
+                .symbol_ids(kind)
+                .filter_map(move |id| policy.symbol_name(kind, id))
+                .map(move |name| Requirement::Symbol {
+                    kind,
+                    name: name.to_string(),
+                })
+        })
+        .collect()
+}
+
+fn declares(base: &Policy, kind: SymbolKind, name: &str) -> bool {
+    match base.module() {
+        Some(module) => module
+            .scopes(kind)
+            .iter()
+            .any(|scope| scope.name() == name && scope.kind() == ScopeKind::Declared),
+        None => match kind {
+            SymbolKind::Common => base.common_classes().get_by_name(name).is_some(),
+            SymbolKind::Class => base.classes().get_by_name(name).is_some(),
+            SymbolKind::Role => base.roles().get_by_name(name).is_some(),
+            SymbolKind::Type => base.types().get_by_name(name).is_some(),
+            SymbolKind::User => base.users().get_by_name(name).is_some(),
+            SymbolKind::Boolean => base.booleans().get_by_name(name).is_some(),
+            SymbolKind::Sensitivity => base.sensitivities().get_by_name(name).is_some(),
+            SymbolKind::Category => base.categories().get_by_name(name).is_some(),
+        },
+    }
+}
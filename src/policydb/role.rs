@@ -1,13 +1,57 @@
 use croaring::Bitmap;
 use policydb::ty::TypeSet;
+use policydb::Class;
 use policydb::CompatibilityProfile;
 use policydb::Feature;
+use policydb::Policy;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
 use policydb::PolicyType;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Remap;
 use policydb::Symbol;
-use std::io::Read;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use policydb::Type;
+use std::io::{Read, Write};
+
+pub(crate) mod constants {
+    pub const ROLE_STAR: u32 = 1;
+    pub const ROLE_COMP: u32 = 2;
+
+    pub const ROLE_ROLE: u32 = 0;
+    pub const ROLE_ATTRIB: u32 = 1;
+
+    /// The role of every object, `object_r`, which kernel policies always declare first.
+    pub const OBJECT_R_VAL: u32 = 1;
+}
+
+/// Whether a `Role` is a concrete role or a role attribute grouping other roles.  Role
+/// attributes only exist in modules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoleFlavor {
+    Role,
+    Attribute,
+}
+
+impl RoleFlavor {
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            constants::ROLE_ROLE => Some(RoleFlavor::Role),
+            constants::ROLE_ATTRIB => Some(RoleFlavor::Attribute),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            RoleFlavor::Role => constants::ROLE_ROLE,
+            RoleFlavor::Attribute => constants::ROLE_ATTRIB,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Role {
@@ -15,16 +59,100 @@ pub struct Role {
     name: String,
     dominates: Bitmap,
     type_set: TypeSet,
-    flavor: Option<u32>,
+    flavor: RoleFlavor,
     roles: Option<Bitmap>,
+    bounds: Option<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum RoleSet {
     Bitmap(Bitmap),
     Set { roles: Bitmap, flags: u32 },
 }
 
+impl RoleSet {
+    /// The 0-based bits of the concrete roles in this set.  Role attributes are replaced by
+    /// their member roles, and the `*` and `~` flags of module role sets select every role or
+    /// the complement of the set.
+    pub fn expand(&self, policy: &Policy) -> Bitmap {
+        self.expand_with(&AttributeRoles::new(policy))
+    }
+
+    pub(crate) fn expand_with(&self, attrs: &AttributeRoles) -> Bitmap {
+        match *self {
+            RoleSet::Bitmap(ref roles) => attrs.expand(roles),
+            RoleSet::Set { ref roles, flags } => {
+                if flags & constants::ROLE_STAR != 0 {
+                    return attrs.roles.clone();
+                }
+
+                let expanded = attrs.expand(roles);
+
+                if flags & constants::ROLE_COMP != 0 {
+                    attrs.roles.andnot(&expanded)
+                } else {
+                    expanded
+                }
+            }
+        }
+    }
+}
+
+/// The member roles of every role attribute in a policy, used to expand role sets.
+#[derive(Debug)]
+pub(crate) struct AttributeRoles {
+    attributes: Bitmap,
+    roles: Bitmap,
+    members: Vec<Bitmap>,
+}
+
+impl AttributeRoles {
+    pub(crate) fn new(policy: &Policy) -> Self {
+        let mut attributes = Bitmap::create();
+        let mut roles = Bitmap::create();
+        let mut members = vec![Bitmap::create(); policy.roles().num_primary_names() as usize];
+
+        for role in policy.roles().all() {
+            let bit = role.id() - 1;
+
+            match role.roles {
+                Some(ref role_members) if role.is_attribute() => {
+                    attributes.add(bit);
+                    members[bit as usize] = role_members.clone();
+                }
+                _ => roles.add(bit),
+            }
+        }
+
+        AttributeRoles {
+            attributes,
+            roles,
+            members,
+        }
+    }
+
+    /// Replaces the role attributes in a bitmap of roles with their members.
+    pub(crate) fn expand(&self, roles: &Bitmap) -> Bitmap {
+        let mut expanded = roles.andnot(&self.attributes);
+
+        for attr in roles.and(&self.attributes).iter() {
+            expanded.or_inplace(&self.members[attr as usize].andnot(&self.attributes));
+        }
+
+        expanded
+    }
+}
+
+impl Remap for RoleSet {
+    fn remap(&mut self, map: &SymbolMap) {
+        match *self {
+            RoleSet::Bitmap(ref mut roles) | RoleSet::Set { ref mut roles, .. } => {
+                *roles = map.bitmap(SymbolKind::Role, roles)
+            }
+        }
+    }
+}
+
 impl PolicyObject for RoleSet {
     fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
         let profile = reader.profile();
@@ -38,6 +166,115 @@ impl PolicyObject for RoleSet {
             Ok(RoleSet::Set { roles, flags })
         }
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        let is_kernel_policy = writer.profile().ty().is_kernel_policy();
+
+        match *self {
+            RoleSet::Bitmap(ref roles) if is_kernel_policy => writer.write_object(roles),
+            RoleSet::Bitmap(ref roles) => {
+                writer.write_object(roles)?;
+                writer.write_u32(0)?;
+
+                Ok(())
+            }
+            RoleSet::Set { ref roles, .. } if is_kernel_policy => writer.write_object(roles),
+            RoleSet::Set { ref roles, flags } => {
+                writer.write_object(roles)?;
+                writer.write_u32(flags)?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Role {
+    /// Creates a role for a kernel policy, authorized for the types with the given 0-based bits.
+    pub(crate) fn new(id: u32, name: String, types: Bitmap) -> Self {
+        Role {
+            id,
+            name,
+            dominates: Bitmap::of(&[id - 1]),
+            type_set: TypeSet::Bitmap(types),
+            flavor: RoleFlavor::Role,
+            roles: None,
+            bounds: None,
+        }
+    }
+
+    /// The 0-based bits of the roles this role dominates, including itself.
+    pub fn dominates(&self) -> &Bitmap {
+        &self.dominates
+    }
+
+    /// The types this role is authorized for.
+    pub fn types(&self) -> &TypeSet {
+        &self.type_set
+    }
+
+    pub fn flavor(&self) -> RoleFlavor {
+        self.flavor
+    }
+
+    pub fn is_attribute(&self) -> bool {
+        self.flavor == RoleFlavor::Attribute
+    }
+
+    /// The id of the role that bounds this one, if any.
+    pub fn bounds_id(&self) -> Option<u32> {
+        self.bounds.filter(|bounds| *bounds != 0)
+    }
+
+    pub(crate) fn clear_bounds(&mut self) {
+        self.bounds = None;
+    }
+
+    /// Authorizes this role for the type with the given id.  Only the expanded type sets of
+    /// kernel policies can be extended.
+    pub(crate) fn add_type(&mut self, ty: u32) {
+        if let TypeSet::Bitmap(ref mut types) = self.type_set {
+            types.add(ty - 1);
+        }
+    }
+
+    /// The ids of the roles in this role attribute.
+    pub fn member_ids<'a>(&'a self) -> impl Iterator<Item = u32> + 'a {
+        self.roles
+            .iter()
+            .flat_map(|roles| roles.iter())
+            .map(|bit| bit + 1)
+    }
+
+    /// Merges a declaration of the same role from another module into this one.
+    pub(crate) fn merge(&mut self, other: Role) {
+        self.dominates.or_inplace(&other.dominates);
+        self.type_set.merge(&other.type_set);
+
+        if let (Some(roles), Some(other_roles)) = (self.roles.as_mut(), other.roles) {
+            roles.or_inplace(&other_roles);
+        }
+    }
+
+    /// Replaces the module type set of this role with its expanded types.
+    pub(crate) fn expand(&mut self, types: Bitmap) {
+        self.type_set = TypeSet::Bitmap(types);
+        self.flavor = RoleFlavor::Role;
+        self.roles = None;
+    }
+}
+
+impl Remap for Role {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.id = map.id(SymbolKind::Role, self.id);
+        self.bounds = self.bounds.map(|bounds| map.id(SymbolKind::Role, bounds));
+        self.dominates = map.bitmap(SymbolKind::Role, &self.dominates);
+        self.type_set.remap(map);
+
+        if let Some(ref mut roles) = self.roles {
+            *roles = map.bitmap(SymbolKind::Role, roles);
+        }
+    }
 }
 
 impl Symbol for Role {
@@ -64,15 +301,15 @@ impl PolicyObject for Role {
         let name = reader.read_string(name_len)?;
         let dominates = reader.read_object()?;
 
-        let is_kernel_policy = reader.profile().ty().is_kernel_policy();
-        let (type_set, flavor, roles) = if is_kernel_policy {
-            (TypeSet::Bitmap(reader.read_object()?), None, None)
+        let type_set = reader.read_object()?;
+        let (flavor, roles) = if reader.profile().supports(Feature::RoleAttributes) {
+            let flavor = reader.read_u32()?;
+            let flavor =
+                RoleFlavor::from_id(flavor).ok_or(PolicyReadError::InvalidRoleFlavor(flavor))?;
+
+            (flavor, Some(reader.read_object()?))
         } else {
-            (
-                reader.read_object()?,
-                Some(reader.read_u32()?),
-                Some(reader.read_object()?),
-            )
+            (RoleFlavor::Role, None)
         };
 
         Ok(Role {
@@ -82,6 +319,179 @@ impl PolicyObject for Role {
             type_set,
             flavor,
             roles,
+            bounds,
+        })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_u32(self.id)?;
+
+        if writer.profile().supports(Feature::Boundary) {
+            writer.write_u32(self.bounds.unwrap_or(0))?;
+        }
+
+        writer.write_string(&self.name)?;
+        writer.write_object(&self.dominates)?;
+        writer.write_object(&self.type_set)?;
+
+        if writer.profile().supports(Feature::RoleAttributes) {
+            writer.write_u32(self.flavor.id())?;
+
+            match self.roles {
+                Some(ref roles) => writer.write_object(roles)?,
+                None => writer.write_object(&Bitmap::create())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A `role_transition` rule, changing the role of a process (or other object class) when it
+/// transitions to a new type.
+#[derive(Debug)]
+pub struct RoleTransition {
+    role: u32,
+    ty: u32,
+    new_role: u32,
+    class: Option<u32>,
+}
+
+impl RoleTransition {
+    pub(crate) fn new(role: u32, ty: u32, new_role: u32, class: Option<u32>) -> Self {
+        RoleTransition {
+            role,
+            ty,
+            new_role,
+            class,
+        }
+    }
+
+    /// Drops the class of a `process` transition, for policies that only have process role
+    /// transitions.
+    pub(crate) fn clear_class(&mut self) {
+        self.class = None;
+    }
+
+    pub fn role<'a>(&self, policy: &'a Policy) -> Option<&'a Role> {
+        policy.roles().get(self.role)
+    }
+
+    pub fn ty<'a>(&self, policy: &'a Policy) -> Option<&'a Type> {
+        policy.types().get(self.ty)
+    }
+
+    /// The class this transition applies to.  Policies older than `POLICYDB_VERSION_ROLETRANS`
+    /// don't record a class and only apply to `process`.
+    pub fn class<'a>(&self, policy: &'a Policy) -> Option<&'a Class> {
+        match self.class {
+            Some(class) => policy.classes().get(class),
+            None => policy.classes().get_by_name("process"),
+        }
+    }
+
+    pub fn new_role<'a>(&self, policy: &'a Policy) -> Option<&'a Role> {
+        policy.roles().get(self.new_role)
+    }
+
+    pub fn role_id(&self) -> u32 {
+        self.role
+    }
+
+    pub fn type_id(&self) -> u32 {
+        self.ty
+    }
+
+    pub fn class_id(&self) -> Option<u32> {
+        self.class
+    }
+
+    pub fn new_role_id(&self) -> u32 {
+        self.new_role
+    }
+}
+
+impl PolicyObject for RoleTransition {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let role = reader.read_u32()?;
+        let ty = reader.read_u32()?;
+        let new_role = reader.read_u32()?;
+        let class = if reader.profile().supports(Feature::RoleTransitionClass) {
+            Some(reader.read_u32()?)
+        } else {
+            None
+        };
+
+        Ok(RoleTransition {
+            role,
+            ty,
+            new_role,
+            class,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.role)?;
+        writer.write_u32(self.ty)?;
+        writer.write_u32(self.new_role)?;
+
+        if writer.profile().supports(Feature::RoleTransitionClass) {
+            match self.class {
+                Some(class) => writer.write_u32(class)?,
+                None => {
+                    return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                        Feature::RoleTransitionClass,
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An `allow` rule between two roles, permitting a change from `role` to `new_role`.
+#[derive(Debug)]
+pub struct RoleAllow {
+    role: u32,
+    new_role: u32,
+}
+
+impl RoleAllow {
+    pub(crate) fn new(role: u32, new_role: u32) -> Self {
+        RoleAllow { role, new_role }
+    }
+
+    pub fn role<'a>(&self, policy: &'a Policy) -> Option<&'a Role> {
+        policy.roles().get(self.role)
+    }
+
+    pub fn new_role<'a>(&self, policy: &'a Policy) -> Option<&'a Role> {
+        policy.roles().get(self.new_role)
+    }
+
+    pub fn role_id(&self) -> u32 {
+        self.role
+    }
+
+    pub fn new_role_id(&self) -> u32 {
+        self.new_role
+    }
+}
+
+impl PolicyObject for RoleAllow {
+    fn decode<R: Read>(reader: &mut PolicyReader<R>) -> Result<Self, PolicyReadError> {
+        let role = reader.read_u32()?;
+        let new_role = reader.read_u32()?;
+
+        Ok(RoleAllow { role, new_role })
+    }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_u32(self.role)?;
+        writer.write_u32(self.new_role)?;
+
+        Ok(())
+    }
 }
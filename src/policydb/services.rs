@@ -0,0 +1,1049 @@
+use croaring::Bitmap;
+use policydb::avtab::{AVTAB_XPERMS_IOCTLDRIVER, AVTAB_XPERMS_IOCTLFUNCTION};
+use policydb::link::permission_value;
+use policydb::role::constants::OBJECT_R_VAL;
+use policydb::AccessVector;
+use policydb::AccessVectorTable;
+use policydb::AccessVectorTableKey;
+use policydb::AvRuleKind;
+use policydb::BinaryOp;
+use policydb::Boolean;
+use policydb::Class;
+use policydb::Constraint;
+use policydb::ConstraintAttribute;
+use policydb::ConstraintExpressionKind;
+use policydb::ConstraintOp;
+use policydb::MlsLevel;
+use policydb::MlsRange;
+use policydb::ObjectDefault;
+use policydb::Policy;
+use policydb::RangeDefault;
+use policydb::RoleSet;
+use policydb::SecurityContext;
+use policydb::Symbol;
+use policydb::TypeSet;
+use policydb::UnaryOp;
+use std::error::Error;
+use std::fmt;
+use std::iter;
+
+/// The classes that the kernel's `security_is_socket_class` labels like processes: every class in
+/// the kernel's class map whose name ends in `socket`.
+const SOCKET_CLASSES: &[&str] = &[
+    "socket",
+    "tcp_socket",
+    "udp_socket",
+    "rawip_socket",
+    "unix_stream_socket",
+    "unix_dgram_socket",
+    "tun_socket",
+    "packet_socket",
+    "key_socket",
+    "appletalk_socket",
+    "netlink_socket",
+    "netlink_route_socket",
+    "netlink_tcpdiag_socket",
+    "netlink_nflog_socket",
+    "netlink_xfrm_socket",
+    "netlink_selinux_socket",
+    "netlink_iscsi_socket",
+    "netlink_audit_socket",
+    "netlink_fib_lookup_socket",
+    "netlink_connector_socket",
+    "netlink_netfilter_socket",
+    "netlink_dnrt_socket",
+    "netlink_kobject_uevent_socket",
+    "netlink_generic_socket",
+    "netlink_scsitransport_socket",
+    "netlink_rdma_socket",
+    "netlink_crypto_socket",
+    "dccp_socket",
+    "sctp_socket",
+    "icmp_socket",
+    "ax25_socket",
+    "ipx_socket",
+    "netrom_socket",
+    "atmpvc_socket",
+    "x25_socket",
+    "rose_socket",
+    "decnet_socket",
+    "atmsvc_socket",
+    "rds_socket",
+    "irda_socket",
+    "pppox_socket",
+    "llc_socket",
+    "can_socket",
+    "tipc_socket",
+    "bluetooth_socket",
+    "iucv_socket",
+    "rxrpc_socket",
+    "isdn_socket",
+    "phonet_socket",
+    "ieee802154_socket",
+    "caif_socket",
+    "alg_socket",
+    "nfc_socket",
+    "vsock_socket",
+    "kcm_socket",
+    "qipcrtr_socket",
+    "smc_socket",
+    "xdp_socket",
+    "mctp_socket",
+];
+
+/// The permissions granted and audited for an access, in the same form as the kernel's
+/// `av_decision`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessDecision {
+    allowed: u32,
+    auditallow: u32,
+    auditdeny: u32,
+    permissive: bool,
+}
+
+impl AccessDecision {
+    /// A decision that allows nothing and audits every denial.
+    fn deny_all() -> Self {
+        AccessDecision {
+            allowed: 0,
+            auditallow: 0,
+            auditdeny: !0,
+            permissive: false,
+        }
+    }
+
+    /// The permissions that are allowed.
+    pub fn allowed(&self) -> u32 {
+        self.allowed
+    }
+
+    /// The permissions that are audited when they are allowed.
+    pub fn auditallow(&self) -> u32 {
+        self.auditallow
+    }
+
+    /// The permissions that are audited when they are denied.
+    pub fn auditdeny(&self) -> u32 {
+        self.auditdeny
+    }
+
+    /// Checks if the source domain is permissive, in which case denials are only logged.
+    pub fn is_permissive(&self) -> bool {
+        self.permissive
+    }
+
+    /// Checks if every one of the requested permissions is allowed.
+    pub fn is_allowed(&self, requested: u32) -> bool {
+        self.allowed & requested == requested
+    }
+}
+
+/// The ioctl commands of one driver that are allowed and audited, in the same form as the
+/// kernel's `extended_perms_decision`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtendedPermissionDecision {
+    driver: u8,
+    allowed: [u32; 8],
+    auditallow: [u32; 8],
+    dontaudit: [u32; 8],
+}
+
+impl ExtendedPermissionDecision {
+    pub fn driver(&self) -> u8 {
+        self.driver
+    }
+
+    /// Checks if the command with the given function number in this driver is allowed.
+    pub fn is_allowed(&self, function: u8) -> bool {
+        xperm_test(&self.allowed, function)
+    }
+
+    /// Checks if the command with the given function number is audited when it is allowed.
+    pub fn is_auditallow(&self, function: u8) -> bool {
+        xperm_test(&self.auditallow, function)
+    }
+
+    /// Checks if denials of the command with the given function number are not audited.
+    pub fn is_dontaudit(&self, function: u8) -> bool {
+        xperm_test(&self.dontaudit, function)
+    }
+}
+
+/// The outcome of an ioctl command, combining the `ioctl` permission with the extended
+/// permission rules for the command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoctlDecision {
+    allowed: bool,
+    audited: bool,
+    permissive: bool,
+}
+
+impl IoctlDecision {
+    pub fn is_allowed(&self) -> bool {
+        self.allowed
+    }
+
+    /// Checks if the kernel logs the decision, either through `auditallow` when the command is
+    /// allowed or because it was denied and not silenced by `dontaudit`.
+    pub fn is_audited(&self) -> bool {
+        self.audited
+    }
+
+    /// Checks if the command is denied without being logged.
+    pub fn is_dontaudited(&self) -> bool {
+        !self.allowed && !self.audited
+    }
+
+    /// Checks if the source domain is permissive, in which case denials are only logged.
+    pub fn is_permissive(&self) -> bool {
+        self.permissive
+    }
+}
+
+#[derive(Debug)]
+pub enum LabelError {
+    /// The class uses `default_range glblub`, but the source and target ranges have no
+    /// sensitivity in common.
+    DisjointRanges,
+    /// The computed context isn't valid in the policy, so the kernel would refuse to label the
+    /// object with it when enforcing.
+    InvalidContext(SecurityContext),
+}
+
+impl Error for LabelError {
+    fn description(&self) -> &str {
+        "Unable to compute security context"
+    }
+}
+
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LabelError::DisjointRanges => {
+                write!(f, "source and target ranges have no common sensitivity")
+            }
+            LabelError::InvalidContext(ref context) => write!(
+                f,
+                "computed context {}:{}:{} is not valid",
+                context.user_id(),
+                context.role_id(),
+                context.type_id()
+            ),
+        }
+    }
+}
+
+/// The parts of a security context used when computing decisions, which can have its type
+/// replaced by a bounding type without copying the range.
+#[derive(Clone, Copy)]
+pub(crate) struct ContextView<'a> {
+    pub(crate) user: u32,
+    pub(crate) role: u32,
+    pub(crate) ty: u32,
+    pub(crate) range: Option<&'a MlsRange>,
+}
+
+impl<'a> From<&'a SecurityContext> for ContextView<'a> {
+    fn from(context: &'a SecurityContext) -> Self {
+        ContextView {
+            user: context.user_id(),
+            role: context.role_id(),
+            ty: context.type_id(),
+            range: context.range(),
+        }
+    }
+}
+
+/// Access decisions for a kernel policy, computed in the same way as the kernel's security
+/// server so that they can be checked without loading the policy.
+impl Policy {
+    /// Computes the permissions of the class with the given id that `source` has on `target`.
+    ///
+    /// The rules for every attribute of the source and target types apply, along with the
+    /// conditional rules enabled by the current boolean values.  Permissions are then removed if
+    /// the class constraints deny them, if a process transition changes role without a
+    /// `role allow` rule, or if a bounded source type would get more than its bounding type.
+    pub fn compute_av(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class: u32,
+    ) -> AccessDecision {
+        let source = ContextView::from(source);
+        let mut decision = self.context_compute_av(&source, &target.into(), class);
+
+        decision.permissive = self
+            .types
+            .get(source.ty)
+            .map_or(false, |ty| ty.is_permissive());
+
+        decision
+    }
+
+    /// Checks if an object of the class with the given id may be relabeled from `old` to `new` by
+    /// a process running in `task`, using the class's `validatetrans` constraints.
+    pub fn validate_transition(
+        &self,
+        old: &SecurityContext,
+        new: &SecurityContext,
+        task: &SecurityContext,
+        class: u32,
+    ) -> bool {
+        let class = match self.classes.get(class) {
+            Some(class) => class,
+            None => return false,
+        };
+
+        let (old, new, task) = (old.into(), new.into(), task.into());
+
+        class
+            .transition_constraints()
+            .iter()
+            .all(|constraint| self.evaluate_constraint(constraint, &old, &new, Some(&task)))
+    }
+
+    /// Computes the extended permissions `source` has on `target` for the commands of one ioctl
+    /// driver, like the kernel's `security_compute_xperms_decision`.  Rules for the whole driver
+    /// include every command, and are combined with the rules for individual commands.
+    pub fn compute_xperms_decision(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class: u32,
+        driver: u8,
+    ) -> ExtendedPermissionDecision {
+        let mut decision = ExtendedPermissionDecision {
+            driver,
+            allowed: [0; 8],
+            auditallow: [0; 8],
+            dontaudit: [0; 8],
+        };
+
+        for (kind, specified, entry_driver, permissions) in
+            self.xperm_entries(&source.into(), &target.into(), class)
+        {
+            let all_commands = match specified {
+                AVTAB_XPERMS_IOCTLFUNCTION if entry_driver == driver => false,
+                AVTAB_XPERMS_IOCTLDRIVER if xperm_test(permissions, driver) => true,
+                _ => continue,
+            };
+
+            let commands = match kind {
+                AvRuleKind::AllowXperm => &mut decision.allowed,
+                AvRuleKind::AuditAllowXperm => &mut decision.auditallow,
+                AvRuleKind::DontAuditXperm => &mut decision.dontaudit,
+                _ => continue,
+            };
+
+            for (commands, new_commands) in commands.iter_mut().zip(permissions.iter()) {
+                *commands |= if all_commands { !0 } else { *new_commands };
+            }
+        }
+
+        decision
+    }
+
+    /// Checks if `source` may use the ioctl command `cmd` on `target`, like the kernel's
+    /// `avc_has_extended_perms`.  The `ioctl` permission has to be allowed, and once any
+    /// extended permission rule exists for the types and class, the command also has to be
+    /// allowed by one.
+    pub fn compute_ioctl(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class: u32,
+        cmd: u16,
+    ) -> IoctlDecision {
+        let av = self.compute_av(source, target, class);
+        let requested = self
+            .classes
+            .get(class)
+            .map_or(0, |class| self.permission_bits(class, &["ioctl"]));
+
+        let driver = (cmd >> 8) as u8;
+        let function = cmd as u8;
+        let mut allowed = requested != 0 && av.is_allowed(requested);
+
+        // Drivers with a rule for some of their commands, or for the whole driver.
+        let mut drivers = [0u32; 8];
+        let mut has_xperms = false;
+
+        for (_, specified, entry_driver, permissions) in
+            self.xperm_entries(&source.into(), &target.into(), class)
+        {
+            match specified {
+                AVTAB_XPERMS_IOCTLFUNCTION => xperm_set(&mut drivers, entry_driver),
+                AVTAB_XPERMS_IOCTLDRIVER => {
+                    for (drivers, new_drivers) in drivers.iter_mut().zip(permissions.iter()) {
+                        *drivers |= *new_drivers;
+                    }
+                }
+                _ => {}
+            }
+
+            has_xperms = true;
+        }
+
+        let xperms = if !has_xperms {
+            None
+        } else if xperm_test(&drivers, driver) {
+            Some(self.compute_xperms_decision(source, target, class, driver))
+        } else {
+            allowed = false;
+            None
+        };
+
+        if let Some(ref xperms) = xperms {
+            allowed &= xperms.is_allowed(function);
+        }
+
+        let audited = if allowed {
+            av.auditallow() & requested != 0
+                && xperms
+                    .as_ref()
+                    .map_or(true, |xperms| xperms.is_auditallow(function))
+        } else {
+            av.auditdeny() & requested != 0
+                && !xperms
+                    .as_ref()
+                    .map_or(false, |xperms| xperms.is_dontaudit(function))
+        };
+
+        IoctlDecision {
+            allowed,
+            audited,
+            permissive: av.is_permissive(),
+        }
+    }
+
+    /// The extended permission entries that apply to the source and target types and class, as
+    /// their rule kind, `specified` value, driver and permissions.
+    fn xperm_entries<'a>(
+        &'a self,
+        source: &ContextView,
+        target: &ContextView,
+        class_id: u32,
+    ) -> Vec<(AvRuleKind, u8, u8, &'a [u32; 8])> {
+        let source_types: Bitmap = self.type_attr_map.attributes_of(source.ty).collect();
+        let target_types: Bitmap = self.type_attr_map.attributes_of(target.ty).collect();
+        let mut entries = vec![];
+
+        for rules in self.active_rules() {
+            collect_xperms(&mut entries, rules, &source_types, &target_types, class_id);
+        }
+
+        entries
+    }
+
+    /// Computes the context of a new object of the class with the given id, created by `source`
+    /// in or from `target`, like the kernel's `security_compute_create`.  For processes the
+    /// target is the executable being run, and for files it is the parent directory, whose
+    /// filename transitions apply when the name of the new file is given.
+    pub fn compute_create(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class: u32,
+        name: Option<&str>,
+    ) -> Result<SecurityContext, LabelError> {
+        self.compute_sid(source, target, class, AvRuleKind::TypeTransition, name)
+    }
+
+    /// Computes the context to relabel an object in `target` to when it is used by `source`,
+    /// using `type_change` rules, like the kernel's `security_change_sid`.
+    pub fn compute_relabel(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class: u32,
+    ) -> Result<SecurityContext, LabelError> {
+        self.compute_sid(source, target, class, AvRuleKind::TypeChange, None)
+    }
+
+    /// Computes the context of a member of the polyinstantiated object `target`, such as a
+    /// polyinstantiated directory, using `type_member` rules, like the kernel's
+    /// `security_member_sid`.
+    pub fn compute_member(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class: u32,
+    ) -> Result<SecurityContext, LabelError> {
+        self.compute_sid(source, target, class, AvRuleKind::TypeMember, None)
+    }
+
+    /// Checks if a context could be used in this policy: its role has to be authorized for its
+    /// type and its user for its role, except for objects in `object_r`, and when MLS is enabled
+    /// its levels may only use the categories allowed for their sensitivities and its range has
+    /// to be within the user's range.
+    pub fn is_valid_context(&self, context: &SecurityContext) -> bool {
+        let (user, role) = match (context.user(self), context.role(self), context.ty(self)) {
+            (Some(user), Some(role), Some(_)) => (user, role),
+            _ => return false,
+        };
+
+        if context.role_id() != OBJECT_R_VAL {
+            let has_type = match *role.types() {
+                TypeSet::Bitmap(ref types) => types.contains(context.type_id() - 1),
+                TypeSet::Set { .. } => false,
+            };
+
+            let has_role = match *user.roles() {
+                RoleSet::Bitmap(ref roles) => roles.contains(context.role_id() - 1),
+                RoleSet::Set { .. } => false,
+            };
+
+            if !has_type || !has_role {
+                return false;
+            }
+        }
+
+        if !self.config.mls_enabled {
+            return true;
+        }
+
+        let range = match context.range() {
+            Some(range) => range,
+            None => return false,
+        };
+
+        // The categories of a level have to be allowed for its sensitivity, like the kernel's
+        // `mls_level_isvalid`.
+        let is_level_valid = |level: &MlsLevel| {
+            self.sensitivities
+                .get(level.sensitivity())
+                .map_or(false, |sensitivity| {
+                    level
+                        .categories()
+                        .is_subset(sensitivity.level().categories())
+                })
+        };
+
+        if !is_level_valid(range.low())
+            || !is_level_valid(range.high())
+            || !range.high().dominates(range.low())
+        {
+            return false;
+        }
+
+        context.role_id() == OBJECT_R_VAL
+            || user.range().map_or(false, |user_range| {
+                range.low().dominates(user_range.low()) && user_range.high().dominates(range.high())
+            })
+    }
+
+    /// Computes a new context in the same way as the kernel's `security_compute_sid`.
+    fn compute_sid(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class_id: u32,
+        kind: AvRuleKind,
+        name: Option<&str>,
+    ) -> Result<SecurityContext, LabelError> {
+        let class = self.classes.get(class_id);
+        let is_process = class.map_or(false, |class| {
+            class.name() == "process" || is_socket_class(class.name())
+        });
+
+        let default_user = class.and_then(Class::default_user);
+        let default_role = class.and_then(Class::default_role);
+        let default_type = class.and_then(Class::default_type);
+
+        let user = match kind {
+            AvRuleKind::TypeMember => target.user_id(),
+            _ if default_user == Some(ObjectDefault::Target) => target.user_id(),
+            _ => source.user_id(),
+        };
+
+        let mut role = match default_role {
+            Some(ObjectDefault::Source) => source.role_id(),
+            Some(ObjectDefault::Target) => target.role_id(),
+            None if is_process => source.role_id(),
+            None => OBJECT_R_VAL,
+        };
+
+        let mut ty = match default_type {
+            Some(ObjectDefault::Source) => source.type_id(),
+            Some(ObjectDefault::Target) => target.type_id(),
+            None if is_process => source.type_id(),
+            None => target.type_id(),
+        };
+
+        if let Some(new_type) = self.type_rule(source.type_id(), target.type_id(), class_id, kind) {
+            ty = new_type;
+        }
+
+        if let Some(name) = name {
+            let transition = self.filename_transitions.iter().find(|trans| {
+                trans.has_source_type(source.type_id())
+                    && trans.target_type_id() == target.type_id()
+                    && trans.class_id() == class_id
+                    && trans.name() == name
+            });
+
+            if let Some(transition) = transition {
+                ty = transition.new_type_id();
+            }
+        }
+
+        if kind == AvRuleKind::TypeTransition {
+            let process = self.classes.get_by_name("process").map(Class::id);
+            let transition = self.role_transitions.iter().find(|trans| {
+                trans.role_id() == source.role_id()
+                    && trans.type_id() == target.type_id()
+                    && trans.class_id().or(process) == Some(class_id)
+            });
+
+            if let Some(transition) = transition {
+                role = transition.new_role_id();
+            }
+        }
+
+        let range = if self.config.mls_enabled {
+            self.compute_range(source, target, class_id, kind, is_process)?
+        } else {
+            source.range().cloned()
+        };
+
+        let context = SecurityContext::new(user, role, ty, range);
+
+        if self.is_valid_context(&context) {
+            Ok(context)
+        } else {
+            Err(LabelError::InvalidContext(context))
+        }
+    }
+
+    /// The new type given by the type rule of the given kind for exactly these types, from the
+    /// unconditional rules or else from the conditional rules enabled by the boolean values.
+    fn type_rule(
+        &self,
+        source_type: u32,
+        target_type: u32,
+        class_id: u32,
+        kind: AvRuleKind,
+    ) -> Option<u32> {
+        self.active_rules().find_map(|rules| {
+            rules.entries().iter().find_map(|entry| {
+                let key = entry.key();
+                let is_match = key.kind() == kind
+                    && u32::from(key.source_type()) == source_type
+                    && u32::from(key.target_type()) == target_type
+                    && u32::from(key.target_class()) == class_id;
+
+                match *entry.av() {
+                    AccessVector::Simple(new_type) if is_match => Some(new_type),
+                    _ => None,
+                }
+            })
+        })
+    }
+
+    /// Computes the range of a new context, like the kernel's `mls_compute_sid`.
+    fn compute_range(
+        &self,
+        source: &SecurityContext,
+        target: &SecurityContext,
+        class_id: u32,
+        kind: AvRuleKind,
+        is_process: bool,
+    ) -> Result<Option<MlsRange>, LabelError> {
+        let (source_range, target_range) = match (source.range(), target.range()) {
+            (Some(source_range), Some(target_range)) => (source_range, target_range),
+            _ => return Ok(source.range().cloned()),
+        };
+
+        let low_range = |range: &MlsRange| MlsRange::new(range.low().clone(), range.low().clone());
+        let high_range =
+            |range: &MlsRange| MlsRange::new(range.high().clone(), range.high().clone());
+
+        if kind == AvRuleKind::TypeTransition {
+            let process = self.classes.get_by_name("process").map(Class::id);
+            let transition = self.range_transitions.iter().find(|trans| {
+                trans.source_type_id() == source.type_id()
+                    && trans.target_type_id() == target.type_id()
+                    && trans.class_id().or(process) == Some(class_id)
+            });
+
+            if let Some(transition) = transition {
+                return Ok(Some(transition.range().clone()));
+            }
+
+            let default_range = self
+                .classes
+                .get(class_id)
+                .and_then(|class| class.default_range());
+
+            let range = match default_range {
+                Some(RangeDefault::SourceLow) => Some(low_range(source_range)),
+                Some(RangeDefault::SourceHigh) => Some(high_range(source_range)),
+                Some(RangeDefault::SourceLowHigh) => Some(source_range.clone()),
+                Some(RangeDefault::TargetLow) => Some(low_range(target_range)),
+                Some(RangeDefault::TargetHigh) => Some(high_range(target_range)),
+                Some(RangeDefault::TargetLowHigh) => Some(target_range.clone()),
+                Some(RangeDefault::Glblub) => Some(glblub(source_range, target_range)?),
+                None => None,
+            };
+
+            if range.is_some() {
+                return Ok(range);
+            }
+        }
+
+        if kind != AvRuleKind::TypeMember && is_process {
+            Ok(Some(source_range.clone()))
+        } else {
+            Ok(Some(low_range(source_range)))
+        }
+    }
+
+    fn context_compute_av(
+        &self,
+        source: &ContextView,
+        target: &ContextView,
+        class_id: u32,
+    ) -> AccessDecision {
+        let mut decision = AccessDecision::deny_all();
+
+        let class = match self.classes.get(class_id) {
+            Some(class) => class,
+            None => return decision,
+        };
+
+        let source_types: Bitmap = self.type_attr_map.attributes_of(source.ty).collect();
+        let target_types: Bitmap = self.type_attr_map.attributes_of(target.ty).collect();
+
+        for rules in self.active_rules() {
+            apply_rules(&mut decision, rules, &source_types, &target_types, class_id);
+        }
+
+        for constraint in class.constraints() {
+            if constraint.permissions() & decision.allowed != 0
+                && !self.evaluate_constraint(constraint, source, target, None)
+            {
+                decision.allowed &= !constraint.permissions();
+            }
+        }
+
+        if class.name() == "process" && source.role != target.role {
+            let transition_perms = self.permission_bits(class, &["transition", "dyntransition"]);
+            let is_role_allowed = self
+                .role_allows
+                .iter()
+                .any(|allow| allow.role_id() == source.role && allow.new_role_id() == target.role);
+
+            if decision.allowed & transition_perms != 0 && !is_role_allowed {
+                decision.allowed &= !transition_perms;
+            }
+        }
+
+        self.apply_type_bounds(&mut decision, source, target, class_id);
+
+        decision
+    }
+
+    /// Removes the permissions a bounded source type has beyond those of its bounding type, like
+    /// the kernel's `type_attribute_bounds_av`.
+    fn apply_type_bounds(
+        &self,
+        decision: &mut AccessDecision,
+        source: &ContextView,
+        target: &ContextView,
+        class_id: u32,
+    ) {
+        let bounds = match self.types.get(source.ty).and_then(|ty| ty.bounds_id()) {
+            Some(bounds) if bounds != source.ty => bounds,
+            _ => return,
+        };
+
+        let bounded_source = ContextView {
+            ty: bounds,
+            ..*source
+        };
+
+        let bounded_target = match self.types.get(target.ty).and_then(|ty| ty.bounds_id()) {
+            Some(bounds) => ContextView {
+                ty: bounds,
+                ..*target
+            },
+            None => *target,
+        };
+
+        let bounded = self.context_compute_av(&bounded_source, &bounded_target, class_id);
+
+        decision.allowed &= bounded.allowed;
+    }
+
+    /// The unconditional rules, followed by the rule list of each conditional that its expression
+    /// selects with the current boolean values, like the kernel's `evaluate_cond_nodes`.  The
+    /// rules of conditionals with malformed expressions are all disabled.
+    fn active_rules<'a>(&'a self) -> impl Iterator<Item = &'a AccessVectorTable> + 'a {
+        let conditional_rules = self.conditionals.iter().filter_map(move |cond| {
+            let value =
+                cond.evaluate(|id| self.booleans.get(id).map_or(false, Boolean::is_toggled));
+
+            match value {
+                Some(true) => Some(cond.true_list()),
+                Some(false) => Some(cond.false_list()),
+                None => None,
+            }
+        });
+
+        iter::once(&self.avtab).chain(conditional_rules)
+    }
+
+    /// The access vector for the named permissions of a class, ignoring unknown permissions.
+    pub(crate) fn permission_bits(&self, class: &Class, permissions: &[&str]) -> u32 {
+        permissions
+            .iter()
+            .filter_map(|perm| permission_value(self, class, perm))
+            .fold(0, |av, value| av | 1 << (value - 1))
+    }
+
+    /// Evaluates a constraint expression for a source and target context, and the context of the
+    /// task for `validatetrans` constraints.  Malformed expressions are never satisfied.
+    fn evaluate_constraint(
+        &self,
+        constraint: &Constraint,
+        source: &ContextView,
+        target: &ContextView,
+        task: Option<&ContextView>,
+    ) -> bool {
+        let mut stack: Vec<bool> = Vec::with_capacity(constraint.expressions().len());
+
+        for expr in constraint.expressions() {
+            let value = match (expr.kind(), expr.attr(), expr.op()) {
+                (&ConstraintExpressionKind::Unary(UnaryOp::Not), _, _) => match stack.pop() {
+                    Some(value) => !value,
+                    None => return false,
+                },
+                (&ConstraintExpressionKind::Binary(ref op), _, _) => {
+                    match (stack.pop(), stack.pop()) {
+                        (Some(rhs), Some(lhs)) => match *op {
+                            BinaryOp::And => lhs && rhs,
+                            BinaryOp::Or => lhs || rhs,
+                        },
+                        _ => return false,
+                    }
+                }
+                (&ConstraintExpressionKind::Attr, Some(attr), Some(op)) => {
+                    self.evaluate_attr(attr, op, source, target)
+                }
+                (&ConstraintExpressionKind::Names { ref names, .. }, Some(attr), Some(op)) => {
+                    evaluate_names(names, attr, op, source, target, task)
+                }
+                _ => return false,
+            };
+
+            stack.push(value);
+        }
+
+        match (stack.pop(), stack.is_empty()) {
+            (Some(value), true) => value,
+            _ => false,
+        }
+    }
+
+    /// Compares an attribute of the source context against the same attribute of the target.
+    fn evaluate_attr(
+        &self,
+        attr: ConstraintAttribute,
+        op: ConstraintOp,
+        source: &ContextView,
+        target: &ContextView,
+    ) -> bool {
+        use self::ConstraintAttribute::*;
+
+        match attr {
+            U1 | U2 | U3 => compare_ids(op, source.user, target.user),
+            T1 | T2 | T3 => compare_ids(op, source.ty, target.ty),
+            R1 | R2 | R3 => self.compare_roles(op, source.role, target.role),
+            L1L2 => compare_levels(op, low(source), low(target)),
+            L1H2 => compare_levels(op, low(source), high(target)),
+            H1L2 => compare_levels(op, high(source), low(target)),
+            H1H2 => compare_levels(op, high(source), high(target)),
+            L1H1 => compare_levels(op, low(source), high(source)),
+            L2H2 => compare_levels(op, low(target), high(target)),
+        }
+    }
+
+    fn compare_roles(&self, op: ConstraintOp, source: u32, target: u32) -> bool {
+        let dominates = |role: u32, other: u32| {
+            self.roles
+                .get(role)
+                .map_or(false, |role| role.dominates().contains(other - 1))
+        };
+
+        match op {
+            ConstraintOp::Dom => dominates(source, target),
+            ConstraintOp::DomBy => dominates(target, source),
+            ConstraintOp::Incomp => !dominates(source, target) && !dominates(target, source),
+            _ => compare_ids(op, source, target),
+        }
+    }
+}
+
+/// Adds the rules of an avtab that apply to the source and target types, given as the ids of
+/// every attribute they are in.
+fn apply_rules(
+    decision: &mut AccessDecision,
+    rules: &AccessVectorTable,
+    source_types: &Bitmap,
+    target_types: &Bitmap,
+    class_id: u32,
+) {
+    for entry in rules.entries() {
+        let key = entry.key();
+
+        if !applies_to(key, source_types, target_types, class_id) {
+            continue;
+        }
+
+        if let AccessVector::Simple(av) = *entry.av() {
+            match key.kind() {
+                AvRuleKind::Allow => decision.allowed |= av,
+                AvRuleKind::AuditAllow => decision.auditallow |= av,
+                AvRuleKind::AuditDeny => decision.auditdeny &= av,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Adds the extended permission entries of an avtab that apply to the source and target types.
+fn collect_xperms<'a>(
+    entries: &mut Vec<(AvRuleKind, u8, u8, &'a [u32; 8])>,
+    rules: &'a AccessVectorTable,
+    source_types: &Bitmap,
+    target_types: &Bitmap,
+    class_id: u32,
+) {
+    for entry in rules.entries() {
+        if !applies_to(entry.key(), source_types, target_types, class_id) {
+            continue;
+        }
+
+        if let AccessVector::Extended {
+            specified,
+            driver,
+            ref permissions,
+        } = *entry.av()
+        {
+            entries.push((entry.key().kind(), specified, driver, permissions));
+        }
+    }
+}
+
+/// The greatest lower bound of two ranges, covering only what is in both.
+fn glblub(lhs: &MlsRange, rhs: &MlsRange) -> Result<MlsRange, LabelError> {
+    let (lhs_low, lhs_high) = (lhs.low(), lhs.high());
+    let (rhs_low, rhs_high) = (rhs.low(), rhs.high());
+
+    if lhs_high.sensitivity() < rhs_low.sensitivity()
+        || rhs_high.sensitivity() < lhs_low.sensitivity()
+    {
+        return Err(LabelError::DisjointRanges);
+    }
+
+    let low = MlsLevel::new(
+        lhs_low.sensitivity().max(rhs_low.sensitivity()),
+        lhs_low.categories().and(rhs_low.categories()),
+    );
+    let high = MlsLevel::new(
+        lhs_high.sensitivity().min(rhs_high.sensitivity()),
+        lhs_high.categories().and(rhs_high.categories()),
+    );
+
+    Ok(MlsRange::new(low, high))
+}
+
+fn is_socket_class(name: &str) -> bool {
+    SOCKET_CLASSES.contains(&name)
+}
+
+/// Checks if an avtab entry is for one of the source and target types and the class.
+fn applies_to(
+    key: &AccessVectorTableKey,
+    source_types: &Bitmap,
+    target_types: &Bitmap,
+    class_id: u32,
+) -> bool {
+    u32::from(key.target_class()) == class_id
+        && source_types.contains(u32::from(key.source_type()))
+        && target_types.contains(u32::from(key.target_type()))
+}
+
+fn xperm_test(permissions: &[u32; 8], bit: u8) -> bool {
+    permissions[(bit >> 5) as usize] & (1 << (bit & 0x1f)) != 0
+}
+
+fn xperm_set(permissions: &mut [u32; 8], bit: u8) {
+    permissions[(bit >> 5) as usize] |= 1 << (bit & 0x1f);
+}
+
+/// Checks if the user, role or type of one of the contexts is in a set of names.
+fn evaluate_names(
+    names: &Bitmap,
+    attr: ConstraintAttribute,
+    op: ConstraintOp,
+    source: &ContextView,
+    target: &ContextView,
+    task: Option<&ContextView>,
+) -> bool {
+    use self::ConstraintAttribute::*;
+
+    let context = match attr {
+        U1 | R1 | T1 => Some(source),
+        U2 | R2 | T2 => Some(target),
+        U3 | R3 | T3 => task,
+        _ => None,
+    };
+
+    let value = match (context, attr) {
+        (Some(context), U1) | (Some(context), U2) | (Some(context), U3) => context.user,
+        (Some(context), R1) | (Some(context), R2) | (Some(context), R3) => context.role,
+        (Some(context), _) => context.ty,
+        (None, _) => return false,
+    };
+
+    let is_named = value > 0 && names.contains(value - 1);
+
+    match op {
+        ConstraintOp::Eq => is_named,
+        ConstraintOp::Neq => !is_named,
+        _ => false,
+    }
+}
+
+fn compare_ids(op: ConstraintOp, lhs: u32, rhs: u32) -> bool {
+    match op {
+        ConstraintOp::Eq => lhs == rhs,
+        ConstraintOp::Neq => lhs != rhs,
+        _ => false,
+    }
+}
+
+fn low<'a>(context: &ContextView<'a>) -> Option<&'a MlsLevel> {
+    context.range.map(MlsRange::low)
+}
+
+fn high<'a>(context: &ContextView<'a>) -> Option<&'a MlsLevel> {
+    context.range.map(MlsRange::high)
+}
+
+/// Compares two MLS levels, which are only missing if the policy doesn't support MLS.
+fn compare_levels(op: ConstraintOp, lhs: Option<&MlsLevel>, rhs: Option<&MlsLevel>) -> bool {
+    let (lhs, rhs) = match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => (lhs, rhs),
+        _ => return false,
+    };
+
+    match op {
+        ConstraintOp::Eq => lhs == rhs,
+        ConstraintOp::Neq => lhs != rhs,
+        ConstraintOp::Dom => lhs.dominates(rhs),
+        ConstraintOp::DomBy => rhs.dominates(lhs),
+        ConstraintOp::Incomp => !lhs.dominates(rhs) && !rhs.dominates(lhs),
+    }
+}
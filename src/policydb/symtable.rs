@@ -2,20 +2,71 @@ use policydb::CompatibilityProfile;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
-use std::collections::btree_map::Values;
+use std::collections::btree_map::{Values, ValuesMut};
 use std::collections::BTreeMap;
 use std::io::Read;
 
+/// The kinds of symbol table found in a policy, in the order they are stored.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SymbolKind {
+    Common,
+    Class,
+    Role,
+    Type,
+    User,
+    Boolean,
+    Sensitivity,
+    Category,
+}
+
+const ALL_SYMBOL_KINDS: &[SymbolKind] = &[
+    SymbolKind::Common,
+    SymbolKind::Class,
+    SymbolKind::Role,
+    SymbolKind::Type,
+    SymbolKind::User,
+    SymbolKind::Boolean,
+    SymbolKind::Sensitivity,
+    SymbolKind::Category,
+];
+
+impl SymbolKind {
+    pub fn all() -> &'static [SymbolKind] {
+        ALL_SYMBOL_KINDS
+    }
+
+    /// The position of this kind's table in the policy.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// A symbol or permission that isn't declared in a policy.  Errors of the APIs that take names
+/// are converted from this.
+#[derive(Debug)]
+pub(crate) enum UnknownName {
+    Symbol { kind: SymbolKind, name: String },
+    Permission { class: String, permission: String },
+}
+
 pub trait Symbol: PolicyObject {
     fn id(&self) -> u32;
 
     fn name(&self) -> &str;
+
+    /// Checks if this symbol is an alias, sharing the id of its primary symbol.
+    fn is_alias(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
 pub struct SymbolTable<SymbolType: Symbol> {
     name_id_map: BTreeMap<String, u32>,
     symbols: BTreeMap<u32, SymbolType>,
+    aliases: BTreeMap<String, SymbolType>,
+    order: Vec<String>,
+    num_primary_names: u32,
 }
 
 impl<SymbolType: Symbol> SymbolTable<SymbolType> {
@@ -23,21 +74,130 @@ impl<SymbolType: Symbol> SymbolTable<SymbolType> {
         SymbolTable {
             name_id_map: BTreeMap::new(),
             symbols: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            order: Vec::with_capacity(capacity),
+            num_primary_names: 0,
         }
     }
 
     pub fn insert(&mut self, sym: SymbolType) {
         let id = sym.id();
 
-        self.name_id_map.insert(sym.name().to_string(), id);
-        self.symbols.insert(id, sym);
+        if id > self.num_primary_names {
+            self.num_primary_names = id;
+        }
+
+        if self
+            .name_id_map
+            .insert(sym.name().to_string(), id)
+            .is_none()
+        {
+            self.order.push(sym.name().to_string());
+        }
+
+        if sym.is_alias() {
+            self.aliases.insert(sym.name().to_string(), sym);
+        } else {
+            self.symbols.insert(id, sym);
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&SymbolType> {
+        self.symbols.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut SymbolType> {
+        self.symbols.get_mut(&id)
+    }
+
+    /// Finds a symbol by name.  Looking up the name of an alias gives its primary symbol.
+    pub fn get_by_name(&self, name: &str) -> Option<&SymbolType> {
+        self.name_id_map
+            .get(name)
+            .and_then(|id| self.symbols.get(id))
     }
 
+    /// The primary symbols in this table, excluding aliases.
     pub fn all(&self) -> Values<u32, SymbolType> {
         self.symbols.values()
     }
 
+    pub fn aliases(&self) -> Values<String, SymbolType> {
+        self.aliases.values()
+    }
+
+    /// The aliases of the symbol with the given id.
+    pub fn aliases_of<'a>(&'a self, id: u32) -> impl Iterator<Item = &'a SymbolType> + 'a {
+        self.aliases.values().filter(move |alias| alias.id() == id)
+    }
+
+    /// Every symbol in this table, including aliases, in the order they were inserted.  Policies
+    /// are written out in this order so that reading and writing a policy gives the same bytes.
+    pub fn ordered<'a>(&'a self) -> impl Iterator<Item = &'a SymbolType> + 'a {
+        self.order.iter().filter_map(move |name| {
+            self.aliases.get(name).or_else(|| {
+                self.name_id_map
+                    .get(name)
+                    .and_then(|id| self.symbols.get(id))
+            })
+        })
+    }
+
+    pub(crate) fn all_mut(&mut self) -> ValuesMut<u32, SymbolType> {
+        self.symbols.values_mut()
+    }
+
+    pub(crate) fn aliases_mut(&mut self) -> ValuesMut<String, SymbolType> {
+        self.aliases.values_mut()
+    }
+
     pub fn len(&self) -> usize {
         self.symbols.len()
     }
+
+    /// The number of entries in this table, including aliases.
+    pub fn num_entries(&self) -> usize {
+        self.symbols.len() + self.aliases.len()
+    }
+
+    /// The number of values allocated to symbols in this table, which is also the highest id.
+    pub fn num_primary_names(&self) -> u32 {
+        self.num_primary_names
+    }
+
+    /// The id that the next symbol inserted into this table should be given.
+    pub fn next_id(&self) -> u32 {
+        self.num_primary_names + 1
+    }
+
+    /// Checks if a symbol or alias with the given name is in this table.
+    pub fn contains(&self, name: &str) -> bool {
+        self.name_id_map.contains_key(name)
+    }
+
+    pub(crate) fn set_num_primary_names(&mut self, num_primary_names: u32) {
+        self.num_primary_names = num_primary_names;
+    }
+
+    pub(crate) fn into_symbols(self) -> impl Iterator<Item = SymbolType> {
+        self.symbols
+            .into_iter()
+            .map(|(_, sym)| sym)
+            .chain(self.aliases.into_iter().map(|(_, alias)| alias))
+    }
+}
+
+/// Looks up the id of the symbol with the given name, or of the primary symbol of an alias.
+pub(crate) fn lookup<S: Symbol>(
+    table: &SymbolTable<S>,
+    kind: SymbolKind,
+    name: &str,
+) -> Result<u32, UnknownName> {
+    table
+        .get_by_name(name)
+        .map(|sym| sym.id())
+        .ok_or_else(|| UnknownName::Symbol {
+            kind,
+            name: name.to_string(),
+        })
 }
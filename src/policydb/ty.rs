@@ -1,11 +1,57 @@
 use croaring::Bitmap;
 use policydb::CompatibilityProfile;
 use policydb::Feature;
+use policydb::Policy;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Remap;
 use policydb::Symbol;
-use std::io::Read;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::iter;
+
+pub(crate) mod constants {
+    pub const TYPE_STAR: u32 = 1;
+    pub const TYPE_COMP: u32 = 2;
+
+    pub const TYPE_TYPE: u32 = 0;
+    pub const TYPE_ATTRIB: u32 = 1;
+    pub const TYPE_ALIAS: u32 = 2;
+}
+
+/// Whether a `Type` is a concrete type, an attribute grouping other types, or an alias for
+/// another type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeFlavor {
+    Type,
+    Attribute,
+    Alias,
+}
+
+impl TypeFlavor {
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            constants::TYPE_TYPE => Some(TypeFlavor::Type),
+            constants::TYPE_ATTRIB => Some(TypeFlavor::Attribute),
+            constants::TYPE_ALIAS => Some(TypeFlavor::Alias),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            TypeFlavor::Type => constants::TYPE_TYPE,
+            TypeFlavor::Attribute => constants::TYPE_ATTRIB,
+            TypeFlavor::Alias => constants::TYPE_ALIAS,
+        }
+    }
+}
 
 bitflags! {
     struct TyProperties: u32 {
@@ -22,7 +68,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum TypeSet {
     Bitmap(Bitmap),
     Set {
@@ -36,13 +82,199 @@ pub enum TypeSet {
 pub struct Type {
     id: u32,
     name: String,
-    primary: bool,
-    flavor: Option<u32>,
+    /// 1 for a primary type.  Aliases have 0 in kernel policies, but from
+    /// `MOD_POLICYDB_VERSION_BOUNDARY_ALIAS` modules store the value of their primary type here.
+    primary: u32,
+    flavor: TypeFlavor,
     flags: TyFlags,
     bounds: Option<u32>,
     assoc_types: Option<Bitmap>,
 }
 
+impl Type {
+    /// Creates a type for a kernel policy.  Aliases share the id of their primary type.
+    pub(crate) fn new(id: u32, name: String, flavor: TypeFlavor) -> Self {
+        Type {
+            id,
+            name,
+            primary: (flavor != TypeFlavor::Alias) as u32,
+            flavor,
+            flags: TyFlags::empty(),
+            bounds: None,
+            assoc_types: None,
+        }
+    }
+
+    /// Checks if this type is a permissive domain, where denials are logged but not enforced.
+    pub fn is_permissive(&self) -> bool {
+        self.flags.contains(TyFlags::Permissive)
+    }
+
+    pub(crate) fn set_permissive(&mut self, permissive: bool) {
+        self.flags.set(TyFlags::Permissive, permissive);
+    }
+
+    pub fn flavor(&self) -> TypeFlavor {
+        if self.is_alias() {
+            TypeFlavor::Alias
+        } else {
+            self.flavor
+        }
+    }
+
+    pub fn is_attribute(&self) -> bool {
+        self.flavor == TypeFlavor::Attribute
+    }
+
+    /// Checks if this type is primary, as the kernel sees it, rather than an alias.
+    pub(crate) fn is_primary(&self) -> bool {
+        self.primary != 0 && !self.is_alias()
+    }
+
+    /// The primary type of an alias, or this type itself if it isn't an alias.  Aliases share the
+    /// id of their primary type.
+    pub fn primary<'a>(&'a self, policy: &'a Policy) -> Option<&'a Type> {
+        if self.is_alias() {
+            policy.types().get(self.id)
+        } else {
+            Some(self)
+        }
+    }
+
+    /// The id of the type that bounds this one, if any.
+    pub fn bounds_id(&self) -> Option<u32> {
+        self.bounds.filter(|bounds| *bounds != 0)
+    }
+
+    pub(crate) fn clear_bounds(&mut self) {
+        self.bounds = None;
+    }
+
+    /// The types associated with this type in a module.  For attributes these are the member
+    /// types, and are not present in kernel policies.
+    pub(crate) fn assoc_types(&self) -> Option<&Bitmap> {
+        self.assoc_types.as_ref()
+    }
+
+    /// Merges a declaration of the same attribute from another module into this one.
+    pub(crate) fn merge(&mut self, other: Type) {
+        if let (Some(types), Some(other_types)) = (self.assoc_types.as_mut(), other.assoc_types) {
+            types.or_inplace(&other_types);
+        }
+
+        self.flags |= other.flags;
+    }
+
+    /// Drops the module-only data from this type when it is expanded into a kernel policy.
+    pub(crate) fn expand(&mut self) {
+        self.assoc_types = None;
+
+        // Kernel policies mark every type and attribute as primary, and only aliases as not.
+        self.primary = if self.is_alias() { 0 } else { 1 };
+    }
+}
+
+impl Remap for Type {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.id = map.id(SymbolKind::Type, self.id);
+        self.bounds = self.bounds.map(|bounds| map.id(SymbolKind::Type, bounds));
+
+        if self.is_alias() && self.primary != 0 {
+            self.primary = map.id(SymbolKind::Type, self.primary);
+        }
+
+        if let Some(ref mut types) = self.assoc_types {
+            *types = map.bitmap(SymbolKind::Type, types);
+        }
+    }
+}
+
+impl TypeSet {
+    /// The 0-based bits of the concrete types in this set.  Attributes are replaced by their
+    /// member types, `-type` exclusions are removed, and the `*` and `~` flags of module type
+    /// sets select every type or the complement of the set.  Fails if the policy has a type with
+    /// an id outside of its types table.
+    pub fn expand(&self, policy: &Policy) -> Result<Bitmap, InvalidTypeId> {
+        Ok(self.expand_with(&AttributeTypes::new(policy, iter::empty())?))
+    }
+
+    pub(crate) fn expand_with(&self, attrs: &AttributeTypes) -> Bitmap {
+        match *self {
+            TypeSet::Bitmap(ref types) => attrs.expand(types),
+            TypeSet::Set {
+                ref types,
+                ref inverse_types,
+                flags,
+            } => {
+                let excluded = attrs.expand(inverse_types);
+
+                if flags & constants::TYPE_STAR != 0 {
+                    return attrs.types.andnot(&excluded);
+                }
+
+                let expanded = attrs.expand(types).andnot(&excluded);
+
+                if flags & constants::TYPE_COMP != 0 {
+                    attrs.types.andnot(&expanded)
+                } else {
+                    expanded
+                }
+            }
+        }
+    }
+
+    /// Checks if the type or attribute with the given id is listed in this set, without
+    /// expanding attributes or applying exclusions and flags.
+    pub(crate) fn lists(&self, id: u32) -> bool {
+        let types = match *self {
+            TypeSet::Bitmap(ref types) | TypeSet::Set { ref types, .. } => types,
+        };
+
+        id > 0 && types.contains(id - 1)
+    }
+
+    /// Adds the types from another set into this one.
+    pub(crate) fn merge(&mut self, other: &TypeSet) {
+        match (self, other) {
+            (&mut TypeSet::Bitmap(ref mut types), &TypeSet::Bitmap(ref other_types)) => {
+                types.or_inplace(other_types)
+            }
+            (
+                &mut TypeSet::Set {
+                    ref mut types,
+                    ref mut inverse_types,
+                    ..
+                },
+                &TypeSet::Set {
+                    types: ref other_types,
+                    inverse_types: ref other_inverse_types,
+                    ..
+                },
+            ) => {
+                types.or_inplace(other_types);
+                inverse_types.or_inplace(other_inverse_types);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Remap for TypeSet {
+    fn remap(&mut self, map: &SymbolMap) {
+        match *self {
+            TypeSet::Bitmap(ref mut types) => *types = map.bitmap(SymbolKind::Type, types),
+            TypeSet::Set {
+                ref mut types,
+                ref mut inverse_types,
+                ..
+            } => {
+                *types = map.bitmap(SymbolKind::Type, types);
+                *inverse_types = map.bitmap(SymbolKind::Type, inverse_types);
+            }
+        }
+    }
+}
+
 impl Symbol for Type {
     fn id(&self) -> u32 {
         self.id
@@ -51,6 +283,13 @@ impl Symbol for Type {
     fn name(&self) -> &str {
         self.name.as_str()
     }
+
+    /// checkmodule doesn't give the aliases of a module the alias flavor, they are the types
+    /// that aren't primary.  The flavor is kept as it was read, so that it is written back the
+    /// same way.
+    fn is_alias(&self) -> bool {
+        self.flavor == TypeFlavor::Alias || (self.flavor == TypeFlavor::Type && self.primary == 0)
+    }
 }
 
 impl PolicyObject for Type {
@@ -62,40 +301,48 @@ impl PolicyObject for Type {
 
         let (primary, flavor, flags, bounds) = if reader.profile().supports(Feature::Boundary) {
             let mut primary = if reader.profile().supports(Feature::BoundaryAlias) {
-                reader.read_u32()? == 1
+                reader.read_u32()?
             } else {
-                false
+                0
             };
 
-            let flags = TyFlags::empty();
             let properties = TyProperties::from_bits(reader.read_u32()?)
                 .ok_or(PolicyReadError::InvalidPolicyCapability)?;
 
-            let permissive = properties.contains(TyProperties::Permissive);
-            let flavor = if properties.contains(TyProperties::Attribute) {
-                Some(1) // todo: fix
-            } else if properties.contains(TyProperties::Alias) {
-                Some(2) // todo: fix
+            // Kernel policies record permissive types in a separate map instead.
+            let flags = if properties.contains(TyProperties::Permissive) && !is_kern_policy {
+                TyFlags::Permissive
             } else {
-                None
+                TyFlags::empty()
             };
-
             if properties.contains(TyProperties::Primary) {
-                primary = true;
+                primary = 1;
+            };
+
+            // Kernel policies don't mark aliases, they are the types that aren't primary.
+            let flavor = if properties.contains(TyProperties::Attribute) {
+                TypeFlavor::Attribute
+            } else if properties.contains(TyProperties::Alias) || (is_kern_policy && primary == 0) {
+                TypeFlavor::Alias
+            } else {
+                TypeFlavor::Type
             };
 
             let bounds = reader.read_u32()?;
 
             (primary, flavor, flags, Some(bounds))
         } else {
-            let primary = reader.read_u32()? == 1;
+            let primary = reader.read_u32()?;
             let flavor = if !is_kern_policy {
-                Some(reader.read_u32()?)
+                let flavor = reader.read_u32()?;
+                TypeFlavor::from_id(flavor).ok_or(PolicyReadError::InvalidTypeFlavor(flavor))?
+            } else if primary == 0 {
+                TypeFlavor::Alias
             } else {
-                None
+                TypeFlavor::Type
             };
 
-            let flags = if is_kern_policy {
+            let flags = if !is_kern_policy && reader.profile().supports(Feature::PermissiveTypes) {
                 TyFlags::from_bits(reader.read_u32()?)
                     .ok_or(PolicyReadError::InvalidPolicyCapability)?
             } else {
@@ -123,6 +370,59 @@ impl PolicyObject for Type {
             assoc_types,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        let is_kern_policy = writer.profile().ty().is_kernel_policy();
+
+        writer.write_len(self.name.len())?;
+        writer.write_u32(self.id)?;
+
+        if writer.profile().supports(Feature::Boundary) {
+            if writer.profile().supports(Feature::BoundaryAlias) {
+                writer.write_u32(self.primary)?;
+            }
+
+            let mut properties = TyProperties::empty();
+            properties.set(TyProperties::Primary, self.is_primary());
+            properties.set(TyProperties::Attribute, self.is_attribute());
+            properties.set(
+                TyProperties::Alias,
+                self.flavor == TypeFlavor::Alias && !is_kern_policy,
+            );
+            properties.set(
+                TyProperties::Permissive,
+                self.is_permissive() && !is_kern_policy,
+            );
+
+            writer.write_u32(properties.bits())?;
+            writer.write_u32(self.bounds.unwrap_or(0))?;
+        } else {
+            writer.write_u32(self.primary)?;
+
+            if !is_kern_policy {
+                writer.write_u32(self.flavor.id())?;
+
+                if writer.profile().supports(Feature::PermissiveTypes) {
+                    writer.write_u32(self.flags.bits())?;
+                } else if self.is_permissive() {
+                    return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                        Feature::PermissiveTypes,
+                    ));
+                }
+            }
+        }
+
+        if !is_kern_policy {
+            match self.assoc_types {
+                Some(ref assoc_types) => writer.write_object(assoc_types)?,
+                None => writer.write_object(&Bitmap::create())?,
+            }
+        }
+
+        writer.write_string(&self.name)?;
+
+        Ok(())
+    }
 }
 
 impl PolicyObject for TypeSet {
@@ -143,4 +443,287 @@ impl PolicyObject for TypeSet {
             })
         }
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        let is_kernel_policy = writer.profile().ty().is_kernel_policy();
+
+        match *self {
+            TypeSet::Bitmap(ref types) if is_kernel_policy => writer.write_object(types),
+            TypeSet::Bitmap(ref types) => {
+                writer.write_object(types)?;
+                writer.write_object(&Bitmap::create())?;
+                writer.write_u32(0)?;
+
+                Ok(())
+            }
+            TypeSet::Set { ref types, .. } if is_kernel_policy => writer.write_object(types),
+            TypeSet::Set {
+                ref types,
+                ref inverse_types,
+                flags,
+            } => {
+                writer.write_object(types)?;
+                writer.write_object(inverse_types)?;
+                writer.write_u32(flags)?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The concrete member types of every attribute in a policy, used to expand type sets.
+#[derive(Debug)]
+pub(crate) struct AttributeTypes {
+    attributes: Bitmap,
+    types: Bitmap,
+    members: Vec<Bitmap>,
+}
+
+impl AttributeTypes {
+    /// Collects the attribute memberships of a policy, along with those recorded on the copies
+    /// of attributes in `decl_types`.  Fails if a type's id is outside of the types table.
+    pub(crate) fn new<'a, I>(policy: &Policy, decl_types: I) -> Result<Self, InvalidTypeId>
+    where
+        I: Iterator<Item = &'a Type>,
+    {
+        let num_types = policy.types().num_primary_names();
+        let mut attributes = Bitmap::create();
+        let mut types = Bitmap::create();
+        let mut members = vec![Bitmap::create(); num_types as usize];
+
+        // The 0-based bit of a type, which has to be within the types table.
+        let bit_of = |ty: &Type| match ty.id().checked_sub(1) {
+            Some(bit) if bit < num_types => Ok(bit),
+            _ => Err(InvalidTypeId(ty.id())),
+        };
+
+        for ty in policy.types().all() {
+            let bit = bit_of(ty)?;
+
+            if !ty.is_attribute() {
+                types.add(bit);
+                continue;
+            }
+
+            attributes.add(bit);
+
+            // Kernel policies only record memberships in the type attribute map.
+            match ty.assoc_types() {
+                Some(assoc_types) => members[bit as usize].or_inplace(assoc_types),
+                None => {
+                    for member in policy.type_attr_map().types_of(ty.id()) {
+                        if member != ty.id() {
+                            members[bit as usize].add(member - 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        for ty in decl_types.filter(|ty| ty.is_attribute()) {
+            let bit = bit_of(ty)?;
+
+            if let Some(assoc_types) = ty.assoc_types() {
+                members[bit as usize].or_inplace(assoc_types);
+            }
+        }
+
+        // Attributes may contain other attributes, so keep replacing them with their members
+        // until only types remain.
+        loop {
+            let mut changed = false;
+
+            for attr in attributes.iter() {
+                let nested = members[attr as usize].and(&attributes);
+
+                if nested.is_empty() {
+                    continue;
+                }
+
+                let mut attr_members = members[attr as usize].andnot(&attributes);
+
+                for nested_attr in nested.iter().filter(|nested_attr| *nested_attr != attr) {
+                    attr_members.or_inplace(&members[nested_attr as usize]);
+                }
+
+                members[attr as usize] = attr_members;
+                changed = true;
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        Ok(AttributeTypes {
+            attributes,
+            types,
+            members,
+        })
+    }
+
+    /// The concrete member types of each attribute, indexed by the attribute's 0-based bit.
+    pub(crate) fn members(&self) -> &[Bitmap] {
+        &self.members
+    }
+
+    /// Replaces the attributes in a bitmap of types with their members.
+    pub(crate) fn expand(&self, types: &Bitmap) -> Bitmap {
+        let mut expanded = types.andnot(&self.attributes);
+
+        for attr in types.and(&self.attributes).iter() {
+            expanded.or_inplace(&self.members[attr as usize]);
+        }
+
+        expanded
+    }
+}
+
+/// A type with an id outside of the types table of its policy, so that it has no entry in the
+/// tables indexed by type.
+#[derive(Debug)]
+pub struct InvalidTypeId(pub u32);
+
+impl Error for InvalidTypeId {
+    fn description(&self) -> &str {
+        "Invalid type id"
+    }
+}
+
+impl fmt::Display for InvalidTypeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "type id {} is not in the types table", self.0)
+    }
+}
+
+/// The kernel's `type_attr_map`, recording the attributes each type belongs to, along with its
+/// inverse.  Every type is treated as a member of itself, as in the kernel.
+#[derive(Debug, Default)]
+pub struct TypeAttributeMap {
+    type_attr_map: Vec<Bitmap>,
+    attr_type_map: Vec<Bitmap>,
+}
+
+impl TypeAttributeMap {
+    /// Builds the map from the member types of each attribute, indexed by the attribute's id.
+    pub(crate) fn from_attributes(num_types: u32, attr_types: &[Bitmap]) -> Self {
+        let mut type_attr_map = vec![Bitmap::create(); num_types as usize];
+        let mut attr_type_map = vec![Bitmap::create(); num_types as usize];
+
+        for ty in 0..num_types {
+            type_attr_map[ty as usize].add(ty);
+            attr_type_map[ty as usize].add(ty);
+        }
+
+        for (attr, types) in attr_types.iter().enumerate() {
+            for ty in types.iter() {
+                if let Some(attributes) = type_attr_map.get_mut(ty as usize) {
+                    attributes.add(attr as u32);
+                    attr_type_map[attr].add(ty);
+                }
+            }
+        }
+
+        TypeAttributeMap {
+            type_attr_map,
+            attr_type_map,
+        }
+    }
+
+    /// Decodes one bitmap for each of the `num_types` types in the policy.  Policies older than
+    /// `POLICYDB_VERSION_AVTAB` don't have attributes, so every type only maps to itself.
+    pub fn decode_for_types<R: Read>(
+        reader: &mut PolicyReader<R>,
+        num_types: u32,
+    ) -> Result<Self, PolicyReadError> {
+        let mut type_attr_map = Vec::with_capacity(num_types as usize);
+        let mut attr_type_map = vec![Bitmap::create(); num_types as usize];
+
+        for ty in 0..num_types {
+            let mut attributes: Bitmap = if reader.profile().supports(Feature::AvTab) {
+                reader.read_object()?
+            } else {
+                Bitmap::create()
+            };
+
+            attributes.add(ty);
+
+            for attr in attributes.iter() {
+                if let Some(types) = attr_type_map.get_mut(attr as usize) {
+                    types.add(ty);
+                }
+            }
+
+            type_attr_map.push(attributes);
+        }
+
+        Ok(TypeAttributeMap {
+            type_attr_map,
+            attr_type_map,
+        })
+    }
+
+    /// Encodes the bitmap of each of the `num_types` types, the inverse of `decode_for_types`.
+    pub fn encode_for_types<W: Write>(
+        &self,
+        writer: &mut PolicyWriter<W>,
+        num_types: u32,
+    ) -> Result<(), PolicyWriteError> {
+        if !writer.profile().supports(Feature::AvTab) {
+            return Ok(());
+        }
+
+        for ty in 0..num_types {
+            match self.type_attr_map.get(ty as usize) {
+                Some(attributes) => writer.write_object(attributes)?,
+                None => writer.write_object(&Bitmap::of(&[ty]))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds an entry for a newly declared type, which is only a member of itself.
+    pub(crate) fn push_type(&mut self) {
+        let ty = self.type_attr_map.len() as u32;
+
+        self.type_attr_map.push(Bitmap::of(&[ty]));
+        self.attr_type_map.push(Bitmap::of(&[ty]));
+    }
+
+    /// Records the type with the given id as a member of the attribute with the given id.  Ids
+    /// without an entry in the map, including 0, are ignored.
+    pub(crate) fn add(&mut self, ty: u32, attr: u32) {
+        let (ty, attr) = match (ty.checked_sub(1), attr.checked_sub(1)) {
+            (Some(ty), Some(attr)) => (ty, attr),
+            _ => return,
+        };
+
+        if let Some(attributes) = self.type_attr_map.get_mut(ty as usize) {
+            attributes.add(attr);
+        }
+
+        if let Some(types) = self.attr_type_map.get_mut(attr as usize) {
+            types.add(ty);
+        }
+    }
+
+    /// The ids of the attributes containing the type with the given id, including itself.
+    pub fn attributes_of<'a>(&'a self, ty: u32) -> impl Iterator<Item = u32> + 'a {
+        Self::ids(&self.type_attr_map, ty)
+    }
+
+    /// The ids of the types contained in the attribute with the given id, including itself.
+    pub fn types_of<'a>(&'a self, attr: u32) -> impl Iterator<Item = u32> + 'a {
+        Self::ids(&self.attr_type_map, attr)
+    }
+
+    fn ids<'a>(map: &'a [Bitmap], id: u32) -> impl Iterator<Item = u32> + 'a {
+        id.checked_sub(1)
+            .and_then(|idx| map.get(idx as usize))
+            .into_iter()
+            .flat_map(|bitmap| bitmap.iter())
+            .map(|bit| bit + 1)
+    }
 }
@@ -1,3 +1,4 @@
+use croaring::Bitmap;
 use policydb::CompatibilityProfile;
 use policydb::Feature;
 use policydb::MlsLevel;
@@ -5,26 +6,100 @@ use policydb::MlsRange;
 use policydb::PolicyObject;
 use policydb::PolicyReadError;
 use policydb::PolicyReader;
+use policydb::PolicyWriteError;
+use policydb::PolicyWriter;
+use policydb::Remap;
 use policydb::RoleSet;
 use policydb::Symbol;
-use std::io::Read;
+use policydb::SymbolKind;
+use policydb::SymbolMap;
+use std::io::{Read, Write};
 
 #[derive(Debug)]
 pub struct User {
     id: u32,
     name: String,
-    default_level: MlsLevel,
-    range: MlsRange,
+    roles: RoleSet,
+    default_level: Option<MlsLevel>,
+    range: Option<MlsRange>,
     bounds: Option<u32>,
 }
 
 impl User {
-    pub fn default_level(&self) -> &MlsLevel {
-        &self.default_level
+    /// Creates a user for a kernel policy, authorized for the roles with the given 0-based bits.
+    pub(crate) fn new(
+        id: u32,
+        name: String,
+        roles: Bitmap,
+        default_level: Option<MlsLevel>,
+        range: Option<MlsRange>,
+    ) -> Self {
+        User {
+            id,
+            name,
+            roles: RoleSet::Bitmap(roles),
+            default_level,
+            range,
+            bounds: None,
+        }
+    }
+
+    /// The roles this user is authorized for.
+    pub fn roles(&self) -> &RoleSet {
+        &self.roles
+    }
+
+    pub fn default_level(&self) -> Option<&MlsLevel> {
+        self.default_level.as_ref()
+    }
+
+    pub fn range(&self) -> Option<&MlsRange> {
+        self.range.as_ref()
+    }
+
+    /// The id of the user that bounds this one, if any.
+    pub fn bounds_id(&self) -> Option<u32> {
+        self.bounds.filter(|bounds| *bounds != 0)
+    }
+
+    pub(crate) fn clear_bounds(&mut self) {
+        self.bounds = None;
+    }
+}
+
+impl User {
+    /// Merges a declaration of the same user from another module into this one.
+    pub(crate) fn merge(&mut self, other: User) {
+        if let (
+            &mut RoleSet::Set { ref mut roles, .. },
+            RoleSet::Set {
+                roles: other_roles, ..
+            },
+        ) = (&mut self.roles, other.roles)
+        {
+            roles.or_inplace(&other_roles);
+        }
+    }
+
+    /// Replaces the module role set of this user with its expanded roles.
+    pub(crate) fn expand(&mut self, roles: Bitmap) {
+        self.roles = RoleSet::Bitmap(roles);
     }
+}
+
+impl Remap for User {
+    fn remap(&mut self, map: &SymbolMap) {
+        self.id = map.id(SymbolKind::User, self.id);
+        self.bounds = self.bounds.map(|bounds| map.id(SymbolKind::User, bounds));
+        self.roles.remap(map);
 
-    pub fn range(&self) -> &MlsRange {
-        &self.range
+        if let Some(ref mut level) = self.default_level {
+            level.remap(map);
+        }
+
+        if let Some(ref mut range) = self.range {
+            range.remap(map);
+        }
     }
 }
 
@@ -49,26 +124,60 @@ impl PolicyObject for User {
         };
 
         let name = reader.read_string(name_len)?;
-        let role_set: RoleSet = reader.read_object()?;
+        let roles: RoleSet = reader.read_object()?;
 
         let is_mls_supported = reader.profile().supports(Feature::Mls);
         let is_mls_users_supported = reader.profile().supports(Feature::MlsUsers);
 
-        let (range, default_level) = if is_mls_supported && is_mls_users_supported {
+        // Modules store users with semantic MLS ranges once `MOD_POLICYDB_VERSION_MLS_USERS` is
+        // supported, everything else uses the same format as the kernel.
+        let (range, default_level) = if is_mls_users_supported {
             (
-                MlsRange::decode_expanded(reader)?,
-                MlsLevel::decode_expanded(reader)?,
+                Some(MlsRange::decode_expanded(reader)?),
+                Some(MlsLevel::decode_expanded(reader)?),
             )
+        } else if is_mls_supported {
+            (Some(reader.read_object()?), Some(reader.read_object()?))
         } else {
-            (reader.read_object()?, reader.read_object()?)
+            (None, None)
         };
 
         Ok(User {
             id,
             name,
+            roles,
             range,
             default_level,
             bounds,
         })
     }
+
+    fn encode<W: Write>(&self, writer: &mut PolicyWriter<W>) -> Result<(), PolicyWriteError> {
+        writer.write_len(self.name.len())?;
+        writer.write_u32(self.id)?;
+
+        if writer.profile().supports(Feature::Boundary) {
+            writer.write_u32(self.bounds.unwrap_or(0))?;
+        }
+
+        writer.write_string(&self.name)?;
+        writer.write_object(&self.roles)?;
+
+        if !writer.profile().supports(Feature::Mls) {
+            return Ok(());
+        }
+
+        let (range, default_level) = match (self.range.as_ref(), self.default_level.as_ref()) {
+            (Some(range), Some(default_level)) => (range, default_level),
+            _ => return Err(PolicyWriteError::UnsupportedFeatureUsed(Feature::Mls)),
+        };
+
+        if writer.profile().supports(Feature::MlsUsers) {
+            range.encode_expanded(writer)?;
+            default_level.encode_expanded(writer)
+        } else {
+            writer.write_object(range)?;
+            writer.write_object(default_level)
+        }
+    }
 }
@@ -0,0 +1,339 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use croaring::Bitmap;
+use std::error::Error;
+use std::fmt;
+use std::io::Error as IoError;
+use std::io::Write;
+
+use policydb::constants::*;
+use policydb::*;
+
+/// Encodes the policy representation into the binary format read by `PolicyReader`.
+pub struct PolicyWriter<W: Write> {
+    buf: W,
+    profile: Option<CompatibilityProfile>,
+}
+
+#[derive(Debug)]
+pub enum PolicyWriteError {
+    InvalidVersion(u32),
+    /// A kernel policy holds module data, or a module is missing it.
+    MismatchedPolicyType,
+    OutputError(IoError),
+    UnsupportedFeatureUsed(Feature),
+}
+
+impl From<IoError> for PolicyWriteError {
+    fn from(output_error: IoError) -> Self {
+        PolicyWriteError::OutputError(output_error)
+    }
+}
+
+impl Error for PolicyWriteError {
+    fn description(&self) -> &str {
+        "Unable to write policy"
+    }
+}
+
+impl fmt::Display for PolicyWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PolicyWriteError::InvalidVersion(version) => {
+                write!(f, "unsupported policy version {}", version)
+            }
+            PolicyWriteError::MismatchedPolicyType => {
+                write!(f, "policy contents don't match the policy type")
+            }
+            PolicyWriteError::OutputError(ref err) => write!(f, "{}", err),
+            PolicyWriteError::UnsupportedFeatureUsed(feature) => {
+                write!(f, "{:?} is not supported by the policy version", feature)
+            }
+        }
+    }
+}
+
+impl<W: Write> PolicyWriter<W> {
+    pub fn new(buf: W) -> Self {
+        PolicyWriter { buf, profile: None }
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> Result<(), IoError> {
+        self.buf.write_u8(value)
+    }
+
+    pub fn write_u16(&mut self, value: u16) -> Result<(), IoError> {
+        self.buf.write_u16::<LittleEndian>(value)
+    }
+
+    pub fn write_u32(&mut self, value: u32) -> Result<(), IoError> {
+        self.buf.write_u32::<LittleEndian>(value)
+    }
+
+    pub fn write_u64(&mut self, value: u64) -> Result<(), IoError> {
+        self.buf.write_u64::<LittleEndian>(value)
+    }
+
+    pub fn write_bytes(&mut self, buf: &[u8]) -> Result<(), IoError> {
+        self.buf.write_all(buf)
+    }
+
+    /// Writes the bytes of a string.  As with `PolicyReader::read_string`, the length is written
+    /// separately by the caller.
+    pub fn write_string(&mut self, value: &str) -> Result<(), IoError> {
+        self.buf.write_all(value.as_bytes())
+    }
+
+    /// Writes the length of a collection as a 32-bit count.
+    pub fn write_len(&mut self, len: usize) -> Result<(), IoError> {
+        self.write_u32(len as u32)
+    }
+
+    pub fn write_bare_symbol_table<S>(
+        &mut self,
+        table: &SymbolTable<S>,
+    ) -> Result<(), PolicyWriteError>
+    where
+        S: Symbol,
+    {
+        for sym in table.ordered() {
+            sym.encode(self)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_symbol_table<S>(&mut self, table: &SymbolTable<S>) -> Result<(), PolicyWriteError>
+    where
+        S: Symbol,
+    {
+        self.write_u32(table.num_primary_names())?;
+        self.write_len(table.num_entries())?;
+        self.write_bare_symbol_table(table)
+    }
+
+    /// Writes the types table of a kernel policy older than `POLICYDB_VERSION_BOUNDARY`, which
+    /// the kernel can't load attributes from.  Like libsepol, the attributes are left out of the
+    /// table but keep their ids, which are still counted and used by the type attribute map.
+    fn write_types_without_attributes(
+        &mut self,
+        types: &SymbolTable<Type>,
+    ) -> Result<(), PolicyWriteError> {
+        self.write_u32(types.num_primary_names())?;
+        self.write_len(types.ordered().filter(|ty| !ty.is_attribute()).count())?;
+
+        for ty in types.ordered().filter(|ty| !ty.is_attribute()) {
+            ty.encode(self)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_object<O: PolicyObject>(&mut self, object: &O) -> Result<(), PolicyWriteError> {
+        object.encode(self)
+    }
+
+    pub fn write_objects<O: PolicyObject>(&mut self, list: &[O]) -> Result<(), PolicyWriteError> {
+        for object in list {
+            object.encode(self)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a policy in the format of the version recorded in its `CompatibilityProfile`.
+    /// Writing a policy that was read by `PolicyReader` gives back the same bytes.
+    pub fn write_policy(mut self, policy: &Policy) -> Result<W, PolicyWriteError> {
+        let profile = policy.profile().clone();
+
+        if !profile.is_supported_version() {
+            return Err(PolicyWriteError::InvalidVersion(profile.version()));
+        }
+
+        if policy.config().mls_enabled && !profile.supports(Feature::Mls) {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(Feature::Mls));
+        }
+
+        self.profile = Some(profile);
+
+        let (magic, platform) = match *self.profile().ty() {
+            PolicyType::Kernel(PolicyTargetPlatform::SELinux) => {
+                (SELINUX_MAGIC_NUMBER, PLATFORM_SELINUX)
+            }
+            PolicyType::Kernel(PolicyTargetPlatform::Xen) => (SELINUX_MAGIC_NUMBER, PLATFORM_XEN),
+            PolicyType::Module { .. } => (SELINUX_MOD_MAGIC_NUMBER, PLATFORM_SELINUX_MODULE),
+        };
+
+        self.write_u32(magic)?;
+        self.write_len(platform.len())?;
+        self.write_string(platform)?;
+
+        if let PolicyType::Module { is_base_module, .. } = *self.profile().ty() {
+            self.write_u32(if is_base_module {
+                POLICY_BASE
+            } else {
+                POLICY_MOD
+            })?;
+        }
+
+        let num_sym_tables = self.profile().num_symbol_tables();
+        let num_ocon_tables = self.profile().num_ocontext_tables();
+
+        self.write_u32(self.profile().version())?;
+        self.write_u32(policy.config().bits())?;
+        self.write_u32(num_sym_tables)?;
+        self.write_u32(num_ocon_tables)?;
+
+        if let PolicyType::Module {
+            is_base_module: false,
+            ref name,
+            ref version,
+        } = *policy.ty()
+        {
+            self.write_len(name.len())?;
+            self.write_string(name)?;
+            self.write_len(version.len())?;
+            self.write_string(version)?;
+        }
+
+        let is_kernel_policy = self.profile().ty().is_kernel_policy();
+
+        self.write_object(policy.polcaps())?;
+
+        if is_kernel_policy && self.profile().supports(Feature::PermissiveTypes) {
+            // The kernel indexes the permissive map by type id, not by the 0-based type bit.
+            let permissive_types: Bitmap = policy.permissive_types().map(|ty| ty.id()).collect();
+            self.write_object(&permissive_types)?;
+        } else if is_kernel_policy && policy.permissive_types().next().is_some() {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::PermissiveTypes,
+            ));
+        }
+
+        self.write_symbol_table(policy.common_classes())?;
+        self.write_symbol_table(policy.classes())?;
+        self.write_symbol_table(policy.roles())?;
+
+        if is_kernel_policy && !self.profile().supports(Feature::Boundary) {
+            self.write_types_without_attributes(policy.types())?;
+        } else {
+            self.write_symbol_table(policy.types())?;
+        }
+
+        self.write_symbol_table(policy.users())?;
+
+        if self.profile().supports(Feature::Booleans) {
+            self.write_symbol_table(policy.booleans())?;
+        } else if policy.booleans().num_entries() > 0 {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(Feature::Booleans));
+        }
+
+        if !is_kernel_policy || self.profile().supports(Feature::Mls) {
+            self.write_symbol_table(policy.sensitivities())?;
+            self.write_symbol_table(policy.categories())?;
+        }
+
+        match policy.module() {
+            None if is_kernel_policy => self.write_kernel_rules(policy)?,
+            Some(module) if !is_kernel_policy => self.write_object(module)?,
+            _ => return Err(PolicyWriteError::MismatchedPolicyType),
+        }
+
+        policy
+            .ocontexts()
+            .encode_tables(&mut self, num_ocon_tables)?;
+
+        // See `PolicyReader::read_policy`, base modules only kept range transitions in the kernel
+        // format before `MOD_POLICYDB_VERSION_RANGETRANS`.
+        let has_range_transitions = match *self.profile().ty() {
+            PolicyType::Kernel(_) => true,
+            PolicyType::Module { is_base_module, .. } => {
+                is_base_module && !self.profile().supports(Feature::RangeTransitionClass)
+            }
+        };
+
+        if has_range_transitions && self.profile().supports(Feature::Mls) {
+            self.write_len(policy.range_transitions().len())?;
+            self.write_objects(policy.range_transitions())?;
+        }
+
+        if is_kernel_policy {
+            let num_types = policy.types().num_primary_names();
+            policy
+                .type_attr_map()
+                .encode_for_types(&mut self, num_types)?;
+        }
+
+        Ok(self.buf)
+    }
+
+    fn write_kernel_rules(&mut self, policy: &Policy) -> Result<(), PolicyWriteError> {
+        policy.avtab().encode_merged(self)?;
+
+        if self.profile().supports(Feature::Booleans) {
+            self.write_len(policy.conditionals().len())?;
+            self.write_objects(policy.conditionals())?;
+        }
+
+        self.write_len(policy.role_transitions().len())?;
+        self.write_objects(policy.role_transitions())?;
+        self.write_len(policy.role_allows().len())?;
+        self.write_objects(policy.role_allows())?;
+
+        let filename_transitions = policy.filename_transitions();
+
+        if self
+            .profile()
+            .supports(Feature::CompressedFilenameTransitions)
+        {
+            // Transitions that share a name, target type and class are written as a single
+            // entry, in the same way as they were read.
+            let entries = group_consecutive(filename_transitions, |a, b| a.shares_entry_with(b));
+
+            self.write_len(entries.len())?;
+
+            for entry in entries {
+                FilenameTransition::encode_compressed(self, entry)?;
+            }
+        } else if self.profile().supports(Feature::FilenameTransitions) {
+            let num_transitions: usize = filename_transitions
+                .iter()
+                .map(|trans| trans.source_type_ids().count())
+                .sum();
+
+            self.write_len(num_transitions)?;
+            self.write_objects(filename_transitions)?;
+        } else if !filename_transitions.is_empty() {
+            return Err(PolicyWriteError::UnsupportedFeatureUsed(
+                Feature::FilenameTransitions,
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn profile(&self) -> &CompatibilityProfile {
+        self.profile
+            .as_ref()
+            .expect("Compatibility profile is uninitialized")
+    }
+}
+
+/// Splits a list into runs of consecutive items that belong together, for tables that the policy
+/// format groups under a shared header.  Each item is compared with the one before it, so the
+/// predicate doesn't have to be transitive.
+pub(crate) fn group_consecutive<T, F>(items: &[T], same_group: F) -> Vec<&[T]>
+where
+    F: Fn(&T, &T) -> bool,
+{
+    let mut groups = vec![];
+    let mut start = 0;
+
+    for idx in 1..=items.len() {
+        if idx == items.len() || !same_group(&items[idx - 1], &items[idx]) {
+            groups.push(&items[start..idx]);
+            start = idx;
+        }
+    }
+
+    groups
+}
@@ -0,0 +1,115 @@
+extern crate croaring;
+extern crate sepolrs;
+
+use croaring::Bitmap;
+use sepolrs::policydb::{EBitmap, PolicyReadError, PolicyReader, PolicyWriter};
+
+/// Encodes an ebitmap with the given high bit and `(start_bit, map)` nodes.
+fn ebitmap_data(high_bit: u32, nodes: &[(u32, u64)]) -> Vec<u8> {
+    let mut data = vec![];
+    {
+        let mut writer = PolicyWriter::new(&mut data);
+
+        writer.write_u32(64).unwrap();
+        writer.write_u32(high_bit).unwrap();
+        writer.write_u32(nodes.len() as u32).unwrap();
+
+        for &(start_bit, map) in nodes {
+            writer.write_u32(start_bit).unwrap();
+            writer.write_u64(map).unwrap();
+        }
+    }
+
+    data
+}
+
+fn decode(data: &[u8]) -> Result<EBitmap, PolicyReadError> {
+    PolicyReader::new(data).read_object()
+}
+
+fn encode(ebitmap: &EBitmap) -> Vec<u8> {
+    let mut data = vec![];
+
+    PolicyWriter::new(&mut data).write_object(ebitmap).unwrap();
+
+    data
+}
+
+fn assert_invalid(high_bit: u32, nodes: &[(u32, u64)]) {
+    match decode(&ebitmap_data(high_bit, nodes)) {
+        Err(PolicyReadError::InvalidBitmap) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn round_trip() {
+    for &(high_bit, ref nodes) in &[
+        (0, vec![]),
+        (64, vec![(0, 0b101)]),
+        (192, vec![(0, 1), (128, 1 << 63)]),
+        // A high bit past the last node is allowed.
+        (256, vec![(64, 2)]),
+    ] {
+        let data = ebitmap_data(high_bit, nodes);
+        let ebitmap = decode(&data).unwrap();
+
+        assert!(ebitmap.is_valid());
+        assert_eq!(ebitmap.high_bit(), high_bit);
+        assert_eq!(encode(&ebitmap), data);
+    }
+}
+
+#[test]
+fn misaligned_start_bit() {
+    assert_invalid(128, &[(32, 1)]);
+}
+
+#[test]
+fn out_of_order_nodes() {
+    assert_invalid(192, &[(128, 1), (0, 1)]);
+    assert_invalid(64, &[(0, 1), (0, 2)]);
+}
+
+#[test]
+fn empty_nodes() {
+    assert_invalid(64, &[(0, 0)]);
+    assert_invalid(128, &[(0, 1), (64, 0)]);
+}
+
+#[test]
+fn mismatched_high_bit() {
+    assert_invalid(64, &[(0, 1), (128, 1)]);
+    assert_invalid(100, &[(0, 1)]);
+    assert_invalid(64, &[]);
+}
+
+#[test]
+fn bitmap_conversion() {
+    let bitmap = Bitmap::of(&[0, 2, 63, 64, 200]);
+    let ebitmap = EBitmap::from(&bitmap);
+
+    assert!(ebitmap.is_valid());
+    assert_eq!(ebitmap.high_bit(), 256);
+    assert_eq!(
+        ebitmap
+            .nodes()
+            .iter()
+            .map(|node| (node.start_bit(), node.map()))
+            .collect::<Vec<_>>(),
+        vec![(0, 1 << 63 | 0b101), (64, 1), (192, 1 << 8)]
+    );
+    assert_eq!(ebitmap.iter().collect::<Vec<_>>(), bitmap.to_vec());
+    assert_eq!(Bitmap::from(&ebitmap).to_vec(), bitmap.to_vec());
+
+    // Bitmaps are read and written through the same format.
+    let data = encode(&ebitmap);
+    let decoded: Bitmap = PolicyReader::new(&data[..]).read_object().unwrap();
+
+    assert_eq!(decoded.to_vec(), bitmap.to_vec());
+
+    let empty = EBitmap::from(&Bitmap::create());
+
+    assert!(empty.is_empty() && empty.is_valid());
+    assert_eq!(empty.high_bit(), 0);
+}
@@ -0,0 +1,90 @@
+//! Helpers shared by the integration tests, for reading the fixtures and naming the symbols of
+//! the policies they contain.
+
+#![allow(dead_code)]
+
+use sepolrs::policydb::{Policy, PolicyReader, PolicyWriter, SecurityContext, Symbol, SymbolKind};
+
+pub const KERNEL_V33: &[u8] = include_bytes!("../fixtures/policy.33");
+pub const KERNEL_MLS_V33: &[u8] = include_bytes!("../fixtures/policy_mls.33");
+
+pub fn read(data: &[u8]) -> Policy {
+    PolicyReader::new(data)
+        .read_policy()
+        .expect("failed to read policy")
+}
+
+pub fn write(policy: &Policy) -> Vec<u8> {
+    PolicyWriter::new(vec![])
+        .write_policy(policy)
+        .expect("failed to write policy")
+}
+
+/// Writes a policy and reads it back, so that checks see what was actually written.
+pub fn reload(policy: &Policy) -> Policy {
+    read(&write(policy))
+}
+
+pub fn id(policy: &Policy, kind: SymbolKind, name: &str) -> u32 {
+    let id = match kind {
+        SymbolKind::User => policy.users().get_by_name(name).map(Symbol::id),
+        SymbolKind::Role => policy.roles().get_by_name(name).map(Symbol::id),
+        SymbolKind::Type => policy.types().get_by_name(name).map(Symbol::id),
+        SymbolKind::Class => policy.classes().get_by_name(name).map(Symbol::id),
+        SymbolKind::Boolean => policy.booleans().get_by_name(name).map(Symbol::id),
+        _ => None,
+    };
+
+    id.unwrap_or_else(|| panic!("{:?} {} is not declared", kind, name))
+}
+
+/// A context without a range from its `user:role:type` names.
+pub fn context(policy: &Policy, context: &str) -> SecurityContext {
+    let names: Vec<&str> = context.split(':').collect();
+
+    SecurityContext::new(
+        id(policy, SymbolKind::User, names[0]),
+        id(policy, SymbolKind::Role, names[1]),
+        id(policy, SymbolKind::Type, names[2]),
+        None,
+    )
+}
+
+/// The `user:role:type` names of a context.
+pub fn names(policy: &Policy, context: &SecurityContext) -> String {
+    format!(
+        "{}:{}:{}",
+        policy.symbol_name(SymbolKind::User, context.user_id()).unwrap(),
+        policy.symbol_name(SymbolKind::Role, context.role_id()).unwrap(),
+        policy.symbol_name(SymbolKind::Type, context.type_id()).unwrap()
+    )
+}
+
+/// The access vector of the named permissions of a class, including those of its common.
+pub fn permissions(policy: &Policy, class: &str, permissions: &[&str]) -> u32 {
+    let class = policy.classes().get_by_name(class).unwrap();
+    let common = class
+        .common_name()
+        .and_then(|common| policy.common_classes().get_by_name(common));
+
+    permissions.iter().fold(0, |av, name| {
+        let perm = class
+            .permissions()
+            .get_by_name(name)
+            .or_else(|| common.and_then(|common| common.permissions().get_by_name(name)))
+            .unwrap_or_else(|| panic!("permission {} is not declared", name));
+
+        av | 1 << (perm.id() - 1)
+    })
+}
+
+/// The permissions of a class that `source` has on `target`, given as `user:role:type` names.
+pub fn allowed(policy: &Policy, source: &str, target: &str, class: &str) -> u32 {
+    policy
+        .compute_av(
+            &context(policy, source),
+            &context(policy, target),
+            id(policy, SymbolKind::Class, class),
+        )
+        .allowed()
+}
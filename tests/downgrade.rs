@@ -0,0 +1,94 @@
+extern crate sepolrs;
+
+mod common;
+
+use common::*;
+use sepolrs::policydb::{downgrade, DowngradeError, DowngradeReport, Feature, Policy};
+
+fn discarded(report: &DowngradeReport) -> Vec<(Feature, usize)> {
+    report
+        .discarded()
+        .iter()
+        .map(|discarded| (discarded.feature(), discarded.count()))
+        .collect()
+}
+
+/// Downgrades the fixture policy, and reads back what was written at the new version.
+fn downgrade_to(version: u32) -> (Policy, DowngradeReport) {
+    let (policy, report) = downgrade(read(KERNEL_V33), version).expect("failed to downgrade");
+    let data = write(&policy);
+    let policy = read(&data);
+
+    assert_eq!(policy.version(), version);
+    assert_eq!(write(&policy), data);
+
+    (policy, report)
+}
+
+/// Checks that rules on attributes still apply to their types.
+fn assert_attribute_rules_apply(policy: &Policy) {
+    assert_eq!(
+        allowed(policy, "system_u:system_r:init_t", "system_u:object_r:tmp_t", "file"),
+        permissions(policy, "file", &["read", "getattr", "open"])
+    );
+}
+
+#[test]
+fn downgrade_v24() {
+    let (policy, report) = downgrade_to(24);
+
+    assert_eq!(
+        discarded(&report),
+        vec![
+            (Feature::FilenameTransitions, 3),
+            (Feature::XpermsIoctl, 4),
+            (Feature::RoleTransitionClass, 1),
+            (Feature::NewObjectDefaults, 4),
+            (Feature::DefaultType, 1),
+        ]
+    );
+
+    assert!(policy.filename_transitions().is_empty());
+    assert_eq!(policy.role_transitions().len(), 1);
+    assert_eq!(policy.type_attributes().count(), 2);
+    assert_eq!(policy.permissive_types().count(), 1);
+    assert_attribute_rules_apply(&policy);
+}
+
+#[test]
+fn downgrade_v20() {
+    let (policy, report) = downgrade_to(20);
+
+    assert_eq!(
+        discarded(&report),
+        vec![
+            (Feature::FilenameTransitions, 3),
+            (Feature::XpermsIoctl, 4),
+            (Feature::RoleTransitionClass, 1),
+            (Feature::PermissiveTypes, 1),
+            (Feature::PolicyCapabilities, 2),
+            (Feature::Boundary, 1),
+            (Feature::NewObjectDefaults, 4),
+            (Feature::DefaultType, 1),
+        ]
+    );
+
+    assert_eq!(policy.permissive_types().count(), 0);
+    assert!(policy.polcaps().all().is_empty());
+
+    // The attributes are left out of the types table, but the type attribute map still has them.
+    assert!(policy.types().get_by_name("domain").is_none());
+    assert!(policy.types().get_by_name("file_type").is_none());
+    assert_attribute_rules_apply(&policy);
+}
+
+#[test]
+fn downgrade_v15() {
+    match downgrade(read(KERNEL_V33), 15) {
+        Err(DowngradeError::UnrepresentableFeatures(features)) => assert_eq!(
+            features,
+            vec![Feature::Booleans, Feature::ValidateTransition, Feature::AvTab]
+        ),
+        result => panic!("unexpected result {:?}", result.map(|(_, report)| report)),
+    }
+}
@@ -0,0 +1,195 @@
+extern crate sepolrs;
+
+mod common;
+
+use common::*;
+use sepolrs::policydb::{AvRuleKind, PolicyEditError, SymbolKind};
+
+#[test]
+fn add_av_rule_merges_permissions() {
+    let mut policy = read(KERNEL_V33);
+
+    policy
+        .add_av_rule(AvRuleKind::Allow, "user_t", "etc_t", "file", &["write"])
+        .unwrap();
+    policy
+        .add_av_rule(AvRuleKind::Allow, "user_t", "etc_t", "file", &["append"])
+        .unwrap();
+
+    let policy = reload(&policy);
+
+    assert_eq!(
+        allowed(&policy, "user_u:user_r:user_t", "system_u:object_r:etc_t", "file"),
+        permissions(&policy, "file", &["read", "getattr", "open", "write", "append"])
+    );
+}
+
+#[test]
+fn add_av_rule_rejects_type_rules() {
+    let mut policy = read(KERNEL_V33);
+
+    match policy.add_av_rule(AvRuleKind::TypeTransition, "user_t", "etc_t", "file", &[]) {
+        Err(PolicyEditError::InvalidRuleKind(AvRuleKind::TypeTransition)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn remove_allowed() {
+    let mut policy = read(KERNEL_V33);
+
+    assert!(policy
+        .remove_allowed("user_t", "user_home_t", "file", &["write"])
+        .unwrap());
+    assert!(!policy
+        .remove_allowed("user_t", "user_home_t", "file", &["write"])
+        .unwrap());
+
+    let policy = reload(&policy);
+    let allowed = allowed(
+        &policy,
+        "user_u:user_r:user_t",
+        "system_u:object_r:user_home_t",
+        "file",
+    );
+
+    assert_eq!(allowed & permissions(&policy, "file", &["write"]), 0);
+    assert_eq!(
+        allowed & permissions(&policy, "file", &["create", "read"]),
+        permissions(&policy, "file", &["create", "read"])
+    );
+}
+
+#[test]
+fn add_type_rule_conflict() {
+    let mut policy = read(KERNEL_V33);
+
+    policy
+        .add_type_rule(
+            AvRuleKind::TypeTransition,
+            "user_t",
+            "tmp_t",
+            "file",
+            "user_home_t",
+        )
+        .unwrap();
+
+    match policy.add_type_rule(
+        AvRuleKind::TypeTransition,
+        "user_t",
+        "tmp_t",
+        "file",
+        "etc_t",
+    ) {
+        Err(PolicyEditError::ConflictingTypeRule { .. }) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn set_permissive() {
+    let mut policy = read(KERNEL_V33);
+
+    policy.set_permissive("child_t", true).unwrap();
+    policy.set_permissive("user_t", false).unwrap();
+
+    let policy = reload(&policy);
+    let is_permissive = |source: &str| {
+        policy
+            .compute_av(
+                &context(&policy, source),
+                &context(&policy, "system_u:object_r:etc_t"),
+                id(&policy, SymbolKind::Class, "file"),
+            )
+            .is_permissive()
+    };
+
+    assert!(is_permissive("system_u:system_r:child_t"));
+    assert!(!is_permissive("user_u:user_r:user_t"));
+}
+
+#[test]
+fn set_boolean_default() {
+    let mut policy = read(KERNEL_V33);
+    let states: Vec<bool> = policy.conditionals().iter().map(|cond| !cond.state()).collect();
+
+    policy.set_boolean_default("allow_exec", false).unwrap();
+    policy.set_boolean_default("allow_write", true).unwrap();
+
+    let policy = reload(&policy);
+    let exec = permissions(&policy, "file", &["execute"]);
+    let write = permissions(&policy, "file", &["write"]);
+
+    assert_eq!(
+        allowed(&policy, "user_u:user_r:user_t", "system_u:object_r:user_home_t", "file") & exec,
+        0
+    );
+    assert_eq!(
+        allowed(&policy, "user_u:user_r:user_t", "system_u:object_r:etc_t", "file") & write,
+        write
+    );
+
+    // Only the lists matching the new value of each expression are enabled.
+    for cond in policy.conditionals() {
+        let state = cond.state();
+
+        assert!(cond
+            .true_list()
+            .entries()
+            .iter()
+            .all(|entry| entry.key().is_enabled() == state));
+        assert!(cond
+            .false_list()
+            .entries()
+            .iter()
+            .all(|entry| entry.key().is_enabled() != state));
+    }
+
+    // Both expressions change value.
+    assert_eq!(
+        policy
+            .conditionals()
+            .iter()
+            .map(|cond| cond.state())
+            .collect::<Vec<_>>(),
+        states
+    );
+}
+
+#[test]
+fn add_types_and_attributes() {
+    let mut policy = read(KERNEL_V33);
+
+    policy.add_type("new_t").unwrap();
+    policy.add_attribute("new_attr").unwrap();
+    policy.add_type_attribute("new_t", "new_attr").unwrap();
+    policy.add_type_attribute("new_t", "domain").unwrap();
+    policy
+        .add_av_rule(
+            AvRuleKind::Allow,
+            "new_attr",
+            "user_t",
+            "process",
+            &["transition"],
+        )
+        .unwrap();
+
+    match policy.add_type_attribute("new_t", "user_t") {
+        Err(PolicyEditError::NotAnAttribute(_)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    let policy = reload(&policy);
+
+    // Rules on `domain` now apply to the new type.
+    assert_eq!(
+        allowed(&policy, "system_u:system_r:new_t", "system_u:object_r:etc_t", "file"),
+        permissions(&policy, "file", &["read", "getattr", "open"])
+    );
+
+    // So does the process transition constraint, which allows `domain` to change user.
+    assert_eq!(
+        allowed(&policy, "system_u:system_r:new_t", "user_u:user_r:user_t", "process"),
+        permissions(&policy, "process", &["transition"])
+    );
+}
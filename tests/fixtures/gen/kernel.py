@@ -0,0 +1,91 @@
+"""Compiles `policy.cil` into the kernel policy fixtures."""
+import os
+
+from libsepol import compile_cil
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+# Statements added to `policy.cil` from the version that supports them.
+NEWER = {
+    27: "(defaultuser file source)\n(defaultrole process target)\n"
+        "(defaultrange dir target low-high)\n",
+    28: "(defaulttype file target)\n",
+    30: "(allowx user_t user_home_t (ioctl file (0x5401 0x5402 (range 0x8900 0x89ff))))\n"
+        "(auditallowx user_t user_home_t (ioctl file (0x5401)))\n"
+        "(dontauditx user_t etc_t (ioctl file (0x1234)))\n",
+    32: "(defaultrange tcp_socket glblub)\n",
+}
+
+# Statements of `policy.cil` that version 19 can't hold.
+NOT_IN_V19 = [
+    "(policycap network_peer_controls)",
+    "(policycap open_perms)",
+    "(typebounds init_t child_t)",
+    "(typepermissive user_t)",
+    '(typetransition user_t tmp_t dir "cache" user_home_t)',
+    '(typetransition init_t tmp_t file "pidfile" etc_t)',
+    '(typetransition kernel_t tmp_t file "pidfile" etc_t)',
+    "(roletransition system_r tmp_t file object_r)",
+]
+
+MLS_LEVELS = """(sensitivity s0)
+(sensitivity s1)
+(sensitivityalias unclassified)
+(sensitivityaliasactual unclassified s0)
+(sensitivityorder (s0 s1))
+(category c0)
+(category c1)
+(category c2)
+(categoryalias cat_zero)
+(categoryaliasactual cat_zero c0)
+(categoryorder (c0 c1 c2))
+(sensitivitycategory s0 (c0 c1))
+(sensitivitycategory s1 (range c0 c2))"""
+
+MLS_RULES = """
+(mls true)
+(rangetransition kernel_t init_exec_t process ((s0) (s1 (c0 c1))))
+(rangetransition user_t tmp_t file ((s0 (c1)) (s0 (c1))))
+(mlsconstrain (file (read write)) (or (dom l1 l2) (eq t1 init_t)))
+(mlsconstrain (process (transition)) (and (domby h1 h2) (incomp l1 h2)))
+(mlsvalidatetrans file (or (eq l1 l2) (and (dom h1 l2) (eq t3 init_t))))
+"""
+
+
+def policy(version):
+    with open(os.path.join(HERE, "policy.cil")) as f:
+        text = f.read()
+
+    return text + "".join(extra for v, extra in sorted(NEWER.items()) if v <= version)
+
+
+def v19():
+    lines = policy(19).split("\n")
+    return "\n".join(line for line in lines if line not in NOT_IN_V19)
+
+
+def mls():
+    text = policy(24)
+    text = text.replace(
+        "(sensitivity s0)\n(sensitivityorder (s0))\n(category c0)\n(categoryorder (c0))\n"
+        "(sensitivitycategory s0 (c0))",
+        MLS_LEVELS,
+    )
+    text = text.replace(
+        "(userrange system_u ((s0) (s0 (c0))))", "(userrange system_u ((s0) (s1 (range c0 c2))))"
+    )
+    return text + MLS_RULES + "".join(NEWER[v] for v in sorted(NEWER))
+
+
+def write(name, data):
+    with open(os.path.join(HERE, "..", name), "wb") as f:
+        f.write(data)
+
+
+if __name__ == "__main__":
+    write("policy.19", compile_cil(v19(), 19))
+
+    for version in (24, 30, 33):
+        write("policy.%d" % version, compile_cil(policy(version), version))
+
+    write("policy_mls.33", compile_cil(mls(), 33, mls=True))
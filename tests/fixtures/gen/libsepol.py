@@ -0,0 +1,47 @@
+"""The libsepol calls used to write the fixtures, through ctypes."""
+import ctypes
+
+lib = ctypes.CDLL("libsepol.so.2")
+c_p = ctypes.c_void_p
+
+POLICY_BASE = 1
+
+
+def _image(pdb):
+    out = c_p()
+    size = ctypes.c_size_t()
+    assert lib.sepol_policydb_to_image(None, pdb, ctypes.byref(out), ctypes.byref(size)) == 0
+    return ctypes.string_at(out, size.value)
+
+
+def compile_cil(text, version, mls=False):
+    """Compiles CIL into a kernel policy, as `secilc -c <version> [-M true]` does."""
+    db = c_p()
+    lib.cil_db_init(ctypes.byref(db))
+    lib.cil_set_log_level(2)
+    lib.cil_set_policy_version(db, version)
+    lib.cil_set_mls(db, 1 if mls else 0)
+    data = text.encode()
+    assert lib.cil_add_file(db, b"policy.cil", data, len(data)) == 0
+    assert lib.cil_compile(db) == 0
+    pdb = c_p()
+    assert lib.cil_build_policydb(db, ctypes.byref(pdb)) == 0
+    return _image(pdb)
+
+
+def rewrite(data, version):
+    """Loads a policy or module and writes it back at the given version."""
+    pdb = c_p()
+    assert lib.sepol_policydb_create(ctypes.byref(pdb)) == 0
+    assert lib.sepol_policydb_from_image(None, data, len(data), pdb) == 0
+    assert lib.sepol_policydb_set_vers(pdb, version) == 0
+    return _image(pdb)
+
+
+def empty_base(version):
+    """The base module of a freshly created policydb."""
+    pdb = c_p()
+    assert lib.sepol_policydb_create(ctypes.byref(pdb)) == 0
+    assert lib.sepol_policydb_set_typevers(pdb, POLICY_BASE) == 0
+    assert lib.sepol_policydb_set_vers(pdb, version) == 0
+    return _image(pdb)
@@ -0,0 +1,163 @@
+"""Encodes a base and a non-base module by hand, as checkmodule would write them, and lets
+libsepol write them back at the fixture versions."""
+import os
+import struct
+
+from libsepol import empty_base, rewrite
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+
+def u32(*xs): return b"".join(struct.pack("<I", x) for x in xs)
+def s(x): return x.encode()
+
+def ebitmap(bits):
+    bits = sorted(bits)
+    nodes = {}
+    for b in bits:
+        nodes.setdefault(b - b % 64, 0)
+        nodes[b - b % 64] |= 1 << (b % 64)
+    high = (max(nodes) + 64) if nodes else 0
+    out = u32(64, high, len(nodes))
+    for start in sorted(nodes):
+        out += u32(start) + struct.pack("<Q", nodes[start])
+    return out
+
+def typeset(bits, neg=(), flags=0): return ebitmap(bits) + ebitmap(neg) + u32(flags)
+def roleset(bits, flags=0): return ebitmap(bits) + u32(flags)
+def sem_level(sens=0, cats=()): return u32(sens, len(cats)) + b"".join(u32(l, h) for l, h in cats)
+
+def context(user, role, ty): return u32(user, role, ty) + u32(1, 0) + ebitmap([])
+
+def symtab(nprim, entries): return u32(nprim, len(entries)) + b"".join(entries)
+def empty_symtabs(): return b"".join(symtab(0, []) for _ in range(8))
+
+def common(name, value, perms):
+    return u32(len(name), value, len(perms), len(perms)) + s(name) + b"".join(u32(len(p), v) + s(p) for p, v in perms)
+
+def cls(name, value, perms, nprim, common_name="", cons=b"", ncons=0, base=True):
+    out = u32(len(name), len(common_name), value, nprim, len(perms), ncons) + s(name) + s(common_name)
+    out += b"".join(u32(len(p), v) + s(p) for p, v in perms)
+    out += cons
+    if not base:
+        return out
+    return out + u32(0) + u32(0, 0, 0, 0)  # no validatetrans, default user/role/range/type
+
+def role(name, value, types, dominates, bounds=0, flavor=0, roles=()):
+    return u32(len(name), value, bounds) + s(name) + ebitmap(dominates) + typeset(types) + u32(flavor) + ebitmap(roles)
+
+def ty(name, value, primary, props, bounds=0, types=()):
+    return u32(len(name), value, primary, props, bounds) + ebitmap(types) + s(name)
+
+def user(name, value, roles, bounds=0):
+    return u32(len(name), value, bounds) + s(name) + roleset(roles) + sem_level() + sem_level() + sem_level()
+
+def boolean(name, value, state, flags=0): return u32(value, state, len(name)) + s(name) + u32(flags)
+
+def avrule(spec, src, tgt, perms, flags=0):
+    return u32(spec, flags) + typeset(src) + typeset(tgt) + u32(len(perms)) + b"".join(u32(c, d) for c, d in perms)
+
+def avrules(rules): return u32(len(rules)) + b"".join(rules)
+
+def scope_index(syms, class_perms=()):
+    return b"".join(ebitmap(syms.get(i, ())) for i in range(8)) + u32(len(class_perms)) + b"".join(ebitmap(p) for p in class_perms)
+
+def decl(decl_id, enabled, conds=b"", nconds=0, rules=(), role_trs=(), role_allows=(), fname=(), required={}, declared={}, class_perms=()):
+    out = u32(decl_id, enabled, nconds) + conds + avrules(list(rules))
+    out += u32(len(role_trs)) + b"".join(role_trs)
+    out += u32(len(role_allows)) + b"".join(role_allows)
+    out += u32(len(fname)) + b"".join(fname)
+    out += u32(0)  # range transitions
+    return out + scope_index(required, class_perms) + scope_index(declared) + empty_symtabs()
+
+def scopes(tables):
+    out = b""
+    for i in range(8):
+        entries = tables.get(i, [])
+        out += u32(len(entries))
+        for name, kind, ids in entries:
+            out += u32(len(name)) + s(name) + u32(kind, len(ids)) + u32(*ids)
+    return out
+
+COMMON, CLASS, ROLE, TYPE, USER, BOOL = range(6)
+DECL, REQ = 2, 1
+PRIMARY, ATTRIB, ALIAS, PERMISSIVE = 1, 2, 4, 8
+
+def header(ptype, version, ocon, name=None):
+    out = u32(0xf97cff8d, 15) + s("SE Linux Module") + u32(ptype, version, 4, 8, ocon)
+    if name:
+        out += u32(len(name[0])) + s(name[0]) + u32(len(name[1])) + s(name[1])
+    return out + ebitmap([1])  # open_perms
+
+def base(version):
+    out = header(1, version, 9)
+    out += symtab(1, [common("file", 1, [("read", 1), ("write", 2)])])
+    # constrain file read (u1 == u2)
+    cons = u32(1, 1) + u32(4, 1, 1)
+    out += symtab(2, [cls("file", 1, [("execute", 3)], 3, "file", cons, 1),
+                      cls("process", 2, [("transition", 1), ("signal", 2)], 2)])
+    out += symtab(2, [role("object_r", 1, [], [0]), role("system_r", 2, [0], [1])])
+    # checkmodule marks attributes as primary, and leaves aliases as types that aren't.
+    out += symtab(4, [ty("init_t", 1, 1, PRIMARY), ty("file_t", 2, 1, PRIMARY),
+                      ty("domain", 3, 1, PRIMARY | ATTRIB, types=[0]),
+                      ty("init_alias_t", 1, 0, 0),
+                      ty("child_t", 4, 1, PRIMARY | PERMISSIVE, bounds=1)])
+    out += symtab(1, [user("system_u", 1, [1])])
+    out += symtab(1, [boolean("secure_mode", 1, 1)])
+    out += symtab(0, []) + symtab(0, [])
+    true_list = avrules([avrule(1, [0], [1], [(1, 4)])])
+    false_list = avrules([avrule(8, [0], [1], [(1, 4)])])
+    conds = u32(1, 1) + u32(1, 1) + true_list + false_list + u32(0)
+    rules = [avrule(1, [2], [1], [(1, 3)]), avrule(2, [0], [1], [(1, 2)]),
+             avrule(0x10, [0], [1], [(1, 2)]), avrule(0x80, [1], [0], [(2, 1)])]
+    glob = decl(1, 1, conds, 1, rules=rules,
+                role_trs=[roleset([1]) + typeset([1]) + ebitmap([1]) + u32(2)],
+                role_allows=[roleset([1]) + roleset([0])],
+                fname=[u32(3) + s("foo") + typeset([0]) + typeset([1]) + u32(1, 2)
+                       + (u32(0) if version >= 21 else b"")],
+                declared={COMMON: [0], CLASS: [0, 1], ROLE: [0, 1], TYPE: [0, 1, 2, 3],
+                          USER: [0], BOOL: [0]})
+    # An optional block, enabled since the base provides what it requires.
+    opt = decl(2, 1, rules=[avrule(1, [0], [3], [(2, 1)])],
+               required={CLASS: [1], TYPE: [0, 3]}, class_perms=[[], [0]])
+    out += u32(2) + u32(1) + glob + u32(1) + opt
+    tnames = ["init_t", "file_t", "domain", "init_alias_t", "child_t"]
+    out += scopes({COMMON: [("file", DECL, [1])],
+                   CLASS: [("file", DECL, [1]), ("process", DECL, [1])],
+                   ROLE: [("object_r", DECL, [1]), ("system_r", DECL, [1])],
+                   TYPE: [(n, DECL, [1]) for n in tnames],
+                   USER: [("system_u", DECL, [1])],
+                   BOOL: [("secure_mode", DECL, [1])]})
+    # ocontexts: one initial sid, one port
+    out += u32(1) + u32(1) + context(1, 2, 1)
+    out += u32(0) * 1  # fs
+    out += u32(1) + u32(6, 22, 22) + context(1, 1, 2)  # port
+    out += u32(0) * 6
+    return out + u32(0)  # genfs
+
+def module(version):
+    out = header(2, version, 0, ("example", "1.0"))
+    out += symtab(0, [])
+    out += symtab(1, [cls("file", 1, [("read", 1)], 1, base=False)])
+    out += symtab(1, [role("object_r", 1, [], [0])])
+    out += symtab(2, [ty("example_t", 1, 1, PRIMARY), ty("file_t", 2, 1, PRIMARY)])
+    out += symtab(0, []) + symtab(0, []) + symtab(0, []) + symtab(0, [])
+    glob = decl(1, 1, rules=[avrule(1, [0], [1], [(1, 1)])],
+                required={CLASS: [0], ROLE: [0], TYPE: [1]}, class_perms=[[0]],
+                declared={TYPE: [0]})
+    out += u32(1) + u32(1) + glob
+    out += scopes({CLASS: [("file", REQ, [1])], ROLE: [("object_r", REQ, [1])],
+                   TYPE: [("example_t", DECL, [1]), ("file_t", REQ, [1])]})
+    return out + u32(0)  # genfs
+
+
+def write(name, data):
+    with open(os.path.join(HERE, "..", name), "wb") as f:
+        f.write(data)
+
+
+if __name__ == "__main__":
+    write("base.4.mod", empty_base(4))
+    write("base.21.mod", rewrite(base(21), 21))
+    write("module.12.mod", rewrite(module(21), 12))
+    write("module.21.mod", rewrite(module(21), 21))
@@ -0,0 +1,167 @@
+extern crate sepolrs;
+
+use sepolrs::policydb::{
+    expand, link, AccessVector, AvRuleKind, Policy, PolicyBuilder, PolicyReader, PolicyWriter,
+};
+
+const KERNEL_V19: &[u8] = include_bytes!("fixtures/policy.19");
+const KERNEL_V24: &[u8] = include_bytes!("fixtures/policy.24");
+const KERNEL_V30: &[u8] = include_bytes!("fixtures/policy.30");
+const KERNEL_V33: &[u8] = include_bytes!("fixtures/policy.33");
+const KERNEL_MLS_V33: &[u8] = include_bytes!("fixtures/policy_mls.33");
+const BASE_V4: &[u8] = include_bytes!("fixtures/base.4.mod");
+const BASE_V21: &[u8] = include_bytes!("fixtures/base.21.mod");
+const MODULE_V12: &[u8] = include_bytes!("fixtures/module.12.mod");
+const MODULE_V21: &[u8] = include_bytes!("fixtures/module.21.mod");
+
+fn read(data: &[u8]) -> Policy {
+    PolicyReader::new(data)
+        .read_policy()
+        .expect("failed to read policy")
+}
+
+fn write(policy: &Policy) -> Vec<u8> {
+    PolicyWriter::new(vec![])
+        .write_policy(policy)
+        .expect("failed to write policy")
+}
+
+fn assert_round_trips(data: &[u8]) {
+    assert_eq!(write(&read(data)), data);
+}
+
+fn assert_write_read_round_trips(policy: &Policy) {
+    let data = write(policy);
+
+    assert_eq!(write(&read(&data)), data);
+}
+
+#[test]
+fn kernel_policy_v19() {
+    assert_round_trips(KERNEL_V19);
+}
+
+#[test]
+fn kernel_policy_v24() {
+    assert_round_trips(KERNEL_V24);
+}
+
+#[test]
+fn kernel_policy_v30() {
+    assert_round_trips(KERNEL_V30);
+}
+
+#[test]
+fn kernel_policy_v33() {
+    assert_round_trips(KERNEL_V33);
+}
+
+#[test]
+fn kernel_policy_mls_v33() {
+    assert_round_trips(KERNEL_MLS_V33);
+}
+
+#[test]
+fn base_module_v4() {
+    assert_round_trips(BASE_V4);
+}
+
+#[test]
+fn base_module_v21() {
+    assert_round_trips(BASE_V21);
+}
+
+#[test]
+fn module_v12() {
+    assert_round_trips(MODULE_V12);
+}
+
+#[test]
+fn module_v21() {
+    assert_round_trips(MODULE_V21);
+}
+
+#[test]
+fn built_policy() {
+    let policy = PolicyBuilder::new(33)
+        .common("file", &["read", "write"])
+        .class("file", Some("file"), &["execute"])
+        .class("process", None, &["transition", "signal"])
+        .ty("init_t")
+        .ty("file_t")
+        .type_alias("init_alias_t", "init_t")
+        .attribute("domain")
+        .type_attribute("init_t", "domain")
+        .permissive("init_t")
+        .role("system_r", &["init_t"])
+        .user("system_u", &["system_r"])
+        .boolean("secure_mode", true)
+        .allow("domain", "file_t", "file", &["read", "write"])
+        .allow_if("secure_mode", "init_t", "file_t", "file", &["execute"])
+        .type_transition("init_t", "file_t", "process", "init_t")
+        .role_allow("system_r", "object_r")
+        .role_transition("system_r", "file_t", "process", "system_r")
+        .initial_sid(1, "system_u", "system_r", "init_t")
+        .build()
+        .expect("failed to build policy");
+
+    assert_write_read_round_trips(&policy);
+}
+
+#[test]
+fn expanded_policy() {
+    let linked = link(read(BASE_V21), vec![read(MODULE_V21)]).expect("failed to link modules");
+    let policy = expand(linked, 33).expect("failed to expand policy");
+
+    assert_write_read_round_trips(&policy);
+}
+
+fn avtab_rules(policy: &Policy) -> Vec<(u16, u16, u16, AvRuleKind, u32)> {
+    let mut rules: Vec<_> = policy
+        .avtab()
+        .entries()
+        .iter()
+        .map(|entry| {
+            let key = entry.key();
+            let datum = match *entry.av() {
+                AccessVector::Simple(datum) => datum,
+                AccessVector::Extended { .. } => panic!("unexpected extended permissions"),
+            };
+
+            (
+                key.source_type(),
+                key.target_type(),
+                key.target_class(),
+                key.kind(),
+                datum,
+            )
+        })
+        .collect();
+
+    rules.sort_by_key(|rule| (rule.0, rule.1, rule.2, rule.4));
+    rules
+}
+
+#[test]
+fn legacy_avtab_rule_order() {
+    // The rules are declared in a different order from the one the legacy avtab format stores
+    // their datums in, so they can't all be merged into a single entry.
+    let policy = PolicyBuilder::new(19)
+        .class("file", None, &["read", "write", "getattr"])
+        .class("process", None, &["transition"])
+        .ty("init_t")
+        .ty("file_t")
+        .role("system_r", &["init_t"])
+        .user("system_u", &["system_r"])
+        .allow("init_t", "file_t", "file", &["read", "getattr"])
+        .auditallow("init_t", "file_t", "file", &["read"])
+        .dontaudit("init_t", "file_t", "file", &["write"])
+        .initial_sid(1, "system_u", "system_r", "init_t")
+        .build()
+        .expect("failed to build policy");
+
+    let data = write(&policy);
+
+    assert_eq!(avtab_rules(&read(&data)), avtab_rules(&policy));
+    assert_write_read_round_trips(&policy);
+}
@@ -0,0 +1,335 @@
+extern crate croaring;
+extern crate sepolrs;
+
+mod common;
+
+use common::*;
+use croaring::Bitmap;
+use sepolrs::policydb::{
+    AccessDecision, AvRuleKind, ExtendedPermissionDecision, IoctlDecision, MlsLevel, MlsRange,
+    Policy, SecurityContext, Symbol, SymbolKind,
+};
+
+fn compute_create(policy: &Policy, source: &str, target: &str, class: &str) -> String {
+    let created = policy
+        .compute_create(
+            &context(policy, source),
+            &context(policy, target),
+            id(policy, SymbolKind::Class, class),
+            None,
+        )
+        .expect("failed to compute context");
+
+    names(policy, &created)
+}
+
+#[test]
+fn create_socket() {
+    let policy = read(KERNEL_V33);
+
+    for class in &["tcp_socket", "tun_socket"] {
+        assert_eq!(
+            compute_create(&policy, "user_u:user_r:user_t", "system_u:object_r:tmp_t", class),
+            "user_u:user_r:user_t"
+        );
+    }
+}
+
+#[test]
+fn create_object() {
+    let policy = read(KERNEL_V33);
+
+    assert_eq!(
+        compute_create(&policy, "user_u:user_r:user_t", "system_u:object_r:tmp_t", "dir"),
+        "user_u:object_r:tmp_t"
+    );
+    assert_eq!(
+        compute_create(&policy, "user_u:user_r:user_t", "system_u:object_r:tmp_t", "file"),
+        "user_u:object_r:user_home_t"
+    );
+}
+
+fn compute_av(policy: &Policy, source: &str, target: &str, class: &str) -> AccessDecision {
+    policy.compute_av(
+        &context(policy, source),
+        &context(policy, target),
+        id(policy, SymbolKind::Class, class),
+    )
+}
+
+#[test]
+fn allow_through_attributes() {
+    let policy = read(KERNEL_V33);
+
+    // `domain` may read `file_type`, and neither type has a rule of its own.
+    assert_eq!(
+        allowed(&policy, "system_u:system_r:init_t", "system_u:object_r:tmp_t", "file"),
+        permissions(&policy, "file", &["read", "getattr", "open"])
+    );
+    assert_eq!(
+        allowed(&policy, "system_u:system_r:init_t", "system_u:object_r:fs_t", "file"),
+        0
+    );
+}
+
+#[test]
+fn constraint_denies() {
+    let mut policy = read(KERNEL_V33);
+
+    policy
+        .add_av_rule(
+            AvRuleKind::Allow,
+            "domain",
+            "file_type",
+            "file",
+            &["relabelto"],
+        )
+        .unwrap();
+
+    let relabelto = permissions(&policy, "file", &["relabelto"]);
+
+    // Only `object_r` objects may be relabeled, and never by `user_t`.
+    assert_eq!(
+        allowed(&policy, "system_u:system_r:init_t", "system_u:object_r:etc_t", "file") & relabelto,
+        relabelto
+    );
+    assert_eq!(
+        allowed(&policy, "system_u:system_r:init_t", "system_u:system_r:etc_t", "file") & relabelto,
+        0
+    );
+    assert_eq!(
+        allowed(&policy, "user_u:user_r:user_t", "system_u:object_r:etc_t", "file") & relabelto,
+        0
+    );
+}
+
+#[test]
+fn audit_rules() {
+    let policy = read(KERNEL_V33);
+    let unlink = permissions(&policy, "file", &["unlink"]);
+    let write = permissions(&policy, "file", &["write"]);
+
+    let decision = compute_av(
+        &policy,
+        "user_u:user_r:user_t",
+        "system_u:object_r:user_home_t",
+        "file",
+    );
+
+    assert_eq!(decision.auditallow(), unlink);
+
+    let decision = compute_av(
+        &policy,
+        "user_u:user_r:user_t",
+        "system_u:object_r:etc_t",
+        "file",
+    );
+
+    assert_eq!(decision.allowed() & write, 0);
+    assert_eq!(decision.auditdeny() & write, 0);
+    assert_eq!(!decision.auditdeny(), write);
+}
+
+#[test]
+fn conditional_rules() {
+    let mut policy = read(KERNEL_V33);
+    let execute = permissions(&policy, "file", &["execute"]);
+    let source = "user_u:user_r:user_t";
+    let target = "system_u:object_r:user_home_t";
+
+    let decision = compute_av(&policy, source, target, "file");
+
+    assert_eq!(decision.allowed() & execute, execute);
+    assert_eq!(decision.auditdeny() & execute, execute);
+
+    policy.set_boolean_default("allow_exec", false).unwrap();
+
+    // The false branch doesn't audit the denial.
+    let decision = compute_av(&policy, source, target, "file");
+
+    assert_eq!(decision.allowed() & execute, 0);
+    assert_eq!(decision.auditdeny() & execute, 0);
+}
+
+#[test]
+fn bounded_type() {
+    let mut policy = read(KERNEL_V33);
+
+    // `init_t` bounds `child_t`, and can't write `etc_t` itself.
+    policy
+        .add_av_rule(AvRuleKind::Allow, "child_t", "etc_t", "file", &["write"])
+        .unwrap();
+
+    assert_eq!(
+        allowed(&policy, "system_u:system_r:child_t", "system_u:object_r:etc_t", "file"),
+        permissions(&policy, "file", &["read", "getattr", "open"])
+    );
+}
+
+#[test]
+fn validate_transition() {
+    let policy = read(KERNEL_V33);
+    let file = id(&policy, SymbolKind::Class, "file");
+    let validate = |old: &str, new: &str, task: &str| {
+        policy.validate_transition(
+            &context(&policy, old),
+            &context(&policy, new),
+            &context(&policy, task),
+            file,
+        )
+    };
+
+    assert!(validate(
+        "system_u:object_r:tmp_t",
+        "system_u:object_r:etc_t",
+        "user_u:user_r:user_t"
+    ));
+    assert!(validate(
+        "system_u:object_r:tmp_t",
+        "user_u:object_r:etc_t",
+        "system_u:system_r:init_t"
+    ));
+    assert!(!validate(
+        "system_u:object_r:tmp_t",
+        "user_u:object_r:etc_t",
+        "user_u:user_r:user_t"
+    ));
+}
+
+#[test]
+fn mls_context_categories() {
+    let policy = read(KERNEL_MLS_V33);
+    let level = |sensitivity: &str, categories: &[&str]| {
+        let categories = categories
+            .iter()
+            .map(|name| policy.categories().get_by_name(name).unwrap().id() - 1)
+            .collect::<Vec<_>>();
+
+        MlsLevel::new(
+            policy.sensitivities().get_by_name(sensitivity).unwrap().id(),
+            Bitmap::of(&categories),
+        )
+    };
+    let context = |low: MlsLevel| {
+        let context = context(&policy, "system_u:system_r:kernel_t");
+
+        SecurityContext::new(
+            context.user_id(),
+            context.role_id(),
+            context.type_id(),
+            Some(MlsRange::new(low, level("s1", &["c0", "c1", "c2"]))),
+        )
+    };
+
+    // `s0` may only be used with `c0` and `c1`.
+    assert!(policy.is_valid_context(&context(level("s0", &["c1"]))));
+    assert!(!policy.is_valid_context(&context(level("s0", &["c2"]))));
+}
+
+/// The fixture policy, with `user_t` allowed to use the `ioctl` permission on files.
+fn ioctl_policy() -> Policy {
+    let mut policy = read(KERNEL_V33);
+
+    policy
+        .add_av_rule(AvRuleKind::Allow, "user_t", "file_type", "file", &["ioctl"])
+        .unwrap();
+    policy
+        .add_av_rule(
+            AvRuleKind::AuditAllow,
+            "user_t",
+            "user_home_t",
+            "file",
+            &["ioctl"],
+        )
+        .unwrap();
+
+    policy
+}
+
+fn compute_ioctl(policy: &Policy, target: &str, cmd: u16) -> IoctlDecision {
+    policy.compute_ioctl(
+        &context(policy, "user_u:user_r:user_t"),
+        &context(policy, target),
+        id(policy, SymbolKind::Class, "file"),
+        cmd,
+    )
+}
+
+fn compute_xperms_decision(policy: &Policy, target: &str, driver: u8) -> ExtendedPermissionDecision {
+    policy.compute_xperms_decision(
+        &context(policy, "user_u:user_r:user_t"),
+        &context(policy, target),
+        id(policy, SymbolKind::Class, "file"),
+        driver,
+    )
+}
+
+#[test]
+fn xperms_whole_driver() {
+    let policy = ioctl_policy();
+    let xperms = compute_xperms_decision(&policy, "system_u:object_r:user_home_t", 0x89);
+
+    assert!((0..=255).all(|function| xperms.is_allowed(function)));
+    assert!(compute_ioctl(&policy, "system_u:object_r:user_home_t", 0x8942).is_allowed());
+}
+
+#[test]
+fn xperms_functions() {
+    let policy = ioctl_policy();
+    let xperms = compute_xperms_decision(&policy, "system_u:object_r:user_home_t", 0x54);
+
+    assert!(xperms.is_allowed(0x01));
+    assert!(xperms.is_allowed(0x02));
+    assert!(!xperms.is_allowed(0x03));
+
+    assert!(compute_ioctl(&policy, "system_u:object_r:user_home_t", 0x5402).is_allowed());
+    assert!(!compute_ioctl(&policy, "system_u:object_r:user_home_t", 0x5403).is_allowed());
+}
+
+#[test]
+fn xperms_without_rules() {
+    let policy = ioctl_policy();
+
+    // Without any extended permission rule every command is allowed by the `ioctl` permission.
+    assert!(compute_ioctl(&policy, "system_u:object_r:tmp_t", 0x1234).is_allowed());
+    assert!(compute_ioctl(&policy, "system_u:object_r:tmp_t", 0x8942).is_allowed());
+
+    // Without the permission no command is.
+    assert!(!compute_ioctl(&policy, "system_u:object_r:fs_t", 0x1234).is_allowed());
+}
+
+#[test]
+fn xperms_uncovered_driver() {
+    let policy = ioctl_policy();
+    let decision = compute_ioctl(&policy, "system_u:object_r:user_home_t", 0x1234);
+
+    assert!(!decision.is_allowed());
+    assert!(decision.is_audited());
+}
+
+#[test]
+fn xperms_audit_rules() {
+    let policy = ioctl_policy();
+    let xperms = compute_xperms_decision(&policy, "system_u:object_r:user_home_t", 0x54);
+
+    assert!(xperms.is_auditallow(0x01));
+    assert!(!xperms.is_auditallow(0x02));
+
+    let decision = compute_ioctl(&policy, "system_u:object_r:user_home_t", 0x5401);
+
+    assert!(decision.is_allowed() && decision.is_audited());
+
+    let decision = compute_ioctl(&policy, "system_u:object_r:user_home_t", 0x5402);
+
+    assert!(decision.is_allowed() && !decision.is_audited());
+
+    // A `dontauditx` rule is the only rule for `etc_t`, so it also denies the other commands.
+    let xperms = compute_xperms_decision(&policy, "system_u:object_r:etc_t", 0x12);
+
+    assert!(xperms.is_dontaudit(0x34));
+    assert!(compute_ioctl(&policy, "system_u:object_r:etc_t", 0x1234).is_dontaudited());
+
+    let decision = compute_ioctl(&policy, "system_u:object_r:etc_t", 0x1235);
+
+    assert!(!decision.is_allowed() && decision.is_audited());
+}
@@ -0,0 +1,222 @@
+extern crate croaring;
+extern crate sepolrs;
+
+mod common;
+
+use common::*;
+use croaring::Bitmap;
+use sepolrs::policydb::{
+    expand, link, Policy, PolicyBuilder, PolicyReader, Symbol, SymbolKind, TypeFlavor, TypeSet,
+};
+
+/// The `*` and `~` flags of module type sets.
+const TYPE_STAR: u32 = 1;
+const TYPE_COMP: u32 = 2;
+
+const BASE_V21: &[u8] = include_bytes!("fixtures/base.21.mod");
+
+/// A policy whose `all_types` attribute contains the `domain` and `file_type` attributes.
+fn nested_policy() -> Policy {
+    PolicyBuilder::new(33)
+        .class("file", None, &["read"])
+        .class("process", None, &["transition"])
+        .ty("init_t")
+        .ty("kernel_t")
+        .ty("etc_t")
+        .ty("tmp_t")
+        .attribute("domain")
+        .attribute("file_type")
+        .attribute("all_types")
+        .type_attribute("init_t", "domain")
+        .type_attribute("kernel_t", "domain")
+        .type_attribute("etc_t", "file_type")
+        .type_attribute("tmp_t", "file_type")
+        .type_attribute("domain", "all_types")
+        .type_attribute("file_type", "all_types")
+        .role("system_r", &["domain"])
+        .user("system_u", &["system_r"])
+        .initial_sid(1, "system_u", "system_r", "kernel_t")
+        .build()
+        .expect("failed to build policy")
+}
+
+/// The 0-based bits of the named types, as stored in type sets.
+fn bits(policy: &Policy, names: &[&str]) -> Bitmap {
+    let bits: Vec<u32> = names
+        .iter()
+        .map(|name| id(policy, SymbolKind::Type, name) - 1)
+        .collect();
+
+    Bitmap::of(&bits)
+}
+
+fn set(policy: &Policy, types: &[&str], inverse_types: &[&str], flags: u32) -> TypeSet {
+    TypeSet::Set {
+        types: bits(policy, types),
+        inverse_types: bits(policy, inverse_types),
+        flags,
+    }
+}
+
+fn expand_set(policy: &Policy, set: &TypeSet) -> Vec<u32> {
+    set.expand(policy).expect("failed to expand type set").to_vec()
+}
+
+#[test]
+fn expand_attributes() {
+    let policy = nested_policy();
+
+    assert_eq!(
+        expand_set(&policy, &TypeSet::Bitmap(bits(&policy, &["domain", "etc_t"]))),
+        bits(&policy, &["init_t", "kernel_t", "etc_t"]).to_vec()
+    );
+
+    // Attributes in attributes are replaced by their members too.
+    assert_eq!(
+        expand_set(&policy, &TypeSet::Bitmap(bits(&policy, &["all_types"]))),
+        bits(&policy, &["init_t", "kernel_t", "etc_t", "tmp_t"]).to_vec()
+    );
+}
+
+#[test]
+fn expand_negated_set() {
+    let policy = nested_policy();
+
+    assert_eq!(
+        expand_set(&policy, &set(&policy, &["all_types"], &["file_type"], 0)),
+        bits(&policy, &["init_t", "kernel_t"]).to_vec()
+    );
+    assert_eq!(
+        expand_set(&policy, &set(&policy, &["domain", "etc_t"], &["kernel_t"], 0)),
+        bits(&policy, &["init_t", "etc_t"]).to_vec()
+    );
+}
+
+#[test]
+fn expand_star() {
+    let policy = nested_policy();
+
+    assert_eq!(
+        expand_set(&policy, &set(&policy, &[], &[], TYPE_STAR)),
+        bits(&policy, &["init_t", "kernel_t", "etc_t", "tmp_t"]).to_vec()
+    );
+    assert_eq!(
+        expand_set(&policy, &set(&policy, &[], &["domain"], TYPE_STAR)),
+        bits(&policy, &["etc_t", "tmp_t"]).to_vec()
+    );
+}
+
+#[test]
+fn expand_complement() {
+    let policy = nested_policy();
+
+    assert_eq!(
+        expand_set(&policy, &set(&policy, &["file_type"], &[], TYPE_COMP)),
+        bits(&policy, &["init_t", "kernel_t"]).to_vec()
+    );
+
+    // The exclusions are applied before the complement is taken.
+    assert_eq!(
+        expand_set(&policy, &set(&policy, &["all_types"], &["tmp_t"], TYPE_COMP)),
+        bits(&policy, &["tmp_t"]).to_vec()
+    );
+}
+
+#[test]
+fn expand_invalid_type_id() {
+    // Claim that the base module's types table holds 3 types, leaving `child_t` (id 4) outside
+    // of it.  The table's header is followed by the name length of `init_alias_t`.
+    let mut data = BASE_V21.to_vec();
+    let header: Vec<u8> = [4u32, 5, 12].iter().flat_map(|n| n.to_le_bytes()).collect();
+    let offset = data
+        .windows(header.len())
+        .position(|window| window == &header[..])
+        .expect("types table not found");
+
+    data[offset] = 3;
+
+    let policy = PolicyReader::new(&data[..])
+        .read_policy()
+        .expect("failed to read module");
+
+    match TypeSet::Bitmap(Bitmap::create()).expand(&policy) {
+        Err(err) => assert_eq!(err.0, 4),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+/// Checks that `init_alias_t` is an alias of `init_t`, and `domain` an attribute.
+fn assert_module_types(policy: &Policy) {
+    let alias = policy
+        .types()
+        .aliases()
+        .find(|alias| alias.name() == "init_alias_t")
+        .expect("alias not found");
+
+    assert_eq!(alias.flavor(), TypeFlavor::Alias);
+    assert_eq!(alias.primary(policy).map(Symbol::name), Some("init_t"));
+    assert_eq!(
+        policy.types().get_by_name("init_alias_t").map(Symbol::name),
+        Some("init_t")
+    );
+    assert_eq!(
+        policy.types().get_by_name("domain").map(|ty| ty.flavor()),
+        Some(TypeFlavor::Attribute)
+    );
+}
+
+#[test]
+fn module_aliases() {
+    // checkmodule writes aliases as types that aren't primary, rather than with the alias flavor.
+    let policy = read(BASE_V21);
+
+    assert_module_types(&policy);
+
+    let policy = expand(link(policy, vec![]).unwrap(), 33).expect("failed to expand policy");
+
+    assert_module_types(&reload(&policy));
+}
+
+fn attribute_map(policy: &Policy) -> Vec<(Vec<u32>, Vec<u32>)> {
+    (1..policy.types().next_id())
+        .map(|id| {
+            (
+                policy.type_attr_map().attributes_of(id).collect(),
+                policy.type_attr_map().types_of(id).collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn type_attribute_map() {
+    let policy = nested_policy();
+    let type_id = |name| id(&policy, SymbolKind::Type, name);
+    let map = policy.type_attr_map();
+
+    assert_eq!(
+        map.attributes_of(type_id("init_t")).collect::<Vec<_>>(),
+        vec![type_id("init_t"), type_id("domain")]
+    );
+    assert_eq!(
+        map.types_of(type_id("file_type")).collect::<Vec<_>>(),
+        vec![type_id("etc_t"), type_id("tmp_t"), type_id("file_type")]
+    );
+
+    // Nothing is recorded for ids outside of the types table.
+    assert_eq!(map.attributes_of(0).count(), 0);
+    assert_eq!(map.types_of(policy.types().next_id()).count(), 0);
+}
+
+#[test]
+fn type_attribute_map_round_trip() {
+    for policy in &[nested_policy(), read(KERNEL_V33)] {
+        let reloaded = reload(policy);
+
+        assert_eq!(attribute_map(&reloaded), attribute_map(policy));
+
+        for ty in reloaded.types().all() {
+            assert!(reloaded.type_attr_map().attributes_of(ty.id()).any(|id| id == ty.id()));
+        }
+    }
+}